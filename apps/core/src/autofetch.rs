@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use core_lib::git::remote;
+use core_lib::store::repos::{self, now, RepoRecord};
+use core_lib::store::settings;
+use git2::Repository;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_specta::Event;
+use uuid::Uuid;
+
+use crate::events::{IncomingBranch, IncomingCommits};
+use crate::jobs::{JobKind, JobManager};
+
+/// How often the scheduler checks which repositories are due.
+const TICK: Duration = Duration::from_secs(30);
+/// Upper bound for the delay after repeated authentication failures.
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+
+/// Power and network state reported by the frontend, used to pause auto-fetch.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Type)]
+pub struct PowerConditions {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Type)]
+pub struct AutoFetchStatus {
+    pub repo_id: Uuid,
+    /// Unix timestamps in seconds.
+    pub last_attempt: Option<i64>,
+    pub last_success: Option<i64>,
+    pub next_attempt: Option<i64>,
+    /// Consecutive authentication failures; each one doubles the interval.
+    pub auth_failures: u32,
+    pub last_error: Option<String>,
+    pub in_flight: bool,
+}
+
+#[derive(Default)]
+struct AutoFetchState {
+    conditions: PowerConditions,
+    repos: HashMap<Uuid, AutoFetchStatus>,
+}
+
+/// Periodically fetches every registered repository in the background.
+///
+/// Managed as Tauri state; the scheduler thread is started with [`AutoFetcher::start`].
+#[derive(Clone, Default)]
+pub struct AutoFetcher {
+    state: Arc<Mutex<AutoFetchState>>,
+}
+
+impl AutoFetcher {
+    pub fn start<R: Runtime>(&self, app: AppHandle<R>) {
+        let fetcher = self.clone();
+        thread::Builder::new()
+            .name("gitultra-autofetch".to_string())
+            .spawn(move || loop {
+                fetcher.tick(&app);
+                thread::sleep(TICK);
+            })
+            .expect("Failed to spawn auto-fetch scheduler");
+    }
+
+    pub fn set_conditions(&self, conditions: PowerConditions) {
+        self.state.lock().unwrap().conditions = conditions;
+    }
+
+    pub fn status(&self) -> Vec<AutoFetchStatus> {
+        self.state.lock().unwrap().repos.values().cloned().collect()
+    }
+
+    fn tick<R: Runtime>(&self, app: &AppHandle<R>) {
+        let settings = match settings::load_settings(app) {
+            Ok(settings) => settings.auto_fetch,
+            Err(e) => {
+                error!("Failed to load auto-fetch settings: {}", e);
+                return;
+            }
+        };
+        let conditions = self.state.lock().unwrap().conditions;
+        if (settings.pause_on_battery && conditions.on_battery) || (settings.pause_on_metered && conditions.metered) {
+            return;
+        }
+        let registry = match repos::load_registry(app) {
+            Ok(registry) => registry,
+            Err(e) => {
+                error!("Failed to load repo registry: {}", e);
+                return;
+            }
+        };
+
+        let current = now();
+        for record in registry.repos {
+            let Some(interval) = settings.interval_for(record.id) else {
+                continue;
+            };
+            let due = {
+                let mut state = self.state.lock().unwrap();
+                let status = state.repos.entry(record.id).or_insert_with(|| AutoFetchStatus {
+                    repo_id: record.id,
+                    ..Default::default()
+                });
+                let next = status
+                    .last_attempt
+                    .map(|last| last + backoff(interval as i64, status.auth_failures));
+                status.next_attempt = next;
+                let due = !status.in_flight && next.is_none_or(|next| next <= current);
+                if due {
+                    status.in_flight = true;
+                    status.last_attempt = Some(current);
+                }
+                due
+            };
+            if due {
+                self.fetch(app, record);
+            }
+        }
+    }
+
+    fn fetch<R: Runtime>(&self, app: &AppHandle<R>, record: RepoRecord) {
+        let state = self.state.clone();
+        let in_flight = InFlight {
+            state: self.state.clone(),
+            repo_id: record.id,
+        };
+        let title = format!("Auto-fetch {}", record.name);
+        app.state::<JobManager>().spawn(app, JobKind::Fetch, title, move |ctx| {
+            let _in_flight = in_flight;
+            let result = Repository::open(&record.path).and_then(|repo| {
+                let summaries =
+                    remote::fetch_all(&repo, |p| ctx.progress(p.received_objects, Some(p.total_objects), None))?;
+                let mut branches = Vec::new();
+                for updated in summaries.iter().flat_map(|s| &s.updated_refs) {
+                    let Some(branch) = updated.name.strip_prefix("refs/remotes/") else {
+                        continue;
+                    };
+                    let new_commits = remote::count_incoming(&repo, updated)?;
+                    if new_commits > 0 {
+                        branches.push(IncomingBranch {
+                            branch: branch.to_string(),
+                            new_commits,
+                        });
+                    }
+                }
+                Ok(branches)
+            });
+
+            let mut state = state.lock().unwrap();
+            let status = state.repos.entry(record.id).or_default();
+            match result {
+                Ok(branches) => {
+                    status.auth_failures = 0;
+                    status.last_error = None;
+                    status.last_success = Some(now());
+                    drop(state);
+                    if !branches.is_empty() {
+                        info!("Auto-fetch of {:?} brought in new commits", record.path);
+                        let event = IncomingCommits {
+                            repo_id: record.id,
+                            path: record.path.clone(),
+                            branches,
+                        };
+                        if let Err(e) = event.emit(ctx.app()) {
+                            error!("Failed to emit incoming commits: {:?}", e);
+                        }
+                    }
+                    Ok(None)
+                }
+                Err(e) => {
+                    if remote::is_auth_error(&e) {
+                        status.auth_failures += 1;
+                        warn!(
+                            "Auto-fetch of {:?} failed to authenticate ({} in a row)",
+                            record.path, status.auth_failures
+                        );
+                    }
+                    status.last_error = Some(e.to_string());
+                    Err(e.to_string())
+                }
+            }
+        });
+    }
+}
+
+/// Marks a repository as no longer being fetched once dropped, so a job that is cancelled before it runs or panics
+/// doesn't keep it from being fetched again.
+struct InFlight {
+    state: Arc<Mutex<AutoFetchState>>,
+    repo_id: Uuid,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(status) = state.repos.get_mut(&self.repo_id) {
+                status.in_flight = false;
+            }
+        }
+    }
+}
+
+/// Doubles `interval` for every consecutive authentication failure, up to [`MAX_BACKOFF_SECS`].
+fn backoff(interval: i64, auth_failures: u32) -> i64 {
+    interval
+        .saturating_mul(1 << auth_failures.min(16))
+        .min(MAX_BACKOFF_SECS.max(interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_per_auth_failure_up_to_cap() {
+        assert_eq!(backoff(300, 0), 300);
+        assert_eq!(backoff(300, 1), 600);
+        assert_eq!(backoff(300, 3), 2400);
+        assert_eq!(backoff(300, 10), MAX_BACKOFF_SECS);
+        assert_eq!(backoff(300, u32::MAX), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_backoff_never_shortens_long_intervals() {
+        let day = 24 * 60 * 60;
+
+        assert_eq!(backoff(day, 0), day);
+        assert_eq!(backoff(day, 5), day);
+    }
+
+    #[test]
+    fn test_in_flight_cleared_on_drop() {
+        let fetcher = AutoFetcher::default();
+        let repo_id = Uuid::new_v4();
+        fetcher.state.lock().unwrap().repos.insert(
+            repo_id,
+            AutoFetchStatus {
+                repo_id,
+                in_flight: true,
+                ..Default::default()
+            },
+        );
+
+        drop(InFlight {
+            state: fetcher.state.clone(),
+            repo_id,
+        });
+
+        assert!(!fetcher.status()[0].in_flight);
+    }
+}
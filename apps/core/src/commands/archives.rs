@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use core_lib::git::archive::{self, ArchiveOptions};
+use git2::Repository;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::jobs::{JobId, JobKind, JobManager};
+
+/// Writes the tree of `rev` to the archive file `out` in the background, leaving out `export-ignore` paths. The
+/// archive summary is the job result.
+#[tauri::command]
+#[specta::specta]
+pub fn start_archive_export<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    rev: String,
+    options: ArchiveOptions,
+    out: PathBuf,
+) -> JobId {
+    let title = format!("Archive {} to {}", rev, out.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Archive, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let summary = archive::export_archive(&repo, &rev, &options, &out, |path, files| {
+            ctx.progress(files, None, Some(path.to_string()))
+        })
+        .map_err(|e| e.to_string())?;
+        ctx.log(format!(
+            "Archived {} files, {} left out",
+            summary.files, summary.ignored
+        ));
+        serde_json::to_value(summary).map(Some).map_err(|e| e.to_string())
+    })
+}
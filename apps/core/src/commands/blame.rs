@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use core_lib::git::blame::{self, BlameHunk, BlameOptions};
+use git2::Repository;
+use log::error;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_specta::Event;
+
+use crate::events::BlameChunk;
+use crate::jobs::{JobId, JobKind, JobManager};
+
+/// Blames a whole file (or line range) at once.
+#[tauri::command]
+#[specta::specta]
+pub fn get_blame(path: PathBuf, file: PathBuf, options: BlameOptions) -> Result<Vec<BlameHunk>, String> {
+    let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+    let mut hunks = Vec::new();
+    blame::blame_file(&repo, &file, &options, |chunk, _| {
+        hunks.extend(chunk);
+        true
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(hunks)
+}
+
+/// Blames a file in the background, emitting a [`BlameChunk`] event per `options.chunk_lines` lines.
+#[tauri::command]
+#[specta::specta]
+pub fn start_blame<T: Runtime>(app: AppHandle<T>, path: PathBuf, file: PathBuf, options: BlameOptions) -> JobId {
+    let title = format!("Blame {}", file.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Blame, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let mut blamed = 0;
+        blame::blame_file(&repo, &file, &options, |hunks, total| {
+            blamed += hunks.iter().map(|h| h.lines).sum::<u32>();
+            let event = BlameChunk {
+                job_id: ctx.id(),
+                file: file.clone(),
+                hunks,
+            };
+            if let Err(e) = event.emit(ctx.app()) {
+                error!("Failed to emit blame chunk: {:?}", e);
+            }
+            ctx.progress(blamed, Some(total), None)
+        })
+        .map_err(|e| e.to_string())?;
+        Ok(None)
+    })
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use core_lib::git::bundle::{self, BundleInfo};
+use git2::Repository;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::jobs::{JobId, JobKind, JobManager};
+
+use super::jobs::report_transfer;
+use super::open_handle;
+
+/// Reads the refs and prerequisites of the bundle file `bundle`.
+#[tauri::command]
+#[specta::specta]
+pub fn get_bundle_info(bundle: PathBuf) -> Result<BundleInfo, String> {
+    bundle::read_bundle_info(&bundle).map_err(|e| e.to_string())
+}
+
+/// Lists the prerequisite commits of `bundle` missing from the repository at `path`, empty when it can be fetched.
+#[tauri::command]
+#[specta::specta]
+pub fn verify_bundle<T: Runtime>(app: AppHandle<T>, path: PathBuf, bundle: PathBuf) -> Result<Vec<String>, String> {
+    let info = bundle::read_bundle_info(&bundle).map_err(|e| e.to_string())?;
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    bundle::missing_prerequisites(&repo, &info).map_err(|e| e.to_string())
+}
+
+/// Writes a bundle of `revs` (refs, `from..ref` ranges or `^excluded` revisions) in the background. The bundle header
+/// is the job result.
+#[tauri::command]
+#[specta::specta]
+pub fn start_bundle_create<T: Runtime>(app: AppHandle<T>, path: PathBuf, bundle: PathBuf, revs: Vec<String>) -> JobId {
+    let title = format!("Create bundle {}", bundle.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Bundle, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let info = bundle::create_bundle(&repo, &bundle, &revs, |stage, current, total| {
+            ctx.progress(current, Some(total), Some(stage.to_string()))
+        })
+        .map_err(|e| e.to_string())?;
+        ctx.log(format!("Bundled {} refs", info.refs.len()));
+        serde_json::to_value(info).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+/// Fetches from the bundle file `bundle` in the background, like from a read-only remote named `name`: branches go
+/// to `refs/remotes/<name>/`, tags to `refs/tags/`. The fetch summary is the job result.
+///
+/// Remotes whose URL is a bundle file are fetched by [`super::jobs::start_fetch`] as well.
+#[tauri::command]
+#[specta::specta]
+pub fn start_bundle_fetch<T: Runtime>(app: AppHandle<T>, path: PathBuf, bundle: PathBuf, name: String) -> JobId {
+    let title = format!("Fetch bundle {}", bundle.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Fetch, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let refspecs = [
+            format!("+refs/heads/*:refs/remotes/{}/*", name),
+            "refs/tags/*:refs/tags/*".to_string(),
+        ];
+        let summary = bundle::fetch_bundle(&repo, &bundle, &name, &refspecs, |p| report_transfer(ctx, &p))
+            .map_err(|e| e.to_string())?;
+        ctx.log(format!(
+            "{}: {} refs updated",
+            summary.remote,
+            summary.updated_refs.len()
+        ));
+        serde_json::to_value(summary).map(Some).map_err(|e| e.to_string())
+    })
+}
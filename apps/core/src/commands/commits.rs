@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use core_lib::git::commit::{self, CommitError, CommitIdentity, CommitOptions};
+use core_lib::git::commit_details::{self, CommitDetails};
+use core_lib::git::hooks::{self, HookError, HookKind};
+use core_lib::git::journal::{self, OperationKind};
+use core_lib::git::signature::{self, Verification};
+use core_lib::store::{identities, settings};
+use git2::{Oid, Repository};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_specta::Event;
+
+use crate::events::{CommitTrust, SignaturesVerified};
+use crate::jobs::{JobId, JobKind, JobManager};
+
+use super::hooks::emit_output;
+use super::open_handle;
+
+/// Commits verified between two [`SignaturesVerified`] events.
+const VERIFY_BATCH: usize = 20;
+
+/// Failure of [`create_commit`] or [`create_tag`], tagged so the UI can tell signing problems and rejecting hooks
+/// apart.
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+#[serde(tag = "kind", content = "message")]
+pub enum CreateError {
+    NothingToCommit(String),
+    Signing(String),
+    Hook(String),
+    Other(String),
+}
+
+impl From<CommitError> for CreateError {
+    fn from(error: CommitError) -> Self {
+        let message = error.to_string();
+        match error {
+            CommitError::NothingToCommit => CreateError::NothingToCommit(message),
+            CommitError::Signing(_) => CreateError::Signing(message),
+            CommitError::Git(_) => CreateError::Other(message),
+        }
+    }
+}
+
+impl From<HookError> for CreateError {
+    fn from(error: HookError) -> Self {
+        match error {
+            HookError::Rejected { .. } => CreateError::Hook(error.to_string()),
+            _ => CreateError::Other(error.to_string()),
+        }
+    }
+}
+
+/// Commits the staged changes, signing the commit when `commit.gpgsign` (or `options.sign`) asks for it.
+///
+/// When an identity rule matches the repository, the commit is made as that identity. The commit hooks run like for
+/// `git commit -m`, unless `options.no_verify` skips them; their output is emitted as [`crate::events::HookOutput`].
+#[tauri::command]
+#[specta::specta]
+pub fn create_commit<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    message: String,
+    options: CommitOptions,
+) -> Result<String, CreateError> {
+    let programs = settings::load_settings(&app)
+        .map_err(|e| CreateError::Other(e.to_string()))?
+        .gpg;
+    let handle = open_handle(&app, &path).map_err(CreateError::Other)?;
+    let repo = handle.repo();
+    let identity = repo_identity(&app, &repo)?;
+    // Like `git commit`, refuse an empty commit before any hook gets to run.
+    commit::check_changes(&repo, &options)?;
+    let message = hooks::run_commit_hooks(&repo, &message, options.no_verify, |hook, stream, line| {
+        emit_output(&app, &path, hook, stream, line)
+    })?;
+    // The pre-commit hook may have staged changes.
+    repo.index()
+        .and_then(|mut index| index.read(false))
+        .map_err(|e| CreateError::Other(e.to_string()))?;
+    let cache = handle.index_cache().map(|cache| cache.as_ref());
+    let description = format!("commit: {}", message.lines().next().unwrap_or_default());
+    let oid = journal::record(&repo, cache, OperationKind::Commit, &description, false, || {
+        commit::create_commit(&repo, &message, &options, identity.as_ref(), &programs)
+    })?;
+    if let Err(e) = hooks::run_post_commit(&repo, |stream, line| {
+        emit_output(&app, &path, HookKind::PostCommit, stream, line)
+    }) {
+        warn!("{}", e);
+    }
+    Ok(oid.to_string())
+}
+
+/// Creates a lightweight tag, or an annotated one (signed per `tag.gpgsign` or `options.sign`) with a message.
+#[tauri::command]
+#[specta::specta]
+pub fn create_tag<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    name: String,
+    target: String,
+    message: Option<String>,
+    force: bool,
+    options: CommitOptions,
+) -> Result<String, CreateError> {
+    let programs = settings::load_settings(&app)
+        .map_err(|e| CreateError::Other(e.to_string()))?
+        .gpg;
+    let handle = open_handle(&app, &path).map_err(CreateError::Other)?;
+    let repo = handle.repo();
+    let identity = repo_identity(&app, &repo)?;
+    let cache = handle.index_cache().map(|cache| cache.as_ref());
+    let description = format!("tag: {}", name);
+    let oid = journal::record(&repo, cache, OperationKind::Tag, &description, false, || {
+        commit::create_tag(
+            &repo,
+            &name,
+            &target,
+            message.as_deref(),
+            force,
+            &options,
+            identity.as_ref(),
+            &programs,
+        )
+    })?;
+    Ok(oid.to_string())
+}
+
+/// The identity profile whose rules match the repository, used instead of the configured user.
+fn repo_identity<T: Runtime>(app: &AppHandle<T>, repo: &Repository) -> Result<Option<CommitIdentity>, CreateError> {
+    let identity = identities::identity_for_repo(app, repo).map_err(|e| CreateError::Other(e.to_string()))?;
+    Ok(identity.map(|identity| identity.commit_identity()))
+}
+
+/// Full details of a single commit: identities, message parts, trailers, signature and stats.
+///
+/// With `verify`, the signature is checked with the configured programs and the result cached.
+#[tauri::command]
+#[specta::specta]
+pub fn get_commit_details<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    rev: String,
+    verify: bool,
+) -> Result<CommitDetails, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let mut details = commit_details::commit_details(&repo, &rev).map_err(|e| e.to_string())?;
+    if let (true, Some(sig)) = (verify, details.signature.as_mut()) {
+        let programs = settings::load_settings(&app).map_err(|e| e.to_string())?.gpg;
+        let oid = Oid::from_str(&details.oid).map_err(|e| e.to_string())?;
+        let cache = handle.index_cache().map(|cache| cache.as_ref());
+        let verification = signature::verify_commit_cached(&repo, cache, oid, &programs).map_err(|e| e.to_string())?;
+        sig.verification = Some(verification);
+    }
+    Ok(details)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn verify_tag_signature<T: Runtime>(app: AppHandle<T>, path: PathBuf, tag: String) -> Result<Verification, String> {
+    let programs = settings::load_settings(&app).map_err(|e| e.to_string())?.gpg;
+    let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+    let oid = repo
+        .revparse_single(&tag)
+        .and_then(|object| object.peel_to_tag())
+        .map_err(|e| e.to_string())?
+        .id();
+    signature::verify_tag(&repo, oid, &programs).map_err(|e| e.to_string())
+}
+
+/// Verifies the signatures of `oids` (e.g. the visible part of the graph) in the background.
+///
+/// Results are emitted as [`SignaturesVerified`] events and cached, so later graph loads include them.
+#[tauri::command]
+#[specta::specta]
+pub fn start_signature_verification<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    oids: Vec<String>,
+) -> Result<JobId, String> {
+    let programs = settings::load_settings(&app).map_err(|e| e.to_string())?.gpg;
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().cloned();
+    let path = handle.path().clone();
+    let title = format!("Verify signatures in {}", path.display());
+    let id = app
+        .state::<JobManager>()
+        .spawn(&app, JobKind::SignatureVerification, title, move |ctx| {
+            let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+            let total = oids.len() as u32;
+            for (i, batch) in oids.chunks(VERIFY_BATCH).enumerate() {
+                if !ctx.progress((i * VERIFY_BATCH) as u32, Some(total), None) {
+                    break;
+                }
+                let mut results = Vec::with_capacity(batch.len());
+                for oid in batch {
+                    let parsed = Oid::from_str(oid).map_err(|e| e.to_string())?;
+                    match signature::verify_commit_cached(&repo, cache.as_deref(), parsed, &programs) {
+                        Ok(verification) => results.push(CommitTrust {
+                            oid: oid.clone(),
+                            trust: verification.trust,
+                        }),
+                        Err(e) => ctx.log(format!("Cannot verify {}: {}", oid, e)),
+                    }
+                }
+                let event = SignaturesVerified {
+                    job_id: ctx.id(),
+                    path: path.clone(),
+                    results,
+                };
+                if let Err(e) = event.emit(ctx.app()) {
+                    error!("Failed to emit signature results: {:?}", e);
+                }
+            }
+            ctx.progress(total, Some(total), None);
+            Ok(None)
+        });
+    Ok(id)
+}
+
+/// Forgets cached verification results, e.g. after importing keys or editing the allowed signers.
+#[tauri::command]
+#[specta::specta]
+pub fn clear_signature_cache<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<(), String> {
+    let handle = open_handle(&app, &path)?;
+    match handle.index_cache() {
+        Some(cache) => cache.clear_verifications().map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
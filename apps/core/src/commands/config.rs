@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use core_lib::git::config::{self, CommonSetting, CommonSettings, ConfigError, ConfigScope, ConfigValue};
+use git2::Repository;
+use tauri::{AppHandle, Runtime};
+
+use super::open_handle;
+
+/// Runs `f` against the repository at `path`, or without one to only reach the user and system files.
+fn with_repo<T: Runtime, R>(
+    app: &AppHandle<T>,
+    path: Option<PathBuf>,
+    f: impl FnOnce(Option<&Repository>) -> Result<R, ConfigError>,
+) -> Result<R, String> {
+    match path {
+        Some(path) => {
+            let handle = open_handle(app, &path)?;
+            let repo = handle.repo();
+            f(Some(&repo))
+        }
+        None => f(None),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Lists every config key with its effective value and the scopes that set it.
+#[tauri::command]
+#[specta::specta]
+pub fn get_config<T: Runtime>(app: AppHandle<T>, path: Option<PathBuf>) -> Result<Vec<ConfigValue>, String> {
+    with_repo(&app, path, config::list_config)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_common_settings<T: Runtime>(app: AppHandle<T>, path: Option<PathBuf>) -> Result<CommonSettings, String> {
+    with_repo(&app, path, config::common_settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn set_config_value<T: Runtime>(
+    app: AppHandle<T>,
+    path: Option<PathBuf>,
+    scope: ConfigScope,
+    name: String,
+    value: String,
+) -> Result<(), String> {
+    with_repo(&app, path, |repo| config::set_value(repo, scope, &name, &value))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn unset_config_value<T: Runtime>(
+    app: AppHandle<T>,
+    path: Option<PathBuf>,
+    scope: ConfigScope,
+    name: String,
+) -> Result<(), String> {
+    with_repo(&app, path, |repo| config::unset_value(repo, scope, &name))
+}
+
+/// Validates and writes one of the settings screen's keys.
+#[tauri::command]
+#[specta::specta]
+pub fn set_common_setting<T: Runtime>(
+    app: AppHandle<T>,
+    path: Option<PathBuf>,
+    scope: ConfigScope,
+    setting: CommonSetting,
+) -> Result<(), String> {
+    with_repo(&app, path, |repo| config::set_common(repo, scope, &setting))
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use core_lib::git::discovery::{self, DiscoveryOptions};
+use core_lib::store::repos::{self, RepoRecord};
+use log::{error, info};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_specta::Event;
+
+use crate::events::{RepoDiscovered, RepoScanFinished};
+use crate::jobs::{JobId, JobKind, JobManager};
+
+/// Starts scanning a folder for repositories as a background job and returns the job id.
+///
+/// Results are streamed as [`RepoDiscovered`] events followed by a single [`RepoScanFinished`].
+#[tauri::command]
+#[specta::specta]
+pub fn scan_for_repos<T: Runtime>(app: AppHandle<T>, options: DiscoveryOptions) -> Result<JobId, String> {
+    let registry = repos::load_registry(&app).map_err(|e| e.to_string())?;
+    let title = format!("Scan {} for repositories", options.root.display());
+
+    let jobs = app.state::<JobManager>();
+    let id = jobs.spawn(&app, JobKind::DiscoveryScan, title, move |ctx| {
+        let scan_id = ctx.id();
+        info!("Starting repo scan {} in {:?}", scan_id, options.root);
+
+        let mut found = 0;
+        let result = discovery::scan(&options, |repo| {
+            found += 1;
+            ctx.log(format!("Found {:?}", repo.path));
+            let event = RepoDiscovered {
+                scan_id,
+                registered: registry.find_by_path(&repo.path).is_some(),
+                repo,
+            };
+            if let Err(e) = event.emit(ctx.app()) {
+                error!("Failed to emit discovered repo: {:?}", e);
+            }
+            ctx.progress(found, None, None)
+        });
+
+        let finished = RepoScanFinished {
+            scan_id,
+            found,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        if let Err(e) = finished.emit(ctx.app()) {
+            error!("Failed to emit scan result: {:?}", e);
+        }
+        result.map(|_| None).map_err(|e| e.to_string())
+    });
+    Ok(id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn add_repos<T: Runtime>(app: AppHandle<T>, paths: Vec<PathBuf>) -> Result<Vec<RepoRecord>, String> {
+    repos::add_repos(&app, &paths).map_err(|e| e.to_string())
+}
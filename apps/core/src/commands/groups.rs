@@ -0,0 +1,220 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use core_lib::git::branch::{self, SwitchOutcome};
+use core_lib::git::journal::{self, OperationKind};
+use core_lib::git::remote;
+use core_lib::git::status::{self, StatusSummary};
+use core_lib::store::groups::{self, RepoGroup};
+use core_lib::store::repos::{self, RepoRecord};
+use git2::Repository;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Manager, Runtime};
+use uuid::Uuid;
+
+use crate::jobs::{JobId, JobKind, JobManager};
+use crate::store::RepoStore;
+
+use super::hooks::{head_oid, post_checkout};
+
+/// Result of a batch operation for a single member of a group.
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct GroupMemberResult<T> {
+    pub repo: RepoRecord,
+    pub result: Option<T>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct GroupMemberStatus {
+    pub status: StatusSummary,
+    pub dirty: bool,
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_groups<T: Runtime>(app: AppHandle<T>) -> Result<Vec<RepoGroup>, String> {
+    groups::get_groups(&app).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn create_group<T: Runtime>(app: AppHandle<T>, name: String, repos: Vec<Uuid>) -> Result<RepoGroup, String> {
+    groups::create_group(&app, name, repos).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn rename_group<T: Runtime>(app: AppHandle<T>, id: Uuid, name: String) -> Result<RepoGroup, String> {
+    groups::rename_group(&app, id, name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn set_group_repos<T: Runtime>(app: AppHandle<T>, id: Uuid, repos: Vec<Uuid>) -> Result<RepoGroup, String> {
+    groups::set_group_repos(&app, id, repos).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn delete_group<T: Runtime>(app: AppHandle<T>, id: Uuid) -> Result<Option<RepoGroup>, String> {
+    groups::delete_group(&app, id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_group_status<T: Runtime>(
+    app: AppHandle<T>,
+    id: Uuid,
+) -> Result<Vec<GroupMemberResult<GroupMemberStatus>>, String> {
+    let members = groups::group_members(&app, id).map_err(|e| e.to_string())?;
+    Ok(for_each_member(members, |repo| {
+        let status = status::status_summary(repo)?;
+        Ok(GroupMemberStatus {
+            dirty: status.is_dirty(),
+            status,
+        })
+    }))
+}
+
+/// Fetches all remotes of every member in the background, one member after the other. The per-member results are
+/// the job result.
+#[tauri::command]
+#[specta::specta]
+pub fn start_group_fetch<T: Runtime>(app: AppHandle<T>, id: Uuid) -> Result<JobId, String> {
+    let group = groups::get_group(&app, id).map_err(|e| e.to_string())?;
+    let members = groups::group_members(&app, id).map_err(|e| e.to_string())?;
+    let title = format!("Fetch group {}", group.name);
+    let job = app.state::<JobManager>().spawn(&app, JobKind::Fetch, title, move |ctx| {
+        info!("Fetching {} repos of group {}", members.len(), id);
+        let total = members.len() as u32;
+        let mut results = Vec::with_capacity(members.len());
+        for (i, record) in members.into_iter().enumerate() {
+            if !ctx.progress(i as u32, Some(total), Some(record.path.display().to_string())) {
+                break;
+            }
+            let result =
+                Repository::open(&record.path).and_then(|repo| remote::fetch_all(&repo, |_| !ctx.is_cancelled()));
+            results.push(member_result(record, result));
+        }
+        ctx.progress(total, Some(total), None);
+        serde_json::to_value(results).map(Some).map_err(|e| e.to_string())
+    });
+    Ok(job)
+}
+
+/// Switches every member that has a branch called `branch` to it. Members without it report [`SwitchOutcome::NotFound`].
+///
+/// The `post-checkout` hook of each switched member runs afterwards, unless `skip_hooks` is set.
+#[tauri::command]
+#[specta::specta]
+pub fn switch_group_branch<T: Runtime>(
+    app: AppHandle<T>,
+    id: Uuid,
+    branch: String,
+    skip_hooks: bool,
+) -> Result<Vec<GroupMemberResult<SwitchOutcome>>, String> {
+    let members = groups::group_members(&app, id).map_err(|e| e.to_string())?;
+    let store = app.state::<RepoStore>();
+    let description = format!("checkout: moving to {}", branch);
+    Ok(for_each_member(members, |repo| {
+        // Only repos open in the store have a journal to record the switch in.
+        let handle = repo.workdir().and_then(|path| store.get_repo(&repos::canonical_path(path)));
+        let cache = handle
+            .as_ref()
+            .and_then(|handle| handle.index_cache())
+            .map(|cache| cache.as_ref());
+        let old = head_oid(repo);
+        let outcome = journal::record(repo, cache, OperationKind::Checkout, &description, true, || {
+            branch::switch_branch(repo, &branch)
+        })?;
+        let switched = matches!(outcome, SwitchOutcome::Switched | SwitchOutcome::CreatedFromRemote);
+        if switched && !skip_hooks {
+            post_checkout(&app, repo, old);
+        }
+        Ok(outcome)
+    }))
+}
+
+/// Runs `op` on every member concurrently, on at most one worker per CPU (up to 8), collecting results in member
+/// order.
+fn for_each_member<R, F>(members: Vec<RepoRecord>, op: F) -> Vec<GroupMemberResult<R>>
+where
+    R: Send,
+    F: Fn(&Repository) -> Result<R, git2::Error> + Sync,
+{
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(8);
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<GroupMemberResult<R>>>> = members.iter().map(|_| Mutex::new(None)).collect();
+    thread::scope(|scope| {
+        for _ in 0..workers.min(members.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(record) = members.get(index) else {
+                    break;
+                };
+                let result = Repository::open(&record.path).and_then(|repo| op(&repo));
+                *results[index].lock().unwrap() = Some(member_result(record.clone(), result));
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().expect("every member was processed"))
+        .collect()
+}
+
+fn member_result<R>(record: RepoRecord, result: Result<R, git2::Error>) -> GroupMemberResult<R> {
+    match result {
+        Ok(result) => GroupMemberResult {
+            repo: record,
+            result: Some(result),
+            error: None,
+        },
+        Err(e) => {
+            warn!("Group operation failed for {:?}: {}", record.path, e);
+            GroupMemberResult {
+                repo: record,
+                result: None,
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_for_each_member_bounds_workers_and_keeps_order() {
+        let dir = std::env::temp_dir().join("gitultra_group_members_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let members: Vec<_> = (0..24)
+            .map(|i| {
+                let path = dir.join(format!("repo{}", i));
+                Repository::init(&path).unwrap();
+                RepoRecord::new(&path)
+            })
+            .collect();
+        let paths: Vec<_> = members.iter().map(|record| record.path.clone()).collect();
+
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let results = for_each_member(members, |repo| {
+            peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5));
+            active.fetch_sub(1, Ordering::SeqCst);
+            Ok(repo.workdir().map(repos::canonical_path))
+        });
+
+        assert!(peak.load(Ordering::SeqCst) <= 8);
+        let result_paths: Vec<_> = results.into_iter().map(|result| result.result.flatten().unwrap()).collect();
+        assert_eq!(result_paths, paths);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use core_lib::git::history::{self, FileHistoryOptions, FileRevision};
+use git2::Repository;
+
+/// Lists the commits touching a file, directory or glob, following renames for single files.
+#[tauri::command]
+#[specta::specta]
+pub fn get_file_history(
+    path: PathBuf,
+    pathspec: PathBuf,
+    options: FileHistoryOptions,
+) -> Result<Vec<FileRevision>, String> {
+    let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+    history::file_history(&repo, &pathspec, &options).map_err(|e| e.to_string())
+}
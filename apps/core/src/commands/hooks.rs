@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+use core_lib::git::hooks::{self, HookInfo, HookKind, HookStream};
+use git2::{Oid, Repository};
+use log::{error, warn};
+use tauri::{AppHandle, Runtime};
+use tauri_specta::Event;
+
+use crate::events::HookOutput;
+
+use super::open_handle;
+
+/// The hooks of the repository at `path`, from `core.hooksPath` or `.git/hooks`, installed or not.
+#[tauri::command]
+#[specta::specta]
+pub fn get_hooks<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<HookInfo>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    hooks::list_hooks(&repo).map_err(|e| e.to_string())
+}
+
+/// Emits a line printed by a hook of the repository at `path` as a [`HookOutput`] event.
+pub(crate) fn emit_output<T: Runtime>(app: &AppHandle<T>, path: &Path, hook: HookKind, stream: HookStream, line: &str) {
+    let event = HookOutput {
+        path: path.to_path_buf(),
+        hook,
+        stream,
+        line: line.to_string(),
+    };
+    if let Err(e) = event.emit(app) {
+        error!("Failed to emit hook output: {:?}", e);
+    }
+}
+
+/// The commit HEAD points to, zero when unborn, to pass as the old HEAD to [`post_checkout`].
+pub(crate) fn head_oid(repo: &Repository) -> Oid {
+    repo.head().ok().and_then(|head| head.target()).unwrap_or_else(Oid::zero)
+}
+
+/// Runs the `post-checkout` hook of `repo` after a checkout moved HEAD from `old`, emitting its output.
+///
+/// The checkout already happened, so a failing hook is only logged.
+pub(crate) fn post_checkout<T: Runtime>(app: &AppHandle<T>, repo: &Repository, old: Oid) {
+    let path = repo.workdir().unwrap_or_else(|| repo.path());
+    let result = hooks::run_post_checkout(repo, old, head_oid(repo), true, |stream, line| {
+        emit_output(app, path, HookKind::PostCheckout, stream, line)
+    });
+    if let Err(e) = result {
+        warn!("{}", e);
+    }
+}
+
+/// Runs the `post-merge` hook of `repo` after a merge or fast-forward updated the working tree, emitting its output.
+///
+/// Like `post-checkout`, a failing hook is only logged.
+pub(crate) fn post_merge<T: Runtime>(app: &AppHandle<T>, repo: &Repository) {
+    let path = repo.workdir().unwrap_or_else(|| repo.path());
+    let result = hooks::run_post_merge(repo, false, |stream, line| {
+        emit_output(app, path, HookKind::PostMerge, stream, line)
+    });
+    if let Err(e) = result {
+        warn!("{}", e);
+    }
+}
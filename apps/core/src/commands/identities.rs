@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use core_lib::store::identities::{self, Identity, IdentityFields, IdentityProfiles, IdentityRule};
+use tauri::{AppHandle, Runtime};
+use uuid::Uuid;
+
+use super::open_handle;
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_identities<T: Runtime>(app: AppHandle<T>) -> Result<IdentityProfiles, String> {
+    identities::load_identities(&app).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn create_identity<T: Runtime>(app: AppHandle<T>, fields: IdentityFields) -> Result<Identity, String> {
+    identities::create_identity(&app, fields).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn update_identity<T: Runtime>(app: AppHandle<T>, id: Uuid, fields: IdentityFields) -> Result<Identity, String> {
+    identities::update_identity(&app, id, fields).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn delete_identity<T: Runtime>(app: AppHandle<T>, id: Uuid) -> Result<Option<Identity>, String> {
+    identities::delete_identity(&app, id).map_err(|e| e.to_string())
+}
+
+/// Replaces the rules; the first one matching a repository picks its identity.
+#[tauri::command]
+#[specta::specta]
+pub fn set_identity_rules<T: Runtime>(
+    app: AppHandle<T>,
+    rules: Vec<IdentityRule>,
+) -> Result<Vec<IdentityRule>, String> {
+    identities::set_identity_rules(&app, rules).map_err(|e| e.to_string())
+}
+
+/// The identity commits in the repository at `path` will be made as, `None` when no rule matches.
+#[tauri::command]
+#[specta::specta]
+pub fn get_repo_identity<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Option<Identity>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    identities::identity_for_repo(&app, &repo).map_err(|e| e.to_string())
+}
+
+/// Writes the identity `id` to the local config of the repository at `path`.
+#[tauri::command]
+#[specta::specta]
+pub fn write_identity_to_repo<T: Runtime>(app: AppHandle<T>, path: PathBuf, id: Uuid) -> Result<(), String> {
+    let profiles = identities::load_identities(&app).map_err(|e| e.to_string())?;
+    let identity = profiles.find(id).ok_or_else(|| format!("Identity not found: {}", id))?;
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    identities::write_identity_to_config(&repo, identity).map_err(|e| e.to_string())
+}
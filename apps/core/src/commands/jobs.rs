@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use core_lib::git::hooks::HookKind;
+use core_lib::git::remote::{self, PullOutcome};
+use core_lib::store::repos;
+use git2::Repository;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::jobs::{JobContext, JobId, JobInfo, JobKind, JobManager};
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_jobs<T: Runtime>(app: AppHandle<T>) -> Vec<JobInfo> {
+    app.state::<JobManager>().list_jobs()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_job<T: Runtime>(app: AppHandle<T>, id: JobId) -> Option<JobInfo> {
+    app.state::<JobManager>().get_job(id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_job_log<T: Runtime>(app: AppHandle<T>, id: JobId) -> Option<Vec<String>> {
+    app.state::<JobManager>().get_log(id)
+}
+
+/// Returns the value produced by a succeeded job, e.g. the nodes of a commit graph walk.
+#[tauri::command]
+#[specta::specta]
+pub fn get_job_result<T: Runtime>(app: AppHandle<T>, id: JobId) -> Option<serde_json::Value> {
+    app.state::<JobManager>().get_result(id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_job<T: Runtime>(app: AppHandle<T>, id: JobId) -> bool {
+    app.state::<JobManager>().cancel(id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn clear_finished_jobs<T: Runtime>(app: AppHandle<T>) {
+    app.state::<JobManager>().clear_finished()
+}
+
+/// Clones `url` into `path` and registers the new repository once done.
+#[tauri::command]
+#[specta::specta]
+pub fn start_clone<T: Runtime>(app: AppHandle<T>, url: String, path: PathBuf) -> JobId {
+    let title = format!("Clone {}", url);
+    app.state::<JobManager>().spawn(&app, JobKind::Clone, title, move |ctx| {
+        ctx.log(format!("Cloning {} into {:?}", url, path));
+        remote::clone_repo(&url, &path, |p| report_transfer(ctx, &p)).map_err(|e| e.to_string())?;
+        let record = repos::add_repo(ctx.app(), &path).map_err(|e| e.to_string())?;
+        serde_json::to_value(record).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+/// Fetches `remote`, or all remotes when `None`.
+#[tauri::command]
+#[specta::specta]
+pub fn start_fetch<T: Runtime>(app: AppHandle<T>, path: PathBuf, remote: Option<String>) -> JobId {
+    let title = format!("Fetch {}", path.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Fetch, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let summaries = match remote {
+            Some(remote) => {
+                vec![remote::fetch_remote(&repo, &remote, |p| report_transfer(ctx, &p)).map_err(|e| e.to_string())?]
+            }
+            None => remote::fetch_all(&repo, |p| report_transfer(ctx, &p)).map_err(|e| e.to_string())?,
+        };
+        for summary in &summaries {
+            ctx.log(format!(
+                "{}: {} refs updated",
+                summary.remote,
+                summary.updated_refs.len()
+            ));
+        }
+        serde_json::to_value(summaries).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+/// Pushes `refspecs` to `remote`. The `pre-push` hook can refuse the push unless `no_verify` skips it; its output is
+/// emitted as [`crate::events::HookOutput`].
+#[tauri::command]
+#[specta::specta]
+pub fn start_push<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    remote: String,
+    refspecs: Vec<String>,
+    no_verify: bool,
+) -> JobId {
+    let title = format!("Push {} to {}", path.display(), remote);
+    app.state::<JobManager>().spawn(&app, JobKind::Push, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let pushed = remote::push_remote(&repo, &remote, &refspecs, no_verify, |stream, line| {
+            super::hooks::emit_output(ctx.app(), &path, HookKind::PrePush, stream, line)
+        })
+        .map_err(|e| e.to_string())?;
+        for pushed_ref in &pushed {
+            match &pushed_ref.error {
+                Some(error) => ctx.log(format!("{}: rejected, {}", pushed_ref.remote_ref, error)),
+                None => ctx.log(format!("{}: updated", pushed_ref.remote_ref)),
+            }
+        }
+        serde_json::to_value(pushed).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+/// Fetches the upstream of the current branch and fast-forwards to it, then runs the `post-merge` hook unless
+/// `skip_hooks`.
+#[tauri::command]
+#[specta::specta]
+pub fn start_pull<T: Runtime>(app: AppHandle<T>, path: PathBuf, skip_hooks: bool) -> JobId {
+    let title = format!("Pull {}", path.display());
+    app.state::<JobManager>().spawn(&app, JobKind::Pull, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let outcome = remote::pull_branch(&repo, |p| report_transfer(ctx, &p)).map_err(|e| e.to_string())?;
+        if let PullOutcome::FastForward { new_oid, .. } = &outcome {
+            ctx.log(format!("Fast-forwarded to {}", new_oid));
+            if !skip_hooks {
+                super::hooks::post_merge(ctx.app(), &repo);
+            }
+        }
+        serde_json::to_value(outcome).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+/// Walks the full commit graph in the background. The nodes are available through [`get_job_result`].
+#[tauri::command]
+#[specta::specta]
+pub fn start_commit_graph<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> JobId {
+    let title = format!("Load history of {}", path.display());
+    app.state::<JobManager>().spawn(&app, JobKind::CommitGraph, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let mut nodes = core_lib::git::walk_commit_graph(&repo, |walked| ctx.progress(walked as u32, None, None))
+            .map_err(|e| e.to_string())?;
+        super::annotate_trust(ctx.app(), &path, &mut nodes);
+        ctx.log(format!("Loaded {} commits", nodes.len()));
+        serde_json::to_value(nodes).map(Some).map_err(|e| e.to_string())
+    })
+}
+
+pub(super) fn report_transfer<R: Runtime>(ctx: &JobContext<R>, progress: &remote::FetchProgress) -> bool {
+    ctx.progress(
+        progress.received_objects,
+        Some(progress.total_objects),
+        Some(format!(
+            "{}: {} bytes received",
+            progress.remote, progress.received_bytes
+        )),
+    )
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use core_lib::git::journal::{self, Operation};
+use tauri::{AppHandle, Runtime};
+
+use super::hooks::{head_oid, post_checkout};
+use super::open_handle;
+
+/// Lists the journaled operations of the repository, newest first.
+#[tauri::command]
+#[specta::specta]
+pub fn get_operations<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    limit: Option<usize>,
+) -> Result<Vec<Operation>, String> {
+    let handle = open_handle(&app, &path)?;
+    match handle.index_cache() {
+        Some(cache) => cache.operations(limit).map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Reverts the last operation GitUltra performed and returns it.
+///
+/// When the working tree is restored, the `post-checkout` hook runs afterwards unless `skip_hooks` is set.
+#[tauri::command]
+#[specta::specta]
+pub fn undo_last<T: Runtime>(app: AppHandle<T>, path: PathBuf, skip_hooks: bool) -> Result<Operation, String> {
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().ok_or("No operation journal for this repo")?;
+    let repo = handle.repo();
+    let old = head_oid(&repo);
+    let operation = journal::undo_last(&repo, cache).map_err(|e| e.to_string())?;
+    if operation.worktree_before.is_some() && !skip_hooks {
+        post_checkout(&app, &repo, old);
+    }
+    Ok(operation)
+}
+
+/// Re-applies the last undone operation and returns it, running `post-checkout` like [`undo_last`].
+#[tauri::command]
+#[specta::specta]
+pub fn redo<T: Runtime>(app: AppHandle<T>, path: PathBuf, skip_hooks: bool) -> Result<Operation, String> {
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().ok_or("No operation journal for this repo")?;
+    let repo = handle.repo();
+    let old = head_oid(&repo);
+    let operation = journal::redo(&repo, cache).map_err(|e| e.to_string())?;
+    if operation.worktree_after.is_some() && !skip_hooks {
+        post_checkout(&app, &repo, old);
+    }
+    Ok(operation)
+}
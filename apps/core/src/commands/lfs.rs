@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use core_lib::git::lfs::{self, LfsDiff, LfsDiffRange};
+use tauri::{AppHandle, Runtime};
+
+use super::open_handle;
+
+/// The LFS files changed in `range`, with diffs of their real content when the objects are stored locally.
+#[tauri::command]
+#[specta::specta]
+pub fn get_lfs_diffs<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    range: LfsDiffRange,
+    pathspec: Option<PathBuf>,
+) -> Result<Vec<LfsDiff>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    lfs::lfs_diffs(&repo, &range, pathspec.as_deref()).map_err(|e| e.to_string())
+}
@@ -1,8 +1,38 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use core_lib::git::CommitNode;
+use core_lib::git::{signature, worktree};
+use core_lib::store::repos::{self, RepoRecord, RepoRecordUpdate};
 use git2::Repository;
-use log::{error, info};
-use tauri::{AppHandle, Runtime};
+use log::{error, info, warn};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_specta::Event;
+use uuid::Uuid;
+
+use crate::events::RepoChanged;
+use crate::store::{RepoHandle, RepoStore};
+
+pub mod archives;
+pub mod blame;
+pub mod bundles;
+pub mod commits;
+pub mod config;
+pub mod discovery;
+pub mod groups;
+pub mod history;
+pub mod hooks;
+pub mod identities;
+pub mod jobs;
+pub mod journal;
+pub mod lfs;
+pub mod patches;
+pub mod reflog;
+pub mod search;
+pub mod settings;
+pub mod sparse;
+pub mod status;
+pub mod submodules;
+pub mod worktrees;
 
 #[tauri::command]
 #[specta::specta]
@@ -12,27 +42,58 @@ pub fn greet(name: &str) -> String {
 
 #[tauri::command]
 #[specta::specta]
-pub fn open_repo_directory<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Vec<String> {
-    println!("Opening repo directory: {:?}", path);
+pub fn open_repo_directory<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<RepoRecord>, String> {
+    info!("Opening repo directory: {:?}", path);
 
     let repo = match core_lib::git::open_repo(&path) {
         Ok(repo) => repo,
         Err(e) => {
             error!("Failed to open repo: {:?}", e);
-            return vec!["".to_string()];
-            // return format!("Failed to open repo: {:?}", e);
+            return Err(format!("Failed to open repo: {:?}", e));
         }
     };
     info!("Repo opened: {:?}", repo.path());
 
-    let repo_name = core_lib::store::repos::add_repo(&app, &path);
-    info!("Repo added to local store: {:?}", repo_name);
+    let record = match worktree::main_repo_path(&repo) {
+        Some(main) => repos::add_worktree(&app, &main, &path),
+        None => repos::add_repo(&app, &path),
+    }
+    .map_err(|e| e.to_string())?;
+    repos::touch_repo(&app, record.id).map_err(|e| e.to_string())?;
+    info!("Repo added to local store: {:?}", record.name);
+
+    watch_repo(&app, record.path.clone()).map_err(|e| e.to_string())?;
+    if let Err(e) = search::start_commit_indexing(app.clone(), record.path) {
+        warn!("Not indexing commits of {:?}: {}", path, e);
+    }
+
+    repos::get_repos(&app).map_err(|e| e.to_string())
+}
+
+/// Stops watching the repository and releases its handle.
+#[tauri::command]
+#[specta::specta]
+pub fn close_repo<T: Runtime>(app: AppHandle<T>, path: PathBuf) {
+    let store = app.state::<RepoStore>();
+    store.close_repo(&repos::canonical_path(&path));
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_repos<T: Runtime>(app: AppHandle<T>) -> Result<Vec<RepoRecord>, String> {
+    repos::get_repos(&app).map_err(|e| e.to_string())
+}
 
-    core_lib::store::repos::get_repos(&app)
-        .unwrap()
-        .iter()
-        .map(|r| r.as_str().unwrap().to_string())
-        .collect()
+#[tauri::command]
+#[specta::specta]
+pub fn remove_repo<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Option<RepoRecord>, String> {
+    repos::remove_repo(&app, &path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn update_repo<T: Runtime>(app: AppHandle<T>, id: Uuid, update: RepoRecordUpdate) -> Result<RepoRecord, String> {
+    repos::update_repo(&app, id, update).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -42,38 +103,70 @@ pub fn get_commit_graph<T: Runtime>(
     path: String,
 ) -> Result<Vec<core_lib::git::CommitNode>, String> {
     info!("Getting commit graph for repo: {:?}", path);
-    /*     let repo_path = match core_lib::store::repos::get_repo(&app, &name) {
-        Some(repo) => repo,
-        None => {
-            return Err(format!("Repo not found: {:?}", name));
-        }
-    }; */
-
-    let w = core_lib::store::repos::get_repos(&app).unwrap();
-    let s = w
-        .iter()
-        .map(|r| r.as_str().unwrap().to_string());
-
-    let v: Vec<String> = s.collect();
-    println!("Repos: {:?}", v);
-
-    let p = v.iter().find(|&x| x.contains(&path));
-    if p.is_none() {
-        return Err(format!("Repo not found: {:?}", path));
-    }
-    
 
+    let record = resolve_repo(&app, &path)?;
 
-    let repo = match Repository::open(p.unwrap()) {
+    let repo = match Repository::open(&record.path) {
         Ok(repo) => repo,
         Err(e) => {
             return Err(format!("Failed to open repo: {:?}", e));
         }
     };
     match core_lib::git::get_commit_graph(&repo) {
-        Ok(x) => Ok(x),
+        Ok(mut x) => {
+            annotate_trust(&app, &record.path, &mut x);
+            Ok(x)
+        }
         Err(e) => Err(e.to_string()),
     }
 }
 
+/// Opens the repository in the [`RepoStore`] and forwards its filesystem changes as [`RepoChanged`] events.
+fn watch_repo<T: Runtime>(app: &AppHandle<T>, path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let store = app.state::<RepoStore>();
+    store.open_repo(path.clone())?;
 
+    let handle = app.clone();
+    let event_path = path.clone();
+    store.watch_repo(&path, move |change| {
+        let event = RepoChanged {
+            path: event_path.clone(),
+            change,
+        };
+        if let Err(e) = event.emit(&handle) {
+            error!("Failed to emit repo change: {:?}", e);
+        }
+    })?;
+    Ok(())
+}
+
+/// Looks up a registered repository by its exact path, falling back to its display name.
+fn resolve_repo<T: Runtime>(app: &AppHandle<T>, path_or_name: &str) -> Result<RepoRecord, String> {
+    let registry = repos::load_registry(app).map_err(|e| e.to_string())?;
+    registry
+        .find_by_path(Path::new(path_or_name))
+        .or_else(|| registry.repos.iter().find(|r| r.name == path_or_name))
+        .cloned()
+        .ok_or_else(|| format!("Repo not found: {:?}", path_or_name))
+}
+
+/// Opens the repository in the [`RepoStore`] if needed and returns its handle.
+pub(crate) fn open_handle<T: Runtime>(app: &AppHandle<T>, path: &Path) -> Result<RepoHandle, String> {
+    let path = repos::canonical_path(path);
+    let store = app.state::<RepoStore>();
+    store.open_repo(path.clone()).map_err(|e| e.to_string())?;
+    store.get_repo(&path).ok_or_else(|| format!("Repo not open: {:?}", path))
+}
+
+/// Adds the cached signature trust levels to graph nodes, if the repository is open with a cache.
+pub(crate) fn annotate_trust<T: Runtime>(app: &AppHandle<T>, path: &Path, nodes: &mut [CommitNode]) {
+    let store = app.state::<RepoStore>();
+    let Some(handle) = store.get_repo(&repos::canonical_path(path)) else {
+        return;
+    };
+    if let Some(cache) = handle.index_cache() {
+        if let Err(e) = signature::annotate_trust(nodes, cache) {
+            warn!("Failed to read cached signature trust: {}", e);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use core_lib::git::journal::{self, OperationKind};
+use core_lib::git::patch::{self, ApplyOutcome, MailPatch, PatchCheck, PatchExportOptions, PatchTarget};
+use core_lib::store::{identities, settings};
+use tauri::{AppHandle, Runtime};
+
+use super::open_handle;
+
+/// Writes the commits of `range` as patch files to `out`, a directory or a single mbox file.
+#[tauri::command]
+#[specta::specta]
+pub fn export_patches<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    range: String,
+    options: PatchExportOptions,
+    out: PathBuf,
+) -> Result<Vec<PathBuf>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    patch::export_patches(&repo, &range, &options, &out).map_err(|e| e.to_string())
+}
+
+/// Reads patch files or mbox series for preview, in the given order.
+#[tauri::command]
+#[specta::specta]
+pub fn read_patch_files(files: Vec<PathBuf>) -> Result<Vec<MailPatch>, String> {
+    patch::read_patches(&files).map_err(|e| e.to_string())
+}
+
+/// Dry run of [`apply_patch_files`], reporting the files and hunks that would fail.
+#[tauri::command]
+#[specta::specta]
+pub fn check_patch_files<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+    target: PatchTarget,
+) -> Result<Vec<PatchCheck>, String> {
+    let patches = patch::read_patches(&files).map_err(|e| e.to_string())?;
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    patch::check_patches(&repo, &patches, target).map_err(|e| e.to_string())
+}
+
+/// Applies patch files to the working tree or index, or commits them like `git am`.
+///
+/// Commits keep the patch author; they are committed as the identity matching the repository, if any, and signed
+/// per `commit.gpgsign`.
+#[tauri::command]
+#[specta::specta]
+pub fn apply_patch_files<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+    target: PatchTarget,
+) -> Result<ApplyOutcome, String> {
+    let patches = patch::read_patches(&files).map_err(|e| e.to_string())?;
+    let programs = settings::load_settings(&app).map_err(|e| e.to_string())?.gpg;
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let identity = identities::identity_for_repo(&app, &repo)
+        .map_err(|e| e.to_string())?
+        .map(|identity| identity.commit_identity());
+    let cache = handle.index_cache().map(|cache| cache.as_ref());
+    let description = match (target, &patches[..]) {
+        (PatchTarget::Commit, [single]) => format!("am: {}", single.summary),
+        (PatchTarget::Commit, _) => format!("am: {} patches", patches.len()),
+        _ => format!("apply: {} patches", patches.len()),
+    };
+    journal::record(&repo, cache, OperationKind::ApplyPatch, &description, true, || {
+        patch::apply_patches(&repo, &patches, target, identity.as_ref(), &programs)
+    })
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use core_lib::git::journal::{self, OperationKind};
+use core_lib::git::reflog::{self, LostCommit, ReflogEntry, ResetMode};
+use tauri::{AppHandle, Runtime};
+
+use super::hooks::{head_oid, post_checkout};
+use super::open_handle;
+
+/// Lists the reflog of `HEAD` or a branch, newest entry first.
+#[tauri::command]
+#[specta::specta]
+pub fn get_reflog<T: Runtime>(app: AppHandle<T>, path: PathBuf, name: String) -> Result<Vec<ReflogEntry>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    reflog::reflog(&repo, &name).map_err(|e| e.to_string())
+}
+
+/// Lists the tips of histories no ref reaches anymore, e.g. after a bad rebase or a deleted branch.
+#[tauri::command]
+#[specta::specta]
+pub fn find_lost_commits<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<LostCommit>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    reflog::lost_commits(&repo).map_err(|e| e.to_string())
+}
+
+/// Creates `branch` at the commit of reflog entry `name@{index}` and returns that commit.
+#[tauri::command]
+#[specta::specta]
+pub fn create_branch_from_reflog<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    name: String,
+    index: usize,
+    branch: String,
+) -> Result<String, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let cache = handle.index_cache().map(|cache| cache.as_ref());
+    let description = format!("branch: {} from {}@{{{}}}", branch, name, index);
+    let oid = journal::record(&repo, cache, OperationKind::CreateBranch, &description, false, || {
+        reflog::create_branch_from_entry(&repo, &name, index, &branch)
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(oid.to_string())
+}
+
+/// Resets the current branch to the commit of reflog entry `name@{index}` and returns that commit.
+///
+/// A hard reset checks the commit out, so the `post-checkout` hook runs afterwards unless `skip_hooks` is set.
+#[tauri::command]
+#[specta::specta]
+pub fn reset_to_reflog_entry<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    name: String,
+    index: usize,
+    mode: ResetMode,
+    skip_hooks: bool,
+) -> Result<String, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let cache = handle.index_cache().map(|cache| cache.as_ref());
+    let description = format!("reset: moving to {}@{{{}}}", name, index);
+    let touches_worktree = mode != ResetMode::Soft;
+    let old = head_oid(&repo);
+    let oid = journal::record(
+        &repo,
+        cache,
+        OperationKind::Reset,
+        &description,
+        touches_worktree,
+        || reflog::reset_to_entry(&repo, &name, index, mode),
+    )
+    .map_err(|e| e.to_string())?;
+    if mode == ResetMode::Hard && !skip_hooks {
+        post_checkout(&app, &repo, old);
+    }
+    Ok(oid.to_string())
+}
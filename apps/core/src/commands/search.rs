@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use core_lib::git::commit_cache;
+use core_lib::git::search::{self, CommitQuery};
+use git2::Repository;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::jobs::{JobId, JobKind, JobManager};
+
+use super::open_handle;
+
+/// Searches the history of a repository in the background, using the commit cache of its
+/// [`RepoStore`](crate::store::RepoStore) handle. The matches are the job result.
+#[tauri::command]
+#[specta::specta]
+pub fn start_commit_search<T: Runtime>(app: AppHandle<T>, path: PathBuf, query: CommitQuery) -> Result<JobId, String> {
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().cloned();
+    let path = handle.path().clone();
+    let title = format!("Search commits of {}", path.display());
+    let id = app.state::<JobManager>().spawn(&app, JobKind::CommitSearch, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let matches = search::search_commits(&repo, cache.as_deref(), &query, |searched| {
+            ctx.progress(searched as u32, None, None)
+        })
+        .map_err(|e| e.to_string())?;
+        ctx.log(format!("Found {} commits", matches.len()));
+        serde_json::to_value(matches).map(Some).map_err(|e| e.to_string())
+    });
+    Ok(id)
+}
+
+/// Full-text search over the cached commit messages, for search-as-you-type. Returns OIDs, newest first.
+///
+/// See [`core_lib::git::message_index::MessageQuery`] for the query syntax.
+#[tauri::command]
+#[specta::specta]
+pub fn search_commit_messages<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<String>, String> {
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().ok_or("Commit cache unavailable")?;
+    cache.search_messages(&query, limit).map_err(|e| e.to_string())
+}
+
+/// Caches and indexes every commit of the repository in the background.
+#[tauri::command]
+#[specta::specta]
+pub fn start_commit_indexing<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<JobId, String> {
+    let handle = open_handle(&app, &path)?;
+    let cache = handle.index_cache().cloned().ok_or("Commit cache unavailable")?;
+    let path = handle.path().clone();
+    let title = format!("Index commits of {}", path.display());
+    let id = app.state::<JobManager>().spawn(&app, JobKind::CommitIndex, title, move |ctx| {
+        let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+        let total = commit_cache::cache_all_commits(&repo, &cache, |done, total| {
+            ctx.progress(done as u32, Some(total as u32), None)
+        })
+        .map_err(|e| e.to_string())?;
+        ctx.log(format!("Indexed {} commits", total));
+        Ok(None)
+    });
+    Ok(id)
+}
@@ -0,0 +1,29 @@
+use core_lib::store::settings::{self, Settings};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::autofetch::{AutoFetchStatus, AutoFetcher, PowerConditions};
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_settings<T: Runtime>(app: AppHandle<T>) -> Result<Settings, String> {
+    settings::load_settings(&app).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn save_settings<T: Runtime>(app: AppHandle<T>, settings: Settings) -> Result<(), String> {
+    settings::save_settings(&app, &settings).map_err(|e| e.to_string())
+}
+
+/// Reports whether the machine runs on battery or a metered connection, pausing auto-fetch if configured.
+#[tauri::command]
+#[specta::specta]
+pub fn set_power_conditions<T: Runtime>(app: AppHandle<T>, conditions: PowerConditions) {
+    app.state::<AutoFetcher>().set_conditions(conditions);
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_auto_fetch_status<T: Runtime>(app: AppHandle<T>) -> Vec<AutoFetchStatus> {
+    app.state::<AutoFetcher>().status()
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use core_lib::git::partial_clone::{self, PromisorRemote};
+use core_lib::git::sparse::{self, SparseApplyOutcome, SparseCheckout, SparseDirectory, SparseExcluded, SparseSpec};
+use tauri::{AppHandle, Runtime};
+
+use super::open_handle;
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_sparse_checkout<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<SparseCheckout, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::read_sparse_checkout(&repo).map_err(|e| e.to_string())
+}
+
+/// The subdirectories of `parent` at HEAD for the directory picker, with how much of each is checked out.
+#[tauri::command]
+#[specta::specta]
+pub fn get_sparse_directories<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    parent: String,
+) -> Result<Vec<SparseDirectory>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::sparse_directories(&repo, &parent).map_err(|e| e.to_string())
+}
+
+/// The tracked files the sparse checkout leaves out, the first `limit` of them listed.
+#[tauri::command]
+#[specta::specta]
+pub fn get_sparse_excluded<T: Runtime>(app: AppHandle<T>, path: PathBuf, limit: u32) -> Result<SparseExcluded, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::excluded_paths(&repo, limit as usize).map_err(|e| e.to_string())
+}
+
+/// Enables sparse checkout with `spec` and updates the working tree to it.
+#[tauri::command]
+#[specta::specta]
+pub fn set_sparse_checkout<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    spec: SparseSpec,
+) -> Result<SparseApplyOutcome, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::set_sparse_checkout(&repo, &spec).map_err(|e| e.to_string())
+}
+
+/// Updates the working tree to the current patterns again, e.g. after files with local changes were committed.
+#[tauri::command]
+#[specta::specta]
+pub fn reapply_sparse_checkout<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<SparseApplyOutcome, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::reapply_sparse_checkout(&repo).map_err(|e| e.to_string())
+}
+
+/// Checks out every file again and turns sparse checkout off.
+#[tauri::command]
+#[specta::specta]
+pub fn disable_sparse_checkout<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<SparseApplyOutcome, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    sparse::disable_sparse_checkout(&repo).map_err(|e| e.to_string())
+}
+
+/// The remotes a partial clone was made from, with their filters; empty for a full clone.
+#[tauri::command]
+#[specta::specta]
+pub fn get_promisor_remotes<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<PromisorRemote>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    partial_clone::promisor_remotes(&repo).map_err(|e| e.to_string())
+}
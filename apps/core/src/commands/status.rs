@@ -0,0 +1,15 @@
+use std::path::PathBuf;
+
+use core_lib::git::status::{self, FileStatus};
+use tauri::{AppHandle, Runtime};
+
+use super::open_handle;
+
+/// The changed paths of the repository at `path`, with the LFS object of each LFS file.
+#[tauri::command]
+#[specta::specta]
+pub fn get_file_statuses<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<FileStatus>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    status::file_statuses(&repo).map_err(|e| e.to_string())
+}
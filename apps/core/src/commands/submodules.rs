@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use core_lib::git::submodule::{self, SubmoduleInfo, SubmoduleUpdateOptions};
+use core_lib::store::repos;
+use git2::Repository;
+use log::warn;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::jobs::{JobId, JobKind, JobManager};
+use crate::store::RepoStore;
+
+use super::jobs::report_transfer;
+use super::open_handle;
+
+#[tauri::command]
+#[specta::specta]
+pub fn list_submodules<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<SubmoduleInfo>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    submodule::list_submodules(&repo).map_err(|e| e.to_string())
+}
+
+/// Registers the URLs of the submodules at `paths` (all when empty) and returns their paths.
+#[tauri::command]
+#[specta::specta]
+pub fn init_submodules<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    paths: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    submodule::init_submodules(&repo, &paths).map_err(|e| e.to_string())
+}
+
+/// Copies the submodule URLs from `.gitmodules` to the configuration and returns the synced paths.
+#[tauri::command]
+#[specta::specta]
+pub fn sync_submodules<T: Runtime>(app: AppHandle<T>, path: PathBuf, recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    submodule::sync_submodules(&repo, recursive).map_err(|e| e.to_string())
+}
+
+/// Clones and checks out submodules in the background. The updated paths are the job result.
+#[tauri::command]
+#[specta::specta]
+pub fn start_submodule_update<T: Runtime>(app: AppHandle<T>, path: PathBuf, options: SubmoduleUpdateOptions) -> JobId {
+    let title = format!("Update submodules of {}", path.display());
+    app.state::<JobManager>()
+        .spawn(&app, JobKind::SubmoduleUpdate, title, move |ctx| {
+            let repo = Repository::open(&path).map_err(|e| e.to_string())?;
+            let updated = submodule::update_submodules(&repo, &options, |p| report_transfer(ctx, &p))
+                .map_err(|e| e.to_string())?;
+            ctx.log(format!("Updated {} submodules", updated.len()));
+            serde_json::to_value(updated).map(Some).map_err(|e| e.to_string())
+        })
+}
+
+/// Opens the submodule at `submodule` (relative to the superproject) as its own repository, linked to its parent,
+/// and returns its path.
+#[tauri::command]
+#[specta::specta]
+pub fn open_submodule<T: Runtime>(app: AppHandle<T>, path: PathBuf, submodule: PathBuf) -> Result<PathBuf, String> {
+    let parent = open_handle(&app, &path)?;
+    let nested = {
+        let repo = parent.repo();
+        let workdir = repo.workdir().ok_or("Bare repositories have no submodules")?;
+        repos::canonical_path(&workdir.join(&submodule))
+    };
+    app.state::<RepoStore>()
+        .open_nested_repo(parent.path(), nested.clone())
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = super::watch_repo(&app, nested.clone()) {
+        warn!("Not watching submodule {:?}: {}", nested, e);
+    }
+    Ok(nested)
+}
+
+/// The superproject of a repository opened with [`open_submodule`], if any.
+#[tauri::command]
+#[specta::specta]
+pub fn get_parent_repo<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Option<PathBuf> {
+    let store = app.state::<RepoStore>();
+    let handle = store.get_repo(&repos::canonical_path(&path))?;
+    handle.parent().cloned()
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use core_lib::git::worktree::{self, AddWorktreeOptions, WorktreeInfo};
+use core_lib::store::repos::{self, RepoRecord};
+use git2::Repository;
+use log::warn;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::store::RepoStore;
+
+use super::open_handle;
+
+/// Lists the linked worktrees of the repository at `path`.
+#[tauri::command]
+#[specta::specta]
+pub fn list_worktrees<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<WorktreeInfo>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    worktree::list_worktrees(&repo).map_err(|e| e.to_string())
+}
+
+/// Creates the worktree `name` at `worktree_path` and registers it grouped with the repository at `path`.
+#[tauri::command]
+#[specta::specta]
+pub fn add_worktree<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    name: String,
+    worktree_path: PathBuf,
+    options: AddWorktreeOptions,
+) -> Result<WorktreeInfo, String> {
+    let handle = open_handle(&app, &path)?;
+    let info = worktree::add_worktree(&handle.repo(), &name, &worktree_path, &options).map_err(|e| e.to_string())?;
+    repos::add_worktree(&app, handle.path(), &info.path).map_err(|e| e.to_string())?;
+    Ok(info)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn lock_worktree<T: Runtime>(
+    app: AppHandle<T>,
+    path: PathBuf,
+    name: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    worktree::lock_worktree(&repo, &name, reason.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn unlock_worktree<T: Runtime>(app: AppHandle<T>, path: PathBuf, name: String) -> Result<(), String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    worktree::unlock_worktree(&repo, &name).map_err(|e| e.to_string())
+}
+
+/// Prunes worktrees whose directory is gone and drops them from the registry. Returns the pruned names.
+#[tauri::command]
+#[specta::specta]
+pub fn prune_worktrees<T: Runtime>(app: AppHandle<T>, path: PathBuf) -> Result<Vec<String>, String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let registered = registered_worktrees(&app, handle.path(), &repo)?;
+    let pruned = worktree::prune_worktrees(&repo).map_err(|e| e.to_string())?;
+    for (name, record) in registered {
+        if pruned.contains(&name) {
+            forget_worktree(&app, record);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Deletes the worktree `name` with its directory and drops it from the registry.
+#[tauri::command]
+#[specta::specta]
+pub fn remove_worktree<T: Runtime>(app: AppHandle<T>, path: PathBuf, name: String, force: bool) -> Result<(), String> {
+    let handle = open_handle(&app, &path)?;
+    let repo = handle.repo();
+    let record = registered_worktrees(&app, handle.path(), &repo)?
+        .into_iter()
+        .find(|(worktree, _)| *worktree == name)
+        .map(|(_, record)| record);
+    worktree::remove_worktree(&repo, &name, force).map_err(|e| e.to_string())?;
+    if let Some(record) = record {
+        forget_worktree(&app, record);
+    }
+    Ok(())
+}
+
+/// Opens the worktree `name` as its own repository, linked to the repository at `path` it belongs to.
+#[tauri::command]
+#[specta::specta]
+pub fn open_worktree<T: Runtime>(app: AppHandle<T>, path: PathBuf, name: String) -> Result<RepoRecord, String> {
+    let main = open_handle(&app, &path)?;
+    let worktree_path = {
+        let repo = main.repo();
+        let worktree = repo.find_worktree(&name).map_err(|e| e.to_string())?;
+        repos::canonical_path(worktree.path())
+    };
+    let record = repos::add_worktree(&app, main.path(), &worktree_path).map_err(|e| e.to_string())?;
+    repos::touch_repo(&app, record.id).map_err(|e| e.to_string())?;
+    app.state::<RepoStore>()
+        .open_nested_repo(main.path(), record.path.clone())
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = super::watch_repo(&app, record.path.clone()) {
+        warn!("Not watching worktree {:?}: {}", record.path, e);
+    }
+    Ok(record)
+}
+
+/// The registry records of the linked worktrees of the repository at `main`, by worktree name.
+///
+/// Looked up before a worktree is removed or pruned, while libgit2 still knows its path.
+fn registered_worktrees<T: Runtime>(
+    app: &AppHandle<T>,
+    main: &Path,
+    repo: &Repository,
+) -> Result<Vec<(String, RepoRecord)>, String> {
+    let registry = repos::load_registry(app).map_err(|e| e.to_string())?;
+    let Some(main) = registry.find_by_path(main) else {
+        return Ok(Vec::new());
+    };
+    let worktrees = worktree::list_worktrees(repo).map_err(|e| e.to_string())?;
+    Ok(worktrees
+        .into_iter()
+        .filter_map(|info| {
+            let record = registry.find_worktree(main.id, &info.path)?;
+            Some((info.name, record.clone()))
+        })
+        .collect())
+}
+
+fn forget_worktree<T: Runtime>(app: &AppHandle<T>, record: RepoRecord) {
+    app.state::<RepoStore>().close_repo(&record.path);
+    if let Err(e) = repos::remove_repo_id(app, record.id) {
+        warn!("Failed to unregister worktree {:?}: {}", record.path, e);
+    }
+}
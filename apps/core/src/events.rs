@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use core_lib::git::blame::BlameHunk;
+use core_lib::git::discovery::DiscoveredRepo;
+use core_lib::git::hooks::{HookKind, HookStream};
+use core_lib::git::signature::TrustLevel;
+use core_lib::git::watcher::RepoChange;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri_specta::{collect_events, Event, Events};
+use uuid::Uuid;
+
+use crate::jobs::{JobId, JobInfo};
+
+pub fn collect() -> Events {
+    collect_events![
+        RepoDiscovered,
+        RepoScanFinished,
+        RepoChanged,
+        JobUpdated,
+        JobLogged,
+        IncomingCommits,
+        BlameChunk,
+        SignaturesVerified,
+        HookOutput
+    ]
+}
+
+/// A repository was found by a running discovery scan.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct RepoDiscovered {
+    /// Id of the scan's job.
+    pub scan_id: Uuid,
+    pub repo: DiscoveredRepo,
+    /// The repository is already part of the registry.
+    pub registered: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct RepoScanFinished {
+    pub scan_id: Uuid,
+    pub found: u32,
+    pub error: Option<String>,
+}
+
+/// Files of an open repository changed on disk. Views refresh according to `change.kinds`.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct RepoChanged {
+    pub path: PathBuf,
+    pub change: RepoChange,
+}
+
+/// A background job was queued, changed state or reported progress.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct JobUpdated(pub JobInfo);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct JobLogged {
+    pub id: JobId,
+    pub line: String,
+}
+
+/// A background fetch brought in new commits on remote tracking branches.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct IncomingCommits {
+    pub repo_id: Uuid,
+    pub path: PathBuf,
+    pub branches: Vec<IncomingBranch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct IncomingBranch {
+    /// Short name of the remote tracking branch, e.g. `origin/main`.
+    pub branch: String,
+    pub new_commits: u32,
+}
+
+/// Hunks of the next range of lines of a running blame job.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct BlameChunk {
+    pub job_id: JobId,
+    pub file: PathBuf,
+    pub hunks: Vec<BlameHunk>,
+}
+
+/// Trust levels of the next batch of commits checked by a signature verification job.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct SignaturesVerified {
+    pub job_id: JobId,
+    pub path: PathBuf,
+    pub results: Vec<CommitTrust>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct CommitTrust {
+    pub oid: String,
+    pub trust: TrustLevel,
+}
+
+/// A line printed by a git hook while it runs, e.g. the `pre-commit` hook of a commit being made.
+#[derive(Serialize, Deserialize, Debug, Clone, Type, Event)]
+pub struct HookOutput {
+    pub path: PathBuf,
+    pub hook: HookKind,
+    pub stream: HookStream,
+    pub line: String,
+}
@@ -0,0 +1,424 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use core_lib::store::repos::now;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Runtime};
+use tauri_specta::Event;
+use uuid::Uuid;
+
+use crate::events::{JobLogged, JobUpdated};
+
+pub type JobId = Uuid;
+
+/// Finished jobs kept for [`JobManager::list_jobs`] before the oldest are dropped.
+const MAX_HISTORY: usize = 200;
+/// Log lines kept per job.
+const MAX_LOG_LINES: usize = 500;
+/// Minimum time between two progress events of the same job.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum JobKind {
+    Clone,
+    Fetch,
+    Push,
+    Pull,
+    CommitGraph,
+    DiscoveryScan,
+    Blame,
+    CommitIndex,
+    CommitSearch,
+    SignatureVerification,
+    SubmoduleUpdate,
+    Bundle,
+    Archive,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Type)]
+pub struct JobProgress {
+    pub current: u32,
+    pub total: Option<u32>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Type)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub title: String,
+    pub status: JobStatus,
+    pub progress: Option<JobProgress>,
+    pub error: Option<String>,
+    /// Unix timestamps in seconds.
+    pub queued_at: i64,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+}
+
+/// Outcome of a job's work function. `Err` marks the job as failed with that message.
+pub type JobResult = Result<Option<serde_json::Value>, String>;
+
+struct JobEntry {
+    info: JobInfo,
+    log: VecDeque<String>,
+    cancel: Arc<AtomicBool>,
+    result: Option<serde_json::Value>,
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct JobTable {
+    entries: HashMap<JobId, JobEntry>,
+    /// Job ids in submission order.
+    order: VecDeque<JobId>,
+}
+
+impl JobTable {
+    fn prune(&mut self) {
+        let finished = self
+            .order
+            .iter()
+            .filter(|id| self.entries[*id].info.status.is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(MAX_HISTORY);
+        let entries = &mut self.entries;
+        self.order.retain(|id| {
+            if excess > 0 && entries[id].info.status.is_finished() {
+                entries.remove(id);
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// Runs long operations on a fixed pool of worker threads and tracks their state.
+///
+/// Managed as Tauri state; every state change is emitted as a [`JobUpdated`] event.
+pub struct JobManager {
+    jobs: Arc<Mutex<JobTable>>,
+    sender: Mutex<mpsc::Sender<Task>>,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4).clamp(2, 8);
+        Self::new(workers)
+    }
+}
+
+impl JobManager {
+    pub fn new(workers: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..workers {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("gitultra-job-{}", i))
+                .spawn(move || loop {
+                    let task = receiver.lock().unwrap().recv();
+                    match task {
+                        Ok(task) => task(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("Failed to spawn job worker");
+        }
+
+        Self {
+            jobs: Arc::new(Mutex::new(JobTable::default())),
+            sender: Mutex::new(sender),
+        }
+    }
+
+    /// Queues `work` and returns its id immediately.
+    pub fn spawn<R, F>(&self, app: &AppHandle<R>, kind: JobKind, title: impl Into<String>, work: F) -> JobId
+    where
+        R: Runtime,
+        F: FnOnce(&JobContext<R>) -> JobResult + Send + 'static,
+    {
+        let id = Uuid::new_v4();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let info = JobInfo {
+            id,
+            kind,
+            title: title.into(),
+            status: JobStatus::Queued,
+            progress: None,
+            error: None,
+            queued_at: now(),
+            started_at: None,
+            finished_at: None,
+        };
+        info!("Queued job {} ({:?}): {}", id, kind, info.title);
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.entries.insert(
+                id,
+                JobEntry {
+                    info: info.clone(),
+                    log: VecDeque::new(),
+                    cancel: cancel.clone(),
+                    result: None,
+                },
+            );
+            jobs.order.push_back(id);
+        }
+        emit_update(app, info);
+
+        let ctx = JobContext {
+            id,
+            app: app.clone(),
+            jobs: self.jobs.clone(),
+            cancel,
+            last_progress: Mutex::new(None),
+        };
+        let task: Task = Box::new(move || ctx.run(work));
+        if self.sender.lock().unwrap().send(task).is_err() {
+            error!("Job workers are gone, job {} will never run", id);
+        }
+        id
+    }
+
+    /// Requests cancellation. Returns `false` if the job is unknown or already finished.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.entries.get(&id) {
+            Some(entry) if !entry.info.status.is_finished() => {
+                entry.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_job(&self, id: JobId) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().entries.get(&id).map(|e| e.info.clone())
+    }
+
+    /// All known jobs, most recent first.
+    pub fn list_jobs(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.order.iter().rev().map(|id| jobs.entries[id].info.clone()).collect()
+    }
+
+    pub fn get_log(&self, id: JobId) -> Option<Vec<String>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .entries
+            .get(&id)
+            .map(|e| e.log.iter().cloned().collect())
+    }
+
+    /// Returns the value a succeeded job produced, if any.
+    pub fn get_result(&self, id: JobId) -> Option<serde_json::Value> {
+        self.jobs.lock().unwrap().entries.get(&id).and_then(|e| e.result.clone())
+    }
+
+    /// Removes finished jobs from the history.
+    pub fn clear_finished(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let JobTable { entries, order } = &mut *jobs;
+        order.retain(|id| {
+            let finished = entries[id].info.status.is_finished();
+            if finished {
+                entries.remove(id);
+            }
+            !finished
+        });
+    }
+}
+
+/// Handle given to a running job to report progress and check for cancellation.
+pub struct JobContext<R: Runtime> {
+    id: JobId,
+    app: AppHandle<R>,
+    jobs: Arc<Mutex<JobTable>>,
+    cancel: Arc<AtomicBool>,
+    last_progress: Mutex<Option<Instant>>,
+}
+
+impl<R: Runtime> JobContext<R> {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn app(&self) -> &AppHandle<R> {
+        &self.app
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Records progress, throttling the emitted events.
+    ///
+    /// Returns `false` once the job was cancelled, so it can be returned straight from git2 progress callbacks.
+    pub fn progress(&self, current: u32, total: Option<u32>, message: Option<String>) -> bool {
+        let done = total.is_some_and(|total| current >= total);
+        let mut last = self.last_progress.lock().unwrap();
+        if done || last.is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL) {
+            *last = Some(Instant::now());
+            self.update(|info| {
+                info.progress = Some(JobProgress {
+                    current,
+                    total,
+                    message,
+                })
+            });
+        }
+        !self.is_cancelled()
+    }
+
+    pub fn log(&self, line: impl Into<String>) {
+        let line = line.into();
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(entry) = jobs.entries.get_mut(&self.id) else {
+                return;
+            };
+            if entry.log.len() == MAX_LOG_LINES {
+                entry.log.pop_front();
+            }
+            entry.log.push_back(line.clone());
+        }
+        if let Err(e) = (JobLogged { id: self.id, line }).emit(&self.app) {
+            error!("Failed to emit job log: {:?}", e);
+        }
+    }
+
+    fn run(self, work: impl FnOnce(&Self) -> JobResult) {
+        if self.is_cancelled() {
+            self.finish(JobStatus::Cancelled, None, None);
+            return;
+        }
+        self.update(|info| {
+            info.status = JobStatus::Running;
+            info.started_at = Some(now());
+        });
+
+        // A panicking job fails like any other instead of taking its worker thread down.
+        match panic::catch_unwind(AssertUnwindSafe(|| work(&self))) {
+            _ if self.is_cancelled() => self.finish(JobStatus::Cancelled, None, None),
+            Ok(Ok(result)) => self.finish(JobStatus::Succeeded, None, result),
+            Ok(Err(e)) => {
+                error!("Job {} failed: {}", self.id, e);
+                self.finish(JobStatus::Failed, Some(e), None)
+            }
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                error!("Job {} panicked: {}", self.id, message);
+                self.finish(JobStatus::Failed, Some(message), None)
+            }
+        }
+    }
+
+    fn finish(&self, status: JobStatus, error: Option<String>, result: Option<serde_json::Value>) {
+        info!("Job {} finished: {:?}", self.id, status);
+        let info = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(entry) = jobs.entries.get_mut(&self.id) else {
+                return;
+            };
+            entry.info.status = status;
+            entry.info.error = error;
+            entry.info.finished_at = Some(now());
+            entry.result = result;
+            let info = entry.info.clone();
+            jobs.prune();
+            info
+        };
+        emit_update(&self.app, info);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut JobInfo)) {
+        let info = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(entry) = jobs.entries.get_mut(&self.id) else {
+                return;
+            };
+            f(&mut entry.info);
+            entry.info.clone()
+        };
+        emit_update(&self.app, info);
+    }
+}
+
+fn emit_update<R: Runtime>(app: &AppHandle<R>, info: JobInfo) {
+    if let Err(e) = JobUpdated(info).emit(app) {
+        error!("Failed to emit job update: {:?}", e);
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (None, Some(message)) => message.as_str(),
+        (None, None) => "unknown cause",
+    };
+    format!("The job panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::test::{mock_app, MockRuntime};
+
+    fn wait_until_finished(manager: &JobManager, id: JobId) -> JobInfo {
+        for _ in 0..500 {
+            match manager.get_job(id) {
+                Some(info) if info.status.is_finished() => return info,
+                _ => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        panic!("job {} did not finish", id);
+    }
+
+    #[test]
+    fn test_panicking_job_fails_and_keeps_pool_usable() {
+        let app = mock_app();
+        let app: &AppHandle<MockRuntime> = app.handle();
+        let manager = JobManager::new(1);
+
+        let id = manager.spawn(app, JobKind::Fetch, "panics", |_| panic!("boom"));
+        let info = wait_until_finished(&manager, id);
+        assert_eq!(info.status, JobStatus::Failed);
+        assert_eq!(info.error.as_deref(), Some("The job panicked: boom"));
+
+        let id = manager.spawn(app, JobKind::Fetch, "succeeds", |ctx| {
+            ctx.log("still running");
+            Ok(Some(serde_json::json!(42)))
+        });
+        assert_eq!(wait_until_finished(&manager, id).status, JobStatus::Succeeded);
+        assert_eq!(manager.get_result(id), Some(serde_json::json!(42)));
+        assert_eq!(manager.get_log(id), Some(vec!["still running".to_string()]));
+    }
+}
@@ -1,22 +1,121 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
 
+use tauri::Manager;
 use tauri_plugin_store::StoreExt;
 use tauri_specta::{collect_commands, Builder};
 
 // mod shortcuts;
+pub mod autofetch;
 pub mod commands;
+pub mod events;
+pub mod jobs;
+pub mod store;
 
 const GITULTRA_TAURI_STORE: &str = "gitultra-tauri-store";
 
 pub fn get_builder() -> Builder {
-    Builder::<tauri::Wry>::new().commands(collect_commands![
-        commands::greet,
-        commands::open_repo_directory::<tauri::Wry>,
-        commands::get_commit_graph::<tauri::Wry>,
-        /*         shortcuts::unregister_shortcut::<tauri::Wry>,
-        shortcuts::change_shortcut::<tauri::Wry>,
-        shortcuts::get_current_shortcut::<tauri::Wry>, */
-    ])
+    Builder::<tauri::Wry>::new()
+        .commands(collect_commands![
+            commands::greet,
+            commands::open_repo_directory::<tauri::Wry>,
+            commands::close_repo::<tauri::Wry>,
+            commands::get_repos::<tauri::Wry>,
+            commands::remove_repo::<tauri::Wry>,
+            commands::update_repo::<tauri::Wry>,
+            commands::discovery::scan_for_repos::<tauri::Wry>,
+            commands::discovery::add_repos::<tauri::Wry>,
+            commands::groups::get_groups::<tauri::Wry>,
+            commands::groups::create_group::<tauri::Wry>,
+            commands::groups::rename_group::<tauri::Wry>,
+            commands::groups::set_group_repos::<tauri::Wry>,
+            commands::groups::delete_group::<tauri::Wry>,
+            commands::groups::get_group_status::<tauri::Wry>,
+            commands::groups::start_group_fetch::<tauri::Wry>,
+            commands::groups::switch_group_branch::<tauri::Wry>,
+            commands::jobs::list_jobs::<tauri::Wry>,
+            commands::jobs::get_job::<tauri::Wry>,
+            commands::jobs::get_job_log::<tauri::Wry>,
+            commands::jobs::get_job_result::<tauri::Wry>,
+            commands::jobs::cancel_job::<tauri::Wry>,
+            commands::jobs::clear_finished_jobs::<tauri::Wry>,
+            commands::jobs::start_clone::<tauri::Wry>,
+            commands::jobs::start_fetch::<tauri::Wry>,
+            commands::jobs::start_push::<tauri::Wry>,
+            commands::jobs::start_pull::<tauri::Wry>,
+            commands::jobs::start_commit_graph::<tauri::Wry>,
+            commands::settings::get_settings::<tauri::Wry>,
+            commands::settings::save_settings::<tauri::Wry>,
+            commands::settings::set_power_conditions::<tauri::Wry>,
+            commands::settings::get_auto_fetch_status::<tauri::Wry>,
+            commands::blame::get_blame,
+            commands::blame::start_blame::<tauri::Wry>,
+            commands::commits::create_commit::<tauri::Wry>,
+            commands::commits::create_tag::<tauri::Wry>,
+            commands::commits::get_commit_details::<tauri::Wry>,
+            commands::commits::verify_tag_signature::<tauri::Wry>,
+            commands::commits::start_signature_verification::<tauri::Wry>,
+            commands::commits::clear_signature_cache::<tauri::Wry>,
+            commands::history::get_file_history,
+            commands::journal::get_operations::<tauri::Wry>,
+            commands::journal::undo_last::<tauri::Wry>,
+            commands::journal::redo::<tauri::Wry>,
+            commands::reflog::get_reflog::<tauri::Wry>,
+            commands::reflog::find_lost_commits::<tauri::Wry>,
+            commands::reflog::create_branch_from_reflog::<tauri::Wry>,
+            commands::reflog::reset_to_reflog_entry::<tauri::Wry>,
+            commands::search::start_commit_search::<tauri::Wry>,
+            commands::search::search_commit_messages::<tauri::Wry>,
+            commands::search::start_commit_indexing::<tauri::Wry>,
+            commands::submodules::list_submodules::<tauri::Wry>,
+            commands::submodules::init_submodules::<tauri::Wry>,
+            commands::submodules::sync_submodules::<tauri::Wry>,
+            commands::submodules::start_submodule_update::<tauri::Wry>,
+            commands::submodules::open_submodule::<tauri::Wry>,
+            commands::submodules::get_parent_repo::<tauri::Wry>,
+            commands::worktrees::list_worktrees::<tauri::Wry>,
+            commands::worktrees::add_worktree::<tauri::Wry>,
+            commands::worktrees::lock_worktree::<tauri::Wry>,
+            commands::worktrees::unlock_worktree::<tauri::Wry>,
+            commands::worktrees::prune_worktrees::<tauri::Wry>,
+            commands::worktrees::remove_worktree::<tauri::Wry>,
+            commands::worktrees::open_worktree::<tauri::Wry>,
+            commands::config::get_config::<tauri::Wry>,
+            commands::config::get_common_settings::<tauri::Wry>,
+            commands::config::set_config_value::<tauri::Wry>,
+            commands::config::unset_config_value::<tauri::Wry>,
+            commands::config::set_common_setting::<tauri::Wry>,
+            commands::identities::get_identities::<tauri::Wry>,
+            commands::identities::create_identity::<tauri::Wry>,
+            commands::identities::update_identity::<tauri::Wry>,
+            commands::identities::delete_identity::<tauri::Wry>,
+            commands::identities::set_identity_rules::<tauri::Wry>,
+            commands::identities::get_repo_identity::<tauri::Wry>,
+            commands::identities::write_identity_to_repo::<tauri::Wry>,
+            commands::patches::export_patches::<tauri::Wry>,
+            commands::patches::read_patch_files,
+            commands::patches::check_patch_files::<tauri::Wry>,
+            commands::patches::apply_patch_files::<tauri::Wry>,
+            commands::bundles::get_bundle_info,
+            commands::bundles::verify_bundle::<tauri::Wry>,
+            commands::bundles::start_bundle_create::<tauri::Wry>,
+            commands::bundles::start_bundle_fetch::<tauri::Wry>,
+            commands::archives::start_archive_export::<tauri::Wry>,
+            commands::hooks::get_hooks::<tauri::Wry>,
+            commands::status::get_file_statuses::<tauri::Wry>,
+            commands::lfs::get_lfs_diffs::<tauri::Wry>,
+            commands::sparse::get_sparse_checkout::<tauri::Wry>,
+            commands::sparse::get_sparse_directories::<tauri::Wry>,
+            commands::sparse::get_sparse_excluded::<tauri::Wry>,
+            commands::sparse::set_sparse_checkout::<tauri::Wry>,
+            commands::sparse::reapply_sparse_checkout::<tauri::Wry>,
+            commands::sparse::disable_sparse_checkout::<tauri::Wry>,
+            commands::sparse::get_promisor_remotes::<tauri::Wry>,
+            commands::get_commit_graph::<tauri::Wry>,
+            /*         shortcuts::unregister_shortcut::<tauri::Wry>,
+            shortcuts::change_shortcut::<tauri::Wry>,
+            shortcuts::get_current_shortcut::<tauri::Wry>, */
+        ])
+        .events(events::collect())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -41,11 +140,14 @@ pub fn run() {
         //.plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .manage(store::RepoStore::new())
+        .manage(jobs::JobManager::default())
+        .manage(autofetch::AutoFetcher::default())
         .invoke_handler(builder.invoke_handler())
         //.invoke_handler(tauri::generate_handler![greet])
         .setup(move |app| {
             // this is needed to use specta events
-            //builder.mount_events(app);
+            builder.mount_events(app);
             if cfg!(debug_assertions) {
                 app.handle()
                     .plugin(tauri_plugin_log::Builder::default().level(log::LevelFilter::Info).build())?;
@@ -81,6 +183,7 @@ pub fn run() {
             */
 
             app.store(GITULTRA_TAURI_STORE).expect("Creating the store failed");
+            app.state::<autofetch::AutoFetcher>().start(app.handle().clone());
             //shortcuts::enable_shortcut(app);
 
             Ok(())
@@ -1,60 +1,142 @@
 use core_lib::git;
+use core_lib::git::index_cache::GitIndexCache;
+use core_lib::git::watcher::{RepoChange, RepoWatcher, WatchError};
 use git2::Repository;
+use log::warn;
 use std::sync::MutexGuard;
 use std::{
     collections::HashMap,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct RepoHandle {
     path: PathBuf,
-    repo: Repository
+    repo: Arc<Mutex<Repository>>,
+    index_cache: Option<Arc<GitIndexCache>>,
+    watcher: Option<Arc<RepoWatcher>>,
+    /// The repository this one was opened from through [`RepoStore::open_nested_repo`]: the superproject of a
+    /// submodule or the main repository of a linked worktree.
+    parent: Option<PathBuf>,
 }
 
 impl RepoHandle {
     pub fn new(path: PathBuf, repo: Repository) -> Self {
-        Self {path, repo}
+        let index_cache = match GitIndexCache::open(&path) {
+            Ok(cache) => Some(Arc::new(cache)),
+            Err(e) => {
+                warn!("Index cache unavailable for {:?}: {}", path, e);
+                None
+            }
+        };
+        Self {
+            path,
+            repo: Arc::new(Mutex::new(repo)),
+            index_cache,
+            watcher: None,
+            parent: None,
+        }
     }
 
-    pub fn path(&self)->&PathBuf {
+    pub fn path(&self) -> &PathBuf {
         &self.path
     }
 
-    pub fn repo(&self) -> &Repository {
-        &self.repo
+    pub fn repo(&self) -> MutexGuard<'_, Repository> {
+        self.repo.lock().unwrap()
     }
 
-}
+    pub fn index_cache(&self) -> Option<&Arc<GitIndexCache>> {
+        self.index_cache.as_ref()
+    }
 
+    pub fn is_watched(&self) -> bool {
+        self.watcher.is_some()
+    }
 
-#[derive(Clone)]
+    pub fn parent(&self) -> Option<&PathBuf> {
+        self.parent.as_ref()
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct RepoStore {
     repos: Arc<Mutex<HashMap<PathBuf, RepoHandle>>>,
 }
 
 impl RepoStore {
     pub fn new() -> Self {
-        Self {
-            repos: Arc::new(Mutex::new(HashMap::new())),
-        }
+        Self::default()
     }
 
+    /// Opens the repository at `path`. Already open repositories are left untouched.
     pub fn open_repo(&self, path: PathBuf) -> Result<(), git2::Error> {
+        if self.repos.lock().unwrap().contains_key(&path) {
+            return Ok(());
+        }
         let repo = git::open_repo(&path)?;
         let handle = RepoHandle::new(path.clone(), repo);
         self.repos.lock().unwrap().insert(path, handle);
         Ok(())
     }
 
-    pub fn get_repo(&self, path: &PathBuf) -> Option<RepoHandle> {
+    /// Opens the repository at `path`, e.g. a submodule or linked worktree, as a child of the already open
+    /// repository `parent`.
+    pub fn open_nested_repo(&self, parent: &Path, path: PathBuf) -> Result<(), git2::Error> {
+        if !self.repos.lock().unwrap().contains_key(parent) {
+            return Err(git2::Error::from_str("parent repository is not open"));
+        }
+        self.open_repo(path.clone())?;
+        if let Some(handle) = self.repos.lock().unwrap().get_mut(&path) {
+            handle.parent = Some(parent.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Paths of the open repositories nested in `parent`.
+    pub fn children(&self, parent: &Path) -> Vec<PathBuf> {
+        let repos = self.repos.lock().unwrap();
+        repos
+            .iter()
+            .filter(|(_, handle)| handle.parent.as_deref() == Some(parent))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    pub fn get_repo(&self, path: &Path) -> Option<RepoHandle> {
         self.repos.lock().unwrap().get(path).cloned()
     }
 
     pub fn list_repos(&self) -> Vec<PathBuf> {
         self.repos.lock().unwrap().keys().cloned().collect()
     }
+
+    /// Starts watching an open repository. `on_change` runs on the watcher thread.
+    ///
+    /// Does nothing if the repository is already watched.
+    pub fn watch_repo(&self, path: &Path, on_change: impl Fn(RepoChange) + Send + 'static) -> Result<(), WatchError> {
+        let mut repos = self.repos.lock().unwrap();
+        let Some(handle) = repos.get_mut(path) else {
+            return Err(git2::Error::from_str("repository is not open").into());
+        };
+        if handle.watcher.is_none() {
+            let watcher = RepoWatcher::new(path, handle.index_cache.clone(), on_change)?;
+            handle.watcher = Some(Arc::new(watcher));
+        }
+        Ok(())
+    }
+
+    pub fn unwatch_repo(&self, path: &Path) {
+        if let Some(handle) = self.repos.lock().unwrap().get_mut(path) {
+            handle.watcher = None;
+        }
+    }
+
+    /// Closes the repository, stopping its watcher once no clone of the handle is left.
+    pub fn close_repo(&self, path: &Path) -> Option<RepoHandle> {
+        self.repos.lock().unwrap().remove(path)
+    }
 }
 
 #[cfg(test)]
@@ -81,9 +163,28 @@ mod tests {
         let retrieved_repo = store.get_repo(&temp_dir).unwrap();
 
         // Verify the retrieved repository matches the original
-        assert_eq!(retrieved_repo.path(), repo.path());
+        assert_eq!(retrieved_repo.repo().path(), repo.path());
 
         // Clean up the temporary directory
         fs::remove_dir_all(&temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_open_nested_repo_links_parent() {
+        let temp_dir = std::env::temp_dir().join("gitultra_nested_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let child_dir = temp_dir.join("vendor/lib");
+        Repository::init(&temp_dir).unwrap();
+        Repository::init(&child_dir).unwrap();
+
+        let store = RepoStore::new();
+        assert!(store.open_nested_repo(&temp_dir, child_dir.clone()).is_err());
+
+        store.open_repo(temp_dir.clone()).unwrap();
+        store.open_nested_repo(&temp_dir, child_dir.clone()).unwrap();
+        assert_eq!(store.get_repo(&child_dir).unwrap().parent(), Some(&temp_dir));
+        assert_eq!(store.children(&temp_dir), vec![child_dir]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
 }
@@ -0,0 +1,684 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use git2::{ObjectType, Repository, Tree};
+use glob::{MatchOptions, Pattern};
+use log::info;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tar::{EntryType, Header};
+use zip::write::{SimpleFileOptions, StreamWriter};
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+const ATTRIBUTES_FILE: &str = ".gitattributes";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, Type)]
+pub enum ArchiveFormat {
+    #[default]
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The usual file extension, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct ArchiveOptions {
+    pub format: ArchiveFormat,
+    /// Directory every entry is placed under, e.g. `project-1.0`. Empty puts the entries at the root.
+    pub prefix: String,
+    /// Only archive this directory of the tree, its contents becoming the root of the archive.
+    pub subdirectory: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct ArchiveSummary {
+    pub files: u32,
+    /// Uncompressed size of the archived files.
+    pub bytes: u64,
+    /// Files and directories left out by `export-ignore`.
+    pub ignored: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("{0} is not a directory of the archived tree")]
+    NotADirectory(PathBuf),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Writes the tree of `rev` to `out` as an archive, like `git archive`.
+///
+/// Paths with the `export-ignore` attribute, from the `.gitattributes` files of the archived tree itself and from
+/// `$GIT_DIR/info/attributes`, are left out. Entries are written while the tree is walked, one blob in memory at a
+/// time. `on_progress` receives each archived path and the number of files so far; returning `false` cancels.
+pub fn write_archive(
+    repo: &Repository,
+    rev: &str,
+    options: &ArchiveOptions,
+    out: impl Write,
+    mut on_progress: impl FnMut(&str, u32) -> bool,
+) -> Result<ArchiveSummary, ArchiveError> {
+    let object = repo.revparse_single(rev)?;
+    let commit = object.peel_to_commit().ok();
+    let mtime = match &commit {
+        Some(commit) => commit.time().seconds(),
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64),
+    };
+    let comment = commit.as_ref().map(|commit| commit.id().to_string());
+
+    let info_rules = match fs::read(repo.path().join("info").join("attributes")) {
+        Ok(content) => AttributeRules::parse("", &content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => AttributeRules::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut walker = Walker {
+        repo,
+        info_rules,
+        tree_rules: Vec::new(),
+        summary: ArchiveSummary::default(),
+        on_progress: &mut on_progress,
+    };
+
+    // Attributes of the directories above the archived one still apply to it.
+    let mut tree = object.peel_to_tree()?;
+    let mut base = String::new();
+    if let Some(subdirectory) = &options.subdirectory {
+        for component in subdirectory.iter() {
+            walker.push_rules(&tree, &base)?;
+            let name = component.to_string_lossy();
+            let id = tree
+                .get_name(&name)
+                .filter(|entry| entry.kind() == Some(ObjectType::Tree))
+                .map(|entry| entry.id());
+            let Some(id) = id else {
+                return Err(ArchiveError::NotADirectory(subdirectory.clone()));
+            };
+            tree = repo.find_tree(id)?;
+            base = join(&base, &name);
+        }
+    }
+
+    let mut prefix = options.prefix.trim_matches('/').to_string();
+    if !prefix.is_empty() {
+        prefix.push('/');
+    }
+    let mut sink: Box<dyn EntrySink + '_> = match options.format {
+        ArchiveFormat::Tar => Box::new(TarSink::new(Plain(out), mtime, comment.as_deref())?),
+        ArchiveFormat::TarGz => Box::new(TarSink::new(
+            GzEncoder::new(out, Compression::default()),
+            mtime,
+            comment.as_deref(),
+        )?),
+        ArchiveFormat::Zip => Box::new(ZipSink::new(out, mtime, comment)?),
+    };
+    if !prefix.is_empty() {
+        sink.directory(&prefix)?;
+    }
+    walker.walk(&tree, &base, &prefix, sink.as_mut())?;
+    sink.finish()?;
+    Ok(walker.summary)
+}
+
+/// Writes the archive of `rev` to the file `path`, removing it again if writing fails or is cancelled.
+pub fn export_archive(
+    repo: &Repository,
+    rev: &str,
+    options: &ArchiveOptions,
+    path: &Path,
+    on_progress: impl FnMut(&str, u32) -> bool,
+) -> Result<ArchiveSummary, ArchiveError> {
+    let result = File::create(path)
+        .map_err(ArchiveError::from)
+        .and_then(|file| write_archive(repo, rev, options, BufWriter::new(file), on_progress));
+    match result {
+        Ok(summary) => {
+            info!("Archived {} files of {} to {:?}", summary.files, rev, path);
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(path);
+            Err(e)
+        }
+    }
+}
+
+struct Walker<'a, F: FnMut(&str, u32) -> bool> {
+    repo: &'a Repository,
+    info_rules: AttributeRules,
+    /// The rules of the `.gitattributes` files from the root down to the directory being walked.
+    tree_rules: Vec<AttributeRules>,
+    summary: ArchiveSummary,
+    on_progress: &'a mut F,
+}
+
+impl<F: FnMut(&str, u32) -> bool> Walker<'_, F> {
+    fn push_rules(&mut self, tree: &Tree, base: &str) -> Result<(), ArchiveError> {
+        let rules = match tree.get_name(ATTRIBUTES_FILE) {
+            Some(entry) if entry.kind() == Some(ObjectType::Blob) => {
+                AttributeRules::parse(base, self.repo.find_blob(entry.id())?.content())
+            }
+            _ => AttributeRules::default(),
+        };
+        self.tree_rules.push(rules);
+        Ok(())
+    }
+
+    /// Whether `path` is `export-ignore`d. `$GIT_DIR/info/attributes` comes first, then deeper files before
+    /// shallower ones.
+    fn is_ignored(&self, path: &str, name: &str) -> bool {
+        std::iter::once(&self.info_rules)
+            .chain(self.tree_rules.iter().rev())
+            .find_map(|rules| rules.export_ignore(path, name))
+            .unwrap_or(false)
+    }
+
+    fn walk(&mut self, tree: &Tree, base: &str, prefix: &str, sink: &mut dyn EntrySink) -> Result<(), ArchiveError> {
+        self.push_rules(tree, base)?;
+        for entry in tree.iter() {
+            let name = String::from_utf8_lossy(entry.name_bytes()).into_owned();
+            let path = join(base, &name);
+            if self.is_ignored(&path, &name) {
+                self.summary.ignored += 1;
+                continue;
+            }
+            let archived = format!("{}{}", prefix, name);
+            let mode = entry.filemode();
+            match entry.kind() {
+                Some(ObjectType::Tree) => {
+                    let dir = format!("{}/", archived);
+                    sink.directory(&dir)?;
+                    self.walk(&self.repo.find_tree(entry.id())?, &path, &dir, sink)?;
+                }
+                // Submodules are archived as empty directories.
+                Some(ObjectType::Commit) => sink.directory(&format!("{}/", archived))?,
+                Some(ObjectType::Blob) => {
+                    let blob = self.repo.find_blob(entry.id())?;
+                    match mode {
+                        0o120000 => sink.symlink(&archived, blob.content())?,
+                        _ => sink.file(&archived, mode & 0o111 != 0, blob.content())?,
+                    }
+                    self.summary.files += 1;
+                    self.summary.bytes += blob.size() as u64;
+                    if !(self.on_progress)(&archived, self.summary.files) {
+                        return Err(git2::Error::new(
+                            git2::ErrorCode::User,
+                            git2::ErrorClass::None,
+                            "archive cancelled",
+                        )
+                        .into());
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.tree_rules.pop();
+        Ok(())
+    }
+}
+
+fn join(base: &str, name: &str) -> String {
+    match base {
+        "" => name.to_string(),
+        _ => format!("{}/{}", base, name),
+    }
+}
+
+/// The `export-ignore` lines of one attributes file, in file order.
+#[derive(Default)]
+struct AttributeRules {
+    rules: Vec<(AttributePattern, bool)>,
+}
+
+enum AttributePattern {
+    /// A pattern without a slash, matched against the name of the entry at any depth.
+    Name(Pattern),
+    /// A pattern with a slash, matched against the full path.
+    Path(Pattern),
+}
+
+impl AttributeRules {
+    /// Parses the `export-ignore` settings of the attributes file in the directory `base`. `export-ignore` sets it,
+    /// `-export-ignore` and `!export-ignore` clear it for the paths matched.
+    fn parse(base: &str, content: &[u8]) -> Self {
+        let mut rules = Vec::new();
+        for line in String::from_utf8_lossy(content).lines() {
+            let mut words = line.split_whitespace();
+            let Some(pattern) = words.next().filter(|p| !p.starts_with('#') && !p.starts_with("[attr]")) else {
+                continue;
+            };
+            let Some(ignore) = words.fold(None, |ignore, word| match word {
+                "export-ignore" => Some(true),
+                "-export-ignore" | "!export-ignore" => Some(false),
+                _ => ignore,
+            }) else {
+                continue;
+            };
+            // Trailing slashes never match in attributes files.
+            if pattern.ends_with('/') {
+                continue;
+            }
+            let compiled = match pattern.trim_start_matches('/') {
+                anchored if anchored.contains('/') || pattern.starts_with('/') => {
+                    Pattern::new(&join(&Pattern::escape(base), anchored)).map(AttributePattern::Path)
+                }
+                _ => Pattern::new(pattern).map(AttributePattern::Name),
+            };
+            if let Ok(compiled) = compiled {
+                rules.push((compiled, ignore));
+            }
+        }
+        AttributeRules { rules }
+    }
+
+    /// The `export-ignore` state the last matching line gives `path`, if any. `name` is its last component.
+    fn export_ignore(&self, path: &str, name: &str) -> Option<bool> {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.rules.iter().rev().find_map(|(pattern, ignore)| {
+            let matched = match pattern {
+                AttributePattern::Name(pattern) => pattern.matches_with(name, options),
+                AttributePattern::Path(pattern) => pattern.matches_with(path, options),
+            };
+            matched.then_some(*ignore)
+        })
+    }
+}
+
+/// Receives the entries of an archive in walk order. Directory paths end with a slash.
+trait EntrySink {
+    fn directory(&mut self, path: &str) -> io::Result<()>;
+    fn file(&mut self, path: &str, executable: bool, content: &[u8]) -> io::Result<()>;
+    fn symlink(&mut self, path: &str, target: &[u8]) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError>;
+}
+
+/// The output stream of an archive, gzip streams being finished before the archive is done.
+trait Finish: Write {
+    fn finish_stream(self) -> io::Result<()>;
+}
+
+/// An output stream that only needs flushing once the archive is written.
+struct Plain<W: Write>(W);
+
+impl<W: Write> Write for Plain<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> Finish for Plain<W> {
+    fn finish_stream(mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> Finish for GzEncoder<W> {
+    fn finish_stream(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+/// Writes a tar stream, with GNU long name entries for paths and link targets that do not fit the header.
+struct TarSink<W: Finish> {
+    builder: tar::Builder<W>,
+    mtime: u64,
+}
+
+impl<W: Finish> TarSink<W> {
+    /// Starts the archive with a pax global header carrying the commit id as comment, which
+    /// `git get-tar-commit-id` reads back.
+    fn new(out: W, mtime: i64, comment: Option<&str>) -> io::Result<Self> {
+        let mut sink = TarSink {
+            builder: tar::Builder::new(out),
+            mtime: mtime.max(0) as u64,
+        };
+        if let Some(comment) = comment {
+            let record = pax_record("comment", comment.as_bytes());
+            let mut header = sink.header(EntryType::XGlobalHeader, 0o666, record.len() as u64);
+            sink.builder.append_data(&mut header, "pax_global_header", record.as_slice())?;
+        }
+        Ok(sink)
+    }
+
+    fn header(&self, kind: EntryType, mode: u32, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_mtime(self.mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header
+    }
+}
+
+impl<W: Finish> EntrySink for TarSink<W> {
+    fn directory(&mut self, path: &str) -> io::Result<()> {
+        let mut header = self.header(EntryType::Directory, 0o755, 0);
+        self.builder.append_data(&mut header, path, io::empty())
+    }
+
+    fn file(&mut self, path: &str, executable: bool, content: &[u8]) -> io::Result<()> {
+        let mode = if executable { 0o755 } else { 0o644 };
+        let mut header = self.header(EntryType::Regular, mode, content.len() as u64);
+        self.builder.append_data(&mut header, path, content)
+    }
+
+    fn symlink(&mut self, path: &str, target: &[u8]) -> io::Result<()> {
+        let mut header = self.header(EntryType::Symlink, 0o777, 0);
+        let target = String::from_utf8_lossy(target);
+        self.builder.append_link(&mut header, path, target.as_ref())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError> {
+        self.builder.into_inner()?.finish_stream()?;
+        Ok(())
+    }
+}
+
+/// A pax extended header record, `<length> <key>=<value>\n` where the length counts itself.
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let rest = key.len() + value.len() + 3;
+    let mut length = rest + 1;
+    while length != rest + length.to_string().len() {
+        length = rest + length.to_string().len();
+    }
+    let mut record = format!("{} {}=", length, key).into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// Writes a zip archive, deflating each file. Entries are streamed with data descriptors, so the output does not
+/// need to be seekable; zip64 records are added as needed.
+struct ZipSink<W: Write> {
+    writer: ZipWriter<StreamWriter<W>>,
+    time: DateTime,
+}
+
+impl<W: Write> ZipSink<W> {
+    fn new(out: W, mtime: i64, comment: Option<String>) -> Result<Self, ArchiveError> {
+        let mut writer = ZipWriter::new_stream(out);
+        if let Some(comment) = comment {
+            writer.set_comment(comment)?;
+        }
+        let (time, date) = dos_time(mtime);
+        Ok(ZipSink {
+            writer,
+            time: DateTime::try_from_msdos(date, time).unwrap_or_default(),
+        })
+    }
+
+    fn options(&self, mode: u32, size: usize) -> SimpleFileOptions {
+        SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(self.time)
+            .unix_permissions(mode)
+            .large_file(size as u64 >= u32::MAX as u64)
+    }
+}
+
+impl<W: Write> EntrySink for ZipSink<W> {
+    fn directory(&mut self, path: &str) -> io::Result<()> {
+        let options = self.options(0o755, 0);
+        Ok(self.writer.add_directory(path, options)?)
+    }
+
+    fn file(&mut self, path: &str, executable: bool, content: &[u8]) -> io::Result<()> {
+        let options = self.options(if executable { 0o755 } else { 0o644 }, content.len());
+        self.writer.start_file(path, options)?;
+        self.writer.write_all(content)
+    }
+
+    fn symlink(&mut self, path: &str, target: &[u8]) -> io::Result<()> {
+        let options = self.options(0o777, target.len());
+        Ok(self.writer.add_symlink(path, String::from_utf8_lossy(target), options)?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError> {
+        self.writer.finish()?.into_inner().flush()?;
+        Ok(())
+    }
+}
+
+/// The MS-DOS (time, date) of the unix timestamp `seconds`, in UTC and clamped to 1980, the earliest date zip holds.
+fn dos_time(seconds: i64) -> (u16, u16) {
+    let days = seconds.div_euclid(86400);
+    let secs = seconds.rem_euclid(86400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = ((secs / 3600) << 11) | ((secs % 3600 / 60) << 5) | ((secs % 60) / 2);
+    let date = ((year.min(2107) - 1980) << 9) | (month << 5) | day;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files_as, init_repo};
+    use git2::Signature;
+    use std::io::Read;
+
+    /// A fixed commit time, so the archives carry a known modification time.
+    fn signature() -> Signature<'static> {
+        Signature::new("Test", "test@example.com", &git2::Time::new(1_700_000_000, 0)).unwrap()
+    }
+
+    /// Marks `path` executable, or turns it into a symbolic link to its content, in the index.
+    fn set_mode(repo: &Repository, path: &str, mode: u32) {
+        let mut index = repo.index().unwrap();
+        let mut entry = index.get_path(Path::new(path), 0).unwrap();
+        entry.mode = mode;
+        index.add(&entry).unwrap();
+        index.write().unwrap();
+    }
+
+    fn commit_index(repo: &Repository) {
+        let mut index = repo.index().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = signature();
+        repo.commit(Some("HEAD"), &signature, &signature, "modes", &tree, &[&head])
+            .unwrap();
+    }
+
+    /// The entries of a tar stream as (path, type, mode, link target, content), and the comment of its global header.
+    #[allow(clippy::type_complexity)]
+    fn read_tar(data: impl Read) -> (Vec<(String, EntryType, u32, Option<String>, Vec<u8>)>, Option<String>) {
+        let mut archive = tar::Archive::new(data);
+        let mut entries = Vec::new();
+        let mut comment = None;
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let kind = entry.header().entry_type();
+            if kind == EntryType::XGlobalHeader {
+                for extension in entry.pax_extensions().unwrap().unwrap() {
+                    let extension = extension.unwrap();
+                    if extension.key() == Ok("comment") {
+                        comment = Some(extension.value().unwrap().to_string());
+                    }
+                }
+                continue;
+            }
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let mode = entry.header().mode().unwrap();
+            let link = entry.link_name().unwrap().map(|link| link.to_string_lossy().into_owned());
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            entries.push((path, kind, mode, link, content));
+        }
+        (entries, comment)
+    }
+
+    #[test]
+    fn test_archives_honor_export_ignore() {
+        let repo = init_repo("archive");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let long = format!("src/{}.rs", "n".repeat(120));
+        commit_files_as(
+            &repo,
+            &signature(),
+            &signature(),
+            &[
+                (".gitattributes", "*.log export-ignore\n/tests export-ignore\n"),
+                ("README", "readme\n"),
+                ("build.log", "log\n"),
+                ("tests/a.rs", "test\n"),
+                ("src/main.rs", "fn main() {}\n"),
+                ("src/keep.log", "kept\n"),
+                ("src/.gitattributes", "keep.log -export-ignore\n"),
+                (&long, "long\n"),
+            ],
+            "files",
+        );
+
+        let mut out = Vec::new();
+        let options = ArchiveOptions {
+            prefix: "project-1.0".into(),
+            ..Default::default()
+        };
+        let summary = write_archive(&repo, "HEAD", &options, &mut out, |_, _| true).unwrap();
+        let (entries, _) = read_tar(out.as_slice());
+        let names: Vec<_> = entries.into_iter().map(|(path, ..)| path).collect();
+        assert_eq!(
+            names,
+            vec![
+                "project-1.0/".to_string(),
+                "project-1.0/.gitattributes".into(),
+                "project-1.0/README".into(),
+                "project-1.0/src/".into(),
+                "project-1.0/src/.gitattributes".into(),
+                "project-1.0/src/keep.log".into(),
+                "project-1.0/src/main.rs".into(),
+                format!("project-1.0/{}", long),
+            ]
+        );
+        assert_eq!((summary.files, summary.ignored), (6, 2));
+
+        let mut zip = Vec::new();
+        let options = ArchiveOptions {
+            format: ArchiveFormat::Zip,
+            subdirectory: Some("src".into()),
+            ..Default::default()
+        };
+        let summary = write_archive(&repo, "HEAD", &options, &mut zip, |_, _| true).unwrap();
+        assert_eq!(summary.files, 4);
+        let archive = zip::ZipArchive::new(io::Cursor::new(zip)).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec![".gitattributes", "keep.log", "main.rs", &long[4..]]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_archives_read_back() {
+        let repo = init_repo("archive_read");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let long = format!("{}/file.txt", "d".repeat(110));
+        commit_files_as(
+            &repo,
+            &signature(),
+            &signature(),
+            &[("run.sh", "#!/bin/sh\n"), ("link", "run.sh"), (&long, "long\n")],
+            "files",
+        );
+        set_mode(&repo, "run.sh", 0o100755);
+        set_mode(&repo, "link", 0o120000);
+        commit_index(&repo);
+        let head = repo.head().unwrap().target().unwrap().to_string();
+        let directory = format!("{}/", &long[..110]);
+
+        for format in [ArchiveFormat::Tar, ArchiveFormat::TarGz] {
+            let mut out = Vec::new();
+            let options = ArchiveOptions {
+                format,
+                ..Default::default()
+            };
+            write_archive(&repo, "HEAD", &options, &mut out, |_, _| true).unwrap();
+            let (entries, comment) = match format {
+                ArchiveFormat::TarGz => read_tar(flate2::read::GzDecoder::new(out.as_slice())),
+                _ => read_tar(out.as_slice()),
+            };
+            assert_eq!(comment.as_deref(), Some(head.as_str()));
+            let kinds: Vec<_> = entries
+                .iter()
+                .map(|(path, kind, mode, link, _)| (path.as_str(), *kind, *mode, link.as_deref()))
+                .collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    (directory.as_str(), EntryType::Directory, 0o755, None),
+                    (long.as_str(), EntryType::Regular, 0o644, None),
+                    ("link", EntryType::Symlink, 0o777, Some("run.sh")),
+                    ("run.sh", EntryType::Regular, 0o755, None),
+                ]
+            );
+            assert_eq!(
+                (&entries[1].4[..], &entries[3].4[..]),
+                (&b"long\n"[..], &b"#!/bin/sh\n"[..])
+            );
+        }
+
+        let mut out = Vec::new();
+        let options = ArchiveOptions {
+            format: ArchiveFormat::Zip,
+            ..Default::default()
+        };
+        write_archive(&repo, "HEAD", &options, &mut out, |_, _| true).unwrap();
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(out)).unwrap();
+        assert_eq!(archive.comment(), head.as_bytes());
+        assert_eq!(archive.len(), 4);
+        let mut content = String::new();
+        archive.by_name(&long).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "long\n");
+        let run = archive.by_name("run.sh").unwrap();
+        assert_eq!(run.unix_mode().map(|mode| mode & 0o777), Some(0o755));
+        drop(run);
+        let mut link = archive.by_name("link").unwrap();
+        assert!(link.is_symlink());
+        let mut target = String::new();
+        link.read_to_string(&mut target).unwrap();
+        assert_eq!(target, "run.sh");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use git2::{BlameOptions as Git2BlameOptions, Oid, Repository};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Lines per chunk of hunks handed out when streaming.
+pub const DEFAULT_CHUNK_LINES: u32 = 500;
+
+/// How many times blame is re-run on the parent of an ignored revision before giving up.
+const MAX_IGNORE_DEPTH: usize = 8;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+#[serde(default)]
+pub struct BlameOptions {
+    /// Revision to blame at, `HEAD` when `None`.
+    pub rev: Option<String>,
+    /// First line to blame, 1-based and inclusive.
+    pub start_line: Option<u32>,
+    /// Last line to blame, 1-based and inclusive.
+    pub end_line: Option<u32>,
+    /// Skip the revisions listed in the file configured as `blame.ignoreRevsFile`.
+    pub use_ignore_revs: bool,
+    /// Additional revisions to skip, like `git blame --ignore-rev`.
+    pub ignore_revs: Vec<String>,
+    pub chunk_lines: u32,
+}
+
+impl Default for BlameOptions {
+    fn default() -> Self {
+        Self {
+            rev: None,
+            start_line: None,
+            end_line: None,
+            use_ignore_revs: true,
+            ignore_revs: Vec::new(),
+            chunk_lines: DEFAULT_CHUNK_LINES,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct BlameHunk {
+    /// First line of the hunk in the blamed revision, 1-based.
+    pub start_line: u32,
+    pub lines: u32,
+    pub commit_oid: String,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    /// Author time in seconds since the epoch and its UTC offset in minutes.
+    pub author_time: i64,
+    pub author_offset_minutes: i32,
+    /// Path and first line of the hunk in `commit_oid`.
+    pub orig_path: Option<PathBuf>,
+    pub orig_start_line: u32,
+    /// The hunk reached the start of history (or the oldest allowed commit).
+    pub boundary: bool,
+}
+
+/// Blames `path`, handing hunks to `on_hunks` one chunk of lines at a time along with the number of lines blamed in
+/// total.
+///
+/// Each chunk is blamed on its own, so early lines arrive before the rest of the file is blamed. Returning `false`
+/// from `on_hunks` stops before the next chunk. Returns the total number of lines blamed.
+pub fn blame_file(
+    repo: &Repository,
+    path: &Path,
+    options: &BlameOptions,
+    mut on_hunks: impl FnMut(Vec<BlameHunk>, u32) -> bool,
+) -> Result<u32, git2::Error> {
+    let commit = match &options.rev {
+        Some(rev) => repo.revparse_single(rev)?.peel_to_commit()?,
+        None => repo.head()?.peel_to_commit()?,
+    };
+    let total_lines = count_lines(repo, commit.id(), path)?;
+    let first = options.start_line.unwrap_or(1).max(1);
+    let last = options.end_line.unwrap_or(total_lines).min(total_lines);
+    if total_lines == 0 || first > last {
+        return Ok(0);
+    }
+
+    let ignored = ignored_revs(repo, options)?;
+    let total = last - first + 1;
+    let chunk = options.chunk_lines.max(1);
+    let mut start = first;
+    while start <= last {
+        let end = start.saturating_add(chunk - 1).min(last);
+        let hunks = blame_range(repo, commit.id(), path, start, end, &ignored, 0)?;
+        if !on_hunks(hunks, total) || end == last {
+            break;
+        }
+        start = end + 1;
+    }
+
+    Ok(total)
+}
+
+/// Blames `path` at `commit` for lines `start..=end`, re-blaming hunks of ignored revisions at their parent.
+fn blame_range(
+    repo: &Repository,
+    commit: Oid,
+    path: &Path,
+    start: u32,
+    end: u32,
+    ignored: &HashSet<Oid>,
+    depth: usize,
+) -> Result<Vec<BlameHunk>, git2::Error> {
+    let mut opts = Git2BlameOptions::new();
+    opts.newest_commit(commit).min_line(start as usize).max_line(end as usize);
+    let blame = repo.blame_file(path, Some(&mut opts))?;
+
+    let mut hunks = Vec::new();
+    for hunk in blame.iter() {
+        let signature = hunk.final_signature();
+        let result = BlameHunk {
+            start_line: hunk.final_start_line() as u32,
+            lines: hunk.lines_in_hunk() as u32,
+            commit_oid: hunk.final_commit_id().to_string(),
+            author_name: signature.name().map(str::to_string),
+            author_email: signature.email().map(str::to_string),
+            author_time: signature.when().seconds(),
+            author_offset_minutes: signature.when().offset_minutes(),
+            orig_path: hunk.path().map(Path::to_path_buf),
+            orig_start_line: hunk.orig_start_line() as u32,
+            boundary: hunk.is_boundary(),
+        };
+
+        if ignored.contains(&hunk.final_commit_id()) && depth < MAX_IGNORE_DEPTH {
+            match reblame_at_parent(repo, &result, ignored, depth) {
+                Ok(Some(parent_hunks)) => {
+                    hunks.extend(parent_hunks);
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => debug!("Keeping ignored revision {}: {}", result.commit_oid, e),
+            }
+        }
+        hunks.push(result);
+    }
+
+    // Clip to the requested range, hunks at the edges may extend past it.
+    hunks.retain_mut(|hunk| {
+        let hunk_end = hunk.start_line + hunk.lines - 1;
+        if hunk_end < start || hunk.start_line > end {
+            return false;
+        }
+        if hunk.start_line < start {
+            hunk.orig_start_line += start - hunk.start_line;
+            hunk.start_line = start;
+        }
+        hunk.lines = hunk_end.min(end) + 1 - hunk.start_line;
+        true
+    });
+    Ok(hunks)
+}
+
+/// Attributes the lines of a hunk from an ignored commit to that commit's first parent.
+///
+/// Like `git blame --ignore-rev`, lines are assumed to sit at the same position in the parent; returns `None`
+/// when the parent does not have them, so the ignored commit keeps the blame.
+fn reblame_at_parent(
+    repo: &Repository,
+    hunk: &BlameHunk,
+    ignored: &HashSet<Oid>,
+    depth: usize,
+) -> Result<Option<Vec<BlameHunk>>, git2::Error> {
+    let commit = repo.find_commit(Oid::from_str(&hunk.commit_oid)?)?;
+    let Some(parent) = commit.parents().next() else {
+        return Ok(None);
+    };
+    let Some(orig_path) = &hunk.orig_path else {
+        return Ok(None);
+    };
+    let parent_lines = count_lines(repo, parent.id(), orig_path)?;
+    let orig_end = hunk.orig_start_line + hunk.lines - 1;
+    if orig_end > parent_lines {
+        return Ok(None);
+    }
+
+    let parent_hunks = blame_range(
+        repo,
+        parent.id(),
+        orig_path,
+        hunk.orig_start_line,
+        orig_end,
+        ignored,
+        depth + 1,
+    )?;
+    Ok(Some(
+        parent_hunks
+            .into_iter()
+            .map(|mut h| {
+                h.start_line = hunk.start_line + (h.start_line - hunk.orig_start_line);
+                h
+            })
+            .collect(),
+    ))
+}
+
+fn count_lines(repo: &Repository, commit: Oid, path: &Path) -> Result<u32, git2::Error> {
+    let entry = repo.find_commit(commit)?.tree()?.get_path(path)?;
+    let blob = repo.find_blob(entry.id())?;
+    let content = blob.content();
+    let newlines = content.iter().filter(|b| **b == b'\n').count();
+    let trailing = !content.is_empty() && !content.ends_with(b"\n");
+    Ok((newlines + trailing as usize) as u32)
+}
+
+/// Collects the revisions to skip from `blame.ignoreRevsFile` and the explicit list in `options`.
+fn ignored_revs(repo: &Repository, options: &BlameOptions) -> Result<HashSet<Oid>, git2::Error> {
+    let mut revs = HashSet::new();
+    for rev in &options.ignore_revs {
+        revs.insert(repo.revparse_single(rev)?.peel_to_commit()?.id());
+    }
+
+    if !options.use_ignore_revs {
+        return Ok(revs);
+    }
+    let Ok(file) = repo.config()?.get_path("blame.ignoreRevsFile") else {
+        return Ok(revs);
+    };
+    let file = match (file.is_relative(), repo.workdir()) {
+        (true, Some(workdir)) => workdir.join(file),
+        _ => file,
+    };
+    let content = match std::fs::read_to_string(&file) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Cannot read blame.ignoreRevsFile {:?}: {}", file, e);
+            return Ok(revs);
+        }
+    };
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match repo.revparse_single(line).and_then(|o| o.peel_to_commit()) {
+            Ok(commit) => {
+                revs.insert(commit.id());
+            }
+            Err(e) => warn!("Ignoring invalid revision {:?} in {:?}: {}", line, file, e),
+        }
+    }
+    Ok(revs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+
+    fn blame(repo: &Repository, options: &BlameOptions) -> Vec<Vec<BlameHunk>> {
+        let mut chunks = Vec::new();
+        blame_file(repo, Path::new("file.txt"), options, |hunks, _| {
+            chunks.push(hunks);
+            true
+        })
+        .unwrap();
+        chunks
+    }
+
+    fn owners(chunks: &[Vec<BlameHunk>]) -> Vec<(u32, u32, String)> {
+        chunks
+            .iter()
+            .flatten()
+            .map(|h| (h.start_line, h.lines, h.commit_oid.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_blame_ranges_chunks_and_ignore_revs() {
+        let repo = init_repo("blame");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let first = commit_files(&repo, &[("file.txt", "1\n2\n3\n4\n5\n")], "first").to_string();
+        let second = commit_files(&repo, &[("file.txt", "1\nTWO\nTHREE\n4\n5\n")], "second").to_string();
+
+        let chunks = blame(&repo, &BlameOptions::default());
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            owners(&chunks),
+            vec![(1, 1, first.clone()), (2, 2, second.clone()), (4, 2, first.clone())]
+        );
+
+        // Only lines 2 to 4, handed out two lines at a time; the hunk of lines 2-3 stays whole, 4 is clipped.
+        let options = BlameOptions {
+            start_line: Some(2),
+            end_line: Some(4),
+            chunk_lines: 2,
+            ..Default::default()
+        };
+        let chunks = blame(&repo, &options);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(owners(&chunks), vec![(2, 2, second.clone()), (4, 1, first.clone())]);
+        assert_eq!(chunks[1][0].orig_start_line, 4);
+
+        // A chunk boundary in the middle of a hunk splits it.
+        let options = BlameOptions {
+            start_line: Some(3),
+            chunk_lines: 2,
+            ..Default::default()
+        };
+        let chunks = blame(&repo, &options);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|c| c.iter().map(|h| h.lines).sum::<u32>())
+                .collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert_eq!(
+            owners(&chunks),
+            vec![(3, 1, second.clone()), (4, 1, first.clone()), (5, 1, first.clone())]
+        );
+        assert_eq!(chunks[1][0].orig_start_line, 5);
+
+        // Revisions from blame.ignoreRevsFile pass their lines on to the parent.
+        fs::write(
+            dir.join(".git-blame-ignore-revs"),
+            format!("# formatting\n{}\n", second),
+        )
+        .unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("blame.ignoreRevsFile", ".git-blame-ignore-revs")
+            .unwrap();
+        let chunks = blame(&repo, &BlameOptions::default());
+        assert!(owners(&chunks).iter().all(|(_, _, oid)| *oid == first));
+        assert_eq!(owners(&chunks).iter().map(|(_, lines, _)| lines).sum::<u32>(), 5);
+
+        let options = BlameOptions {
+            use_ignore_revs: false,
+            ..Default::default()
+        };
+        assert!(owners(&blame(&repo, &options)).iter().any(|(_, _, oid)| *oid == second));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_blame_stops_after_rejected_chunk() {
+        let repo = init_repo("blame_stop");
+        commit_files(&repo, &[("file.txt", "1\n2\n3\n4\n5\n")], "first");
+
+        let options = BlameOptions {
+            chunk_lines: 2,
+            ..Default::default()
+        };
+        let mut chunks = Vec::new();
+        let total = blame_file(&repo, Path::new("file.txt"), &options, |hunks, _| {
+            chunks.push(hunks);
+            false
+        })
+        .unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(chunks.len(), 1);
+        let (start, lines, _) = &owners(&chunks)[0];
+        assert_eq!((*start, *lines), (1, 2));
+
+        let _ = fs::remove_dir_all(repo.workdir().unwrap());
+    }
+}
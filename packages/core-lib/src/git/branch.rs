@@ -0,0 +1,130 @@
+use git2::build::CheckoutBuilder;
+use git2::{BranchType, Repository};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::sparse;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum SwitchOutcome {
+    Switched,
+    /// A local branch was created from the matching remote tracking branch and checked out.
+    CreatedFromRemote,
+    AlreadyOnBranch,
+    NotFound,
+}
+
+/// Checks out the local branch `name`, like `git switch <name>`.
+///
+/// If only a remote tracking branch of that name exists on exactly one remote, a local branch
+/// tracking it is created first. Local changes are kept; the checkout fails if they conflict.
+pub fn switch_branch(repo: &Repository, name: &str) -> Result<SwitchOutcome, git2::Error> {
+    if let Ok(head) = repo.head() {
+        if head.is_branch() && head.shorthand() == Some(name) {
+            return Ok(SwitchOutcome::AlreadyOnBranch);
+        }
+    }
+
+    let (branch, outcome) = match repo.find_branch(name, BranchType::Local) {
+        Ok(branch) => (branch, SwitchOutcome::Switched),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => {
+            let Some(remote_branch) = find_unique_remote_branch(repo, name)? else {
+                return Ok(SwitchOutcome::NotFound);
+            };
+            let commit = remote_branch.get().peel_to_commit()?;
+            let mut branch = repo.branch(name, &commit, false)?;
+            branch.set_upstream(remote_branch.name()?)?;
+            (branch, SwitchOutcome::CreatedFromRemote)
+        }
+        Err(e) => return Err(e),
+    };
+
+    if let Err(e) = checkout_branch(repo, &branch) {
+        // Don't leave a branch behind that the failed switch created.
+        if outcome == SwitchOutcome::CreatedFromRemote {
+            let mut branch = branch;
+            if let Err(delete_error) = branch.delete() {
+                warn!("Failed to delete branch {} after failed switch: {}", name, delete_error);
+            }
+        }
+        return Err(e);
+    }
+
+    Ok(outcome)
+}
+
+fn checkout_branch(repo: &Repository, branch: &git2::Branch) -> Result<(), git2::Error> {
+    let refname = branch
+        .get()
+        .name()
+        .ok_or_else(|| git2::Error::from_str("branch name is not valid utf-8"))?
+        .to_string();
+    let tree = branch.get().peel_to_tree()?;
+    sparse::prepare_checkout(repo, &tree)?;
+    let checked_out = repo
+        .checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))
+        .and_then(|()| repo.set_head(&refname));
+    // The sparse checkout is put back whether or not the switch went through.
+    let restored = sparse::restore_after_checkout(repo);
+    checked_out.and(restored)
+}
+
+fn find_unique_remote_branch<'a>(repo: &'a Repository, name: &str) -> Result<Option<git2::Branch<'a>>, git2::Error> {
+    let mut candidates = Vec::new();
+    for remote in repo.remotes()?.iter().flatten() {
+        if let Ok(branch) = repo.find_branch(&format!("{}/{}", remote, name), BranchType::Remote) {
+            candidates.push(branch);
+        }
+    }
+    Ok(if candidates.len() == 1 { candidates.pop() } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::commit_files;
+    use std::fs;
+
+    #[test]
+    fn test_switch_branch() {
+        let dir = std::env::temp_dir().join("gitultra_branch_test");
+        let _ = fs::remove_dir_all(&dir);
+        let origin = Repository::init(dir.join("origin")).unwrap();
+        commit_files(&origin, &[("a.txt", "main")], "main");
+        let main = origin.head().unwrap().shorthand().unwrap().to_string();
+        let base = origin.head().unwrap().peel_to_commit().unwrap();
+        origin.branch("feature", &base, false).unwrap();
+        origin.set_head("refs/heads/feature").unwrap();
+        commit_files(&origin, &[("a.txt", "feature")], "feature");
+        origin.set_head(&format!("refs/heads/{}", main)).unwrap();
+
+        let repo = git2::build::RepoBuilder::new()
+            .clone(dir.join("origin").to_str().unwrap(), &dir.join("clone"))
+            .unwrap();
+        let workdir = repo.workdir().unwrap().to_path_buf();
+
+        assert_eq!(switch_branch(&repo, &main).unwrap(), SwitchOutcome::AlreadyOnBranch);
+        assert_eq!(switch_branch(&repo, "missing").unwrap(), SwitchOutcome::NotFound);
+
+        // A conflicting local change makes the checkout fail; the branch created for it is removed again.
+        fs::write(workdir.join("a.txt"), "local").unwrap();
+        assert!(switch_branch(&repo, "feature").is_err());
+        assert!(repo.find_branch("feature", BranchType::Local).is_err());
+        assert_eq!(repo.head().unwrap().shorthand(), Some(main.as_str()));
+
+        fs::write(workdir.join("a.txt"), "main").unwrap();
+        assert_eq!(
+            switch_branch(&repo, "feature").unwrap(),
+            SwitchOutcome::CreatedFromRemote
+        );
+        assert_eq!(fs::read_to_string(workdir.join("a.txt")).unwrap(), "feature");
+        let feature = repo.find_branch("feature", BranchType::Local).unwrap();
+        assert_eq!(feature.upstream().unwrap().name().unwrap(), Some("origin/feature"));
+
+        assert_eq!(switch_branch(&repo, &main).unwrap(), SwitchOutcome::Switched);
+        assert_eq!(fs::read_to_string(workdir.join("a.txt")).unwrap(), "main");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,412 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use git2::{ObjectType, Oid, PackBuilderStage, Repository, Revwalk};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::remote::{FetchProgress, FetchSummary, UpdatedRef};
+
+const BUNDLE_V2: &str = "# v2 git bundle";
+const BUNDLE_V3: &str = "# v3 git bundle";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct BundleRef {
+    pub name: String,
+    pub oid: String,
+}
+
+/// The header of a bundle file: the refs it carries and the commits the receiving repository must already have.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct BundleInfo {
+    pub version: u32,
+    pub prerequisites: Vec<String>,
+    pub refs: Vec<BundleRef>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("Not a git bundle")]
+    NotABundle,
+    #[error("Unsupported bundle capability {0}")]
+    Unsupported(String),
+    #[error("Refusing to create an empty bundle, name at least one ref")]
+    Empty,
+    #[error("The repository lacks the prerequisite commits {}", .0.join(", "))]
+    MissingPrerequisites(Vec<String>),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Whether `path` is a bundle file, e.g. the URL of a remote used to move history between offline machines.
+pub fn is_bundle(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut line = String::new();
+    BufReader::new(file).take(64).read_line(&mut line).is_ok() && version(line.trim_end()).is_some()
+}
+
+pub fn read_bundle_info(path: &Path) -> Result<BundleInfo, BundleError> {
+    read_header(&mut BufReader::new(File::open(path)?))
+}
+
+/// Lists the prerequisite commits of the bundle that `repo` does not have, so fetching from it would fail.
+pub fn missing_prerequisites(repo: &Repository, info: &BundleInfo) -> Result<Vec<String>, BundleError> {
+    let odb = repo.odb()?;
+    let mut missing = Vec::new();
+    for oid in &info.prerequisites {
+        if !odb.exists(Oid::from_str(oid)?) {
+            missing.push(oid.clone());
+        }
+    }
+    Ok(missing)
+}
+
+/// Writes a bundle of `revs` to `path`, like `git bundle create`.
+///
+/// Each rev is a ref (`main`, `v1.0`, `HEAD`), a range whose end is a ref (`v1.0..main`) or an exclusion (`^v1.0`).
+/// The refs are recorded in the bundle, excluded commits become its prerequisites. `on_progress` receives the stage,
+/// the objects done and their total; returning `false` cancels.
+pub fn create_bundle(
+    repo: &Repository,
+    path: &Path,
+    revs: &[String],
+    mut on_progress: impl FnMut(&str, u32, u32) -> bool,
+) -> Result<BundleInfo, BundleError> {
+    let mut refs = Vec::new();
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for rev in revs {
+        if let Some(hidden) = rev.strip_prefix('^') {
+            exclude.push(repo.revparse_single(hidden)?.peel_to_commit()?.id());
+            continue;
+        }
+        let tip = match rev.split_once("..") {
+            Some((from, to)) => {
+                exclude.push(repo.revparse_single(from)?.peel_to_commit()?.id());
+                to
+            }
+            None => rev.as_str(),
+        };
+        // HEAD is recorded as is rather than as the branch it points to, like git does.
+        let reference = match tip {
+            "HEAD" => repo.find_reference("HEAD")?,
+            _ => repo.resolve_reference_from_short_name(tip)?,
+        };
+        let oid = reference.resolve()?.target().ok_or(BundleError::Empty)?;
+        include.push(repo.find_object(oid, None)?.peel_to_commit()?.id());
+        refs.push(BundleRef {
+            name: reference.name().unwrap_or(tip).to_string(),
+            oid: oid.to_string(),
+        });
+    }
+    if refs.is_empty() {
+        return Err(BundleError::Empty);
+    }
+
+    let commits = revwalk(repo, &include, &exclude)?.collect::<Result<HashSet<Oid>, _>>()?;
+    let mut prerequisites = Vec::new();
+    for oid in &commits {
+        for parent in repo.find_commit(*oid)?.parents() {
+            if !commits.contains(&parent.id()) && !prerequisites.iter().any(|(oid, _)| *oid == parent.id()) {
+                prerequisites.push((parent.id(), parent.summary().unwrap_or_default().to_string()));
+            }
+        }
+    }
+
+    let mut builder = repo.packbuilder()?;
+    builder.insert_walk(&mut revwalk(repo, &include, &exclude)?)?;
+    for bundle_ref in &refs {
+        let oid = Oid::from_str(&bundle_ref.oid)?;
+        if repo.find_object(oid, None)?.kind() == Some(ObjectType::Tag) {
+            builder.insert_object(oid, None)?;
+        }
+    }
+    builder.set_progress_callback(|stage, current, total| match stage {
+        PackBuilderStage::AddingObjects => on_progress("Counting objects", current, total),
+        PackBuilderStage::Deltafication => on_progress("Compressing objects", current, total),
+    })?;
+
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", BUNDLE_V2)?;
+        for (oid, summary) in &prerequisites {
+            writeln!(out, "-{} {}", oid, summary)?;
+        }
+        for bundle_ref in &refs {
+            writeln!(out, "{} {}", bundle_ref.oid, bundle_ref.name)?;
+        }
+        writeln!(out)?;
+        let mut error = None;
+        builder.foreach(|chunk| match out.write_all(chunk) {
+            Ok(()) => true,
+            Err(e) => {
+                error = Some(e);
+                false
+            }
+        })?;
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+        out.flush()?;
+        Ok::<_, BundleError>(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+    info!("Created bundle {:?} with {} objects", path, builder.object_count());
+
+    Ok(BundleInfo {
+        version: 2,
+        prerequisites: prerequisites.iter().map(|(oid, _)| oid.to_string()).collect(),
+        refs,
+    })
+}
+
+/// Fetches the objects of a bundle and updates the refs matching `refspecs`, like fetching from a remote.
+///
+/// Refspecs are `[+]src:dst` with an optional `*` in both sides; refs not matched by any are ignored and non-forced
+/// updates must be fast-forwards. `label` names the bundle in progress reports and the summary.
+pub fn fetch_bundle(
+    repo: &Repository,
+    path: &Path,
+    label: &str,
+    refspecs: &[String],
+    mut on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<FetchSummary, BundleError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let info = read_header(&mut reader)?;
+    let missing = missing_prerequisites(repo, &info)?;
+    if !missing.is_empty() {
+        return Err(BundleError::MissingPrerequisites(missing));
+    }
+
+    // The indexer does not count bytes when fed directly, so they are counted while copying.
+    let received_bytes = Cell::new(0);
+    let mut received_objects = 0;
+    let odb = repo.odb()?;
+    let mut writer = odb.packwriter()?;
+    writer.progress(|stats| {
+        received_objects = stats.received_objects() as u32;
+        on_progress(FetchProgress {
+            remote: label.to_string(),
+            total_objects: stats.total_objects() as u32,
+            received_objects: stats.received_objects() as u32,
+            indexed_objects: stats.indexed_objects() as u32,
+            received_bytes: received_bytes.get(),
+        })
+    });
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        received_bytes.set(received_bytes.get() + read as u64);
+    }
+    writer.commit()?;
+    drop(writer);
+    let received_bytes = received_bytes.get();
+
+    let message = format!("fetch: bundle {}", path.display());
+    let mut updated_refs = Vec::new();
+    for bundle_ref in &info.refs {
+        let Some((destination, force)) = refspecs.iter().find_map(|spec| map_ref(spec, &bundle_ref.name)) else {
+            continue;
+        };
+        let new = Oid::from_str(&bundle_ref.oid)?;
+        let old = repo.refname_to_id(&destination).ok();
+        if old == Some(new) {
+            continue;
+        }
+        if let (Some(old), false) = (old, force) {
+            if !repo.graph_descendant_of(new, old).unwrap_or(false) {
+                debug!("{}: not a fast-forward, skipping", destination);
+                continue;
+            }
+        }
+        repo.reference(&destination, new, true, &message)?;
+        updated_refs.push(UpdatedRef {
+            name: destination,
+            old_oid: old.map(|oid| oid.to_string()),
+            new_oid: new.to_string(),
+        });
+    }
+    info!(
+        "Fetched bundle {:?}: {} objects, {} refs updated",
+        path,
+        received_objects,
+        updated_refs.len()
+    );
+
+    Ok(FetchSummary {
+        remote: label.to_string(),
+        updated_refs,
+        received_objects,
+        received_bytes,
+    })
+}
+
+fn revwalk<'r>(repo: &'r Repository, include: &[Oid], exclude: &[Oid]) -> Result<Revwalk<'r>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    include.iter().try_for_each(|oid| walk.push(*oid))?;
+    exclude.iter().try_for_each(|oid| walk.hide(*oid))?;
+    Ok(walk)
+}
+
+fn version(line: &str) -> Option<u32> {
+    match line {
+        BUNDLE_V2 => Some(2),
+        BUNDLE_V3 => Some(3),
+        _ => None,
+    }
+}
+
+/// Reads the header up to the blank line, leaving `reader` at the start of the pack.
+fn read_header(reader: &mut impl BufRead) -> Result<BundleInfo, BundleError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let version = version(line.trim_end()).ok_or(BundleError::NotABundle)?;
+
+    let mut info = BundleInfo {
+        version,
+        prerequisites: Vec::new(),
+        refs: Vec::new(),
+    };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(BundleError::NotABundle);
+        }
+        let line = line.trim_end_matches('\n');
+        if line.is_empty() {
+            return Ok(info);
+        }
+        if let Some(capability) = line.strip_prefix('@') {
+            if capability != "object-format=sha1" {
+                return Err(BundleError::Unsupported(capability.to_string()));
+            }
+        } else if let Some(prerequisite) = line.strip_prefix('-') {
+            let oid = prerequisite.split(' ').next().unwrap_or_default();
+            info.prerequisites.push(oid.to_string());
+        } else {
+            let (oid, name) = line.split_once(' ').ok_or(BundleError::NotABundle)?;
+            info.refs.push(BundleRef {
+                name: name.to_string(),
+                oid: oid.to_string(),
+            });
+        }
+    }
+}
+
+/// Maps `name` through a `[+]src:dst` refspec, returning the destination and whether the update is forced.
+fn map_ref(spec: &str, name: &str) -> Option<(String, bool)> {
+    let (force, spec) = match spec.strip_prefix('+') {
+        Some(spec) => (true, spec),
+        None => (false, spec),
+    };
+    let (src, dst) = spec.split_once(':')?;
+    if dst.is_empty() {
+        return None;
+    }
+    let destination = match (src.split_once('*'), dst.split_once('*')) {
+        (Some((prefix, suffix)), Some((dst_prefix, dst_suffix))) => {
+            let matched = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            format!("{}{}{}", dst_prefix, matched, dst_suffix)
+        }
+        (None, None) if src == name => dst.to_string(),
+        _ => return None,
+    };
+    Some((destination, force))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+
+    #[test]
+    fn test_map_ref() {
+        assert_eq!(
+            map_ref("+refs/heads/*:refs/remotes/usb/*", "refs/heads/feature/x"),
+            Some(("refs/remotes/usb/feature/x".to_string(), true))
+        );
+        assert_eq!(
+            map_ref("refs/tags/*:refs/tags/*", "refs/tags/v1"),
+            Some(("refs/tags/v1".to_string(), false))
+        );
+        assert_eq!(map_ref("refs/heads/main:refs/heads/main", "refs/heads/dev"), None);
+        assert_eq!(map_ref("refs/heads/*:refs/remotes/usb/*", "HEAD"), None);
+    }
+
+    #[test]
+    fn test_create_and_fetch_incremental_bundle() {
+        let temp_dir = std::env::temp_dir().join("gitultra_bundle_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let source = Repository::init(temp_dir.join("source")).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let mut parent = None;
+        for i in 0..3 {
+            let mut builder = source.treebuilder(None).unwrap();
+            let blob = source.blob(format!("version {}\n", i).as_bytes()).unwrap();
+            builder.insert("file.txt", blob, 0o100644).unwrap();
+            let tree = source.find_tree(builder.write().unwrap()).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let oid = source
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    &format!("commit {}", i),
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            parent = Some(source.find_commit(oid).unwrap());
+        }
+        source
+            .branch("first", &parent.as_ref().unwrap().parent(0).unwrap(), false)
+            .unwrap();
+
+        let full = temp_dir.join("full.bundle");
+        let info = create_bundle(&source, &full, &["first".to_string()], |_, _, _| true).unwrap();
+        assert!(is_bundle(&full));
+        assert!(info.prerequisites.is_empty());
+        assert_eq!(read_bundle_info(&full).unwrap(), info);
+
+        let incremental = temp_dir.join("incremental.bundle");
+        let range = "first..HEAD".to_string();
+        let info = create_bundle(&source, &incremental, &[range], |_, _, _| true).unwrap();
+        assert_eq!(info.refs[0].name, "HEAD");
+        assert_eq!(info.prerequisites.len(), 1);
+
+        let target = Repository::init_bare(temp_dir.join("target.git")).unwrap();
+        let refspecs = vec![
+            "+refs/heads/*:refs/remotes/usb/*".to_string(),
+            "HEAD:refs/heads/main".to_string(),
+        ];
+        assert!(matches!(
+            fetch_bundle(&target, &incremental, "usb", &refspecs, |_| true),
+            Err(BundleError::MissingPrerequisites(_))
+        ));
+        let summary = fetch_bundle(&target, &full, "usb", &refspecs, |_| true).unwrap();
+        assert_eq!(summary.updated_refs[0].name, "refs/remotes/usb/first");
+        let summary = fetch_bundle(&target, &incremental, "usb", &refspecs, |_| true).unwrap();
+        assert_eq!(summary.updated_refs[0].name, "refs/heads/main");
+        assert_eq!(
+            target.refname_to_id("refs/heads/main").unwrap(),
+            source.head().unwrap().target().unwrap()
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
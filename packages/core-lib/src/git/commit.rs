@@ -0,0 +1,353 @@
+use git2::{Commit, Config, ErrorCode, ObjectType, Oid, Repository, Signature, Tree};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::signature::GpgPrograms;
+use super::signing::{self, SigningConfig, SigningError};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct CommitOptions {
+    pub allow_empty: bool,
+    /// Overrides `commit.gpgsign` (or `tag.gpgsign` for tags) when set.
+    pub sign: Option<bool>,
+    /// Skips the `pre-commit` and `commit-msg` hooks, like `git commit --no-verify`.
+    pub no_verify: bool,
+}
+
+/// Who to commit as instead of `user.name`, `user.email` and `user.signingkey` from the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct CommitIdentity {
+    pub name: String,
+    pub email: String,
+    /// Replaces `user.signingkey` when set.
+    pub signing_key: Option<String>,
+}
+
+impl CommitIdentity {
+    fn signature(&self) -> Result<Signature<'static>, git2::Error> {
+        Signature::now(&self.name, &self.email)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommitError {
+    #[error("Nothing to commit")]
+    NothingToCommit,
+    #[error("Signing failed: {0}")]
+    Signing(#[from] SigningError),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Commits the index on top of HEAD, signing the commit if configured, and advances the current branch.
+///
+/// The commit is authored as `identity` when given, otherwise as the configured user.
+pub fn create_commit(
+    repo: &Repository,
+    message: &str,
+    options: &CommitOptions,
+    identity: Option<&CommitIdentity>,
+    programs: &GpgPrograms,
+) -> Result<Oid, CommitError> {
+    let signature = committer(repo, identity)?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let parent = head_commit(repo)?;
+    if !options.allow_empty && parent.as_ref().is_some_and(|p| p.tree_id() == tree.id()) {
+        return Err(CommitError::NothingToCommit);
+    }
+
+    let parents: Vec<_> = parent.iter().collect();
+    let content = CommitContent {
+        author: &signature,
+        committer: &signature,
+        message,
+        tree: &tree,
+        parents: &parents,
+    };
+    let oid = write_commit(repo, &content, options.sign, identity, programs)?;
+
+    let summary = message.lines().next().unwrap_or_default();
+    let reflog = match parent {
+        Some(_) => format!("commit: {}", summary),
+        None => format!("commit (initial): {}", summary),
+    };
+    advance_head(repo, oid, parent.as_ref().map(Commit::id), &reflog)?;
+    Ok(oid)
+}
+
+/// The objects a commit is made of.
+pub(super) struct CommitContent<'a, 'r> {
+    pub author: &'a Signature<'a>,
+    pub committer: &'a Signature<'a>,
+    pub message: &'a str,
+    pub tree: &'a Tree<'r>,
+    pub parents: &'a [&'a Commit<'r>],
+}
+
+/// Writes a commit without updating any reference, signing it when `sign` (or `commit.gpgsign` when `None`) asks for
+/// it. The signing key of `identity` replaces `user.signingkey` when set.
+pub(super) fn write_commit(
+    repo: &Repository,
+    content: &CommitContent,
+    sign: Option<bool>,
+    identity: Option<&CommitIdentity>,
+    programs: &GpgPrograms,
+) -> Result<Oid, CommitError> {
+    let CommitContent {
+        author,
+        committer,
+        message,
+        tree,
+        parents,
+    } = content;
+    let config = repo.config()?;
+    let signing = signing_config(&config, identity)?;
+    if !sign.unwrap_or(signing.sign_commits) {
+        return Ok(repo.commit(None, author, committer, message, tree, parents)?);
+    }
+
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let gpg_signature = signing::sign(&signing, programs, &config, committer, &buffer)?;
+    let content = std::str::from_utf8(&buffer).map_err(|_| git2::Error::from_str("commit is not valid UTF-8"))?;
+    Ok(repo.commit_signed(content, &gpg_signature, None)?)
+}
+
+/// Who commits: `identity` when given, otherwise the configured user.
+pub(super) fn committer(
+    repo: &Repository,
+    identity: Option<&CommitIdentity>,
+) -> Result<Signature<'static>, git2::Error> {
+    match identity {
+        Some(identity) => identity.signature(),
+        None => repo.signature(),
+    }
+}
+
+/// Fails with [`CommitError::NothingToCommit`] when the index matches HEAD and `options` does not allow empty commits,
+/// so callers can refuse a commit before running any hook.
+pub fn check_changes(repo: &Repository, options: &CommitOptions) -> Result<(), CommitError> {
+    if options.allow_empty {
+        return Ok(());
+    }
+    let tree = repo.index()?.write_tree()?;
+    match head_commit(repo)? {
+        Some(parent) if parent.tree_id() == tree => Err(CommitError::NothingToCommit),
+        _ => Ok(()),
+    }
+}
+
+fn head_commit(repo: &Repository) -> Result<Option<Commit<'_>>, git2::Error> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?)),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a tag pointing at `target`: annotated (and signed if configured) when `message` is given,
+/// lightweight otherwise. Annotated tags are made by `identity` when given, otherwise by the configured user.
+#[allow(clippy::too_many_arguments)]
+pub fn create_tag(
+    repo: &Repository,
+    name: &str,
+    target: &str,
+    message: Option<&str>,
+    force: bool,
+    options: &CommitOptions,
+    identity: Option<&CommitIdentity>,
+    programs: &GpgPrograms,
+) -> Result<Oid, CommitError> {
+    let target = repo.revparse_single(target)?;
+    let Some(message) = message else {
+        return Ok(repo.tag_lightweight(name, &target, force)?);
+    };
+
+    let config = repo.config()?;
+    let signing = signing_config(&config, identity)?;
+    let tagger = committer(repo, identity)?;
+    if !options.sign.unwrap_or(signing.sign_tags) {
+        return Ok(repo.tag(name, &target, &tagger, message, force)?);
+    }
+
+    // libgit2 cannot sign tags, so the tag object is assembled and written by hand.
+    let mut content = format!(
+        "object {}\ntype {}\ntag {}\ntagger {}\n\n{}",
+        target.id(),
+        target.kind().map(|kind| kind.str()).unwrap_or("commit"),
+        name,
+        raw_signature(&tagger),
+        message
+    );
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    let gpg_signature = signing::sign(&signing, programs, &config, &tagger, content.as_bytes())?;
+    content.push_str(&gpg_signature);
+
+    let oid = repo.odb()?.write(ObjectType::Tag, content.as_bytes())?;
+    repo.reference(&format!("refs/tags/{}", name), oid, force, "tag: signed")?;
+    Ok(oid)
+}
+
+fn signing_config(config: &Config, identity: Option<&CommitIdentity>) -> Result<SigningConfig, SigningError> {
+    let mut signing = SigningConfig::from_config(config)?;
+    if let Some(key) = identity.and_then(|identity| identity.signing_key.clone()) {
+        signing.key = Some(key);
+    }
+    Ok(signing)
+}
+
+/// Points the current branch (or a detached HEAD) at `oid`.
+///
+/// Fails instead of overwriting it when the branch no longer points at `old`, `None` meaning it must not exist yet,
+/// e.g. because another program committed in the meantime.
+pub(super) fn advance_head(repo: &Repository, oid: Oid, old: Option<Oid>, reflog: &str) -> Result<(), git2::Error> {
+    let head = repo.find_reference("HEAD")?;
+    let name = head.symbolic_target().unwrap_or("HEAD");
+    match old {
+        Some(old) => repo.reference_matching(name, oid, true, old, reflog).map(|_| ()),
+        None => repo.reference(name, oid, false, reflog).map(|_| ()),
+    }
+}
+
+/// Formats a signature as in raw objects: `Name <email> 1700000000 +0100`.
+fn raw_signature(signature: &Signature) -> String {
+    let when = signature.when();
+    let offset = when.offset_minutes();
+    format!(
+        "{} {} {}{:02}{:02}",
+        signature,
+        when.seconds(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn init(name: &str) -> (std::path::PathBuf, Repository) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_commit_refuses_to_clobber_concurrent_updates() {
+        let (dir, repo) = init("gitultra_commit_test");
+        let options = CommitOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let programs = GpgPrograms::default();
+        let first = create_commit(&repo, "first", &options, None, &programs).unwrap();
+        let second = create_commit(&repo, "second", &options, None, &programs).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(second));
+        assert_eq!(repo.find_commit(second).unwrap().parent_id(0).unwrap(), first);
+        assert!(matches!(
+            check_changes(&repo, &CommitOptions::default()),
+            Err(CommitError::NothingToCommit)
+        ));
+        assert!(check_changes(&repo, &options).is_ok());
+
+        // Someone else moved the branch since `first` was read.
+        let stale = repo.find_commit(first).unwrap();
+        let tree = stale.tree().unwrap();
+        let signature = repo.signature().unwrap();
+        let orphan = repo.commit(None, &signature, &signature, "x", &tree, &[&stale]).unwrap();
+        assert!(advance_head(&repo, orphan, Some(first), "commit: x").is_err());
+        assert!(advance_head(&repo, orphan, None, "commit: x").is_err());
+        assert_eq!(repo.head().unwrap().target(), Some(second));
+
+        repo.set_head_detached(first).unwrap();
+        let detached = create_commit(&repo, "detached", &options, None, &programs).unwrap();
+        assert!(repo.head_detached().unwrap());
+        assert_eq!(repo.head().unwrap().target(), Some(detached));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_raw_signature() {
+        let signature = Signature::new("Jane Doe", "jane@example.com", &git2::Time::new(1700000000, -90)).unwrap();
+
+        assert_eq!(
+            raw_signature(&signature),
+            "Jane Doe <jane@example.com> 1700000000 -0130"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signed_tag() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, repo) = init("gitultra_signed_tag_test");
+        let options = CommitOptions {
+            allow_empty: true,
+            sign: Some(true),
+            ..Default::default()
+        };
+        // Stands in for gpg: reports success and prints a fixed signature.
+        let program = dir.join("fake-gpg");
+        fs::write(
+            &program,
+            "#!/bin/sh\ncat > /dev/null\necho '[GNUPG:] SIG_CREATED D' >&2\n\
+             printf -- '-----BEGIN PGP SIGNATURE-----\\nsig\\n-----END PGP SIGNATURE-----\\n'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        let programs = GpgPrograms {
+            openpgp: Some(program.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let identity = CommitIdentity {
+            name: "Tagger".to_string(),
+            email: "tagger@example.com".to_string(),
+            signing_key: Some("ABCDEF".to_string()),
+        };
+        let commit = create_commit(&repo, "release", &options, None, &programs).unwrap();
+        assert!(repo.extract_signature(&commit, None).is_ok());
+
+        let oid = create_tag(
+            &repo,
+            "v1.0",
+            "HEAD",
+            Some("Release 1.0"),
+            false,
+            &options,
+            Some(&identity),
+            &programs,
+        )
+        .unwrap();
+
+        let tag = repo.find_tag(oid).unwrap();
+        assert_eq!(tag.name(), Some("v1.0"));
+        assert_eq!(tag.target_id(), commit);
+        assert_eq!(tag.tagger().unwrap().name(), Some("Tagger"));
+        assert_eq!(
+            tag.message(),
+            Some("Release 1.0\n-----BEGIN PGP SIGNATURE-----\nsig\n-----END PGP SIGNATURE-----\n")
+        );
+        assert_eq!(repo.find_reference("refs/tags/v1.0").unwrap().target(), Some(oid));
+        let odb = repo.odb().unwrap();
+        let object = odb.read(oid).unwrap();
+        let content = String::from_utf8_lossy(object.data()).into_owned();
+        assert!(content.starts_with(&format!(
+            "object {}\ntype commit\ntag v1.0\ntagger Tagger <tagger@example.com> ",
+            commit
+        )));
+        // The same tag again is refused without `force`.
+        assert!(create_tag(&repo, "v1.0", "HEAD", Some("Again"), false, &options, None, &programs).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
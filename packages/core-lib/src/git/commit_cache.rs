@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use git2::{Commit, Oid, Repository, Sort};
+use log::warn;
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+
+use super::index_cache::{CacheError, GitIndexCache};
+use super::message_index::{self, MESSAGE_INDEX_TABLE};
+
+/// Commits cached per batch by [`cache_all_commits`].
+const BATCH_SIZE: usize = 1000;
+
+/// Commit metadata keyed by raw OID bytes. Commits never change, so entries are never invalidated.
+const COMMIT_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("commits");
+
+/// The parts of a commit needed to filter history without touching the object database.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CachedCommit {
+    pub oid: String,
+    pub parents: Vec<String>,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_time: i64,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_time: i64,
+    pub message: String,
+}
+
+impl CachedCommit {
+    pub fn from_commit(commit: &Commit) -> Self {
+        let author = commit.author();
+        let committer = commit.committer();
+        Self {
+            oid: commit.id().to_string(),
+            parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+            author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            author_time: author.when().seconds(),
+            committer_name: String::from_utf8_lossy(committer.name_bytes()).into_owned(),
+            committer_email: String::from_utf8_lossy(committer.email_bytes()).into_owned(),
+            committer_time: committer.when().seconds(),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+        }
+    }
+
+    /// The author as `Name <email>`, the form `git log --author` matches against.
+    pub fn author(&self) -> String {
+        format!("{} <{}>", self.author_name, self.author_email)
+    }
+
+    pub fn committer(&self) -> String {
+        format!("{} <{}>", self.committer_name, self.committer_email)
+    }
+
+    pub fn is_merge(&self) -> bool {
+        self.parents.len() > 1
+    }
+}
+
+impl GitIndexCache {
+    /// Returns the cached commits among `oids`; misses are simply absent from the map.
+    pub fn get_commits(&self, oids: &[Oid]) -> Result<HashMap<Oid, CachedCommit>, CacheError> {
+        let read_txn = self.database().begin_read()?;
+        let table = match read_txn.open_table(COMMIT_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut commits = HashMap::new();
+        for oid in oids {
+            if let Some(value) = table.get(oid.as_bytes())? {
+                commits.insert(*oid, serde_json::from_slice(value.value())?);
+            }
+        }
+        Ok(commits)
+    }
+
+    /// Stores `commits` and adds their messages to the full-text index in the same transaction.
+    pub fn put_commits(&self, commits: &[CachedCommit]) -> Result<(), CacheError> {
+        let write_txn = self.database().begin_write()?;
+        {
+            let mut table = write_txn.open_table(COMMIT_TABLE)?;
+            let mut index = write_txn.open_multimap_table(MESSAGE_INDEX_TABLE)?;
+            for commit in commits {
+                let oid = Oid::from_str(&commit.oid)?;
+                let previous = table.insert(oid.as_bytes(), serde_json::to_vec(commit)?.as_slice())?;
+                if previous.is_none() {
+                    message_index::index_message(&mut index, oid, &commit.message)?;
+                }
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Loads the metadata of `oids` in order, from `cache` when possible.
+///
+/// Misses are read from `repo` and written back. Cache failures are logged and otherwise ignored.
+pub fn load_commits(
+    repo: &Repository,
+    cache: Option<&GitIndexCache>,
+    oids: &[Oid],
+) -> Result<Vec<CachedCommit>, git2::Error> {
+    let mut cached = match cache.map(|cache| cache.get_commits(oids)) {
+        Some(Ok(cached)) => cached,
+        Some(Err(e)) => {
+            warn!("Failed to read commit cache: {}", e);
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    };
+
+    let mut misses = Vec::new();
+    let mut commits = Vec::with_capacity(oids.len());
+    for oid in oids {
+        let commit = match cached.remove(oid) {
+            Some(commit) => commit,
+            None => {
+                let commit = CachedCommit::from_commit(&repo.find_commit(*oid)?);
+                misses.push(commit.clone());
+                commit
+            }
+        };
+        commits.push(commit);
+    }
+
+    if let (Some(cache), false) = (cache, misses.is_empty()) {
+        if let Err(e) = cache.put_commits(&misses) {
+            warn!("Failed to update commit cache: {}", e);
+        }
+    }
+    Ok(commits)
+}
+
+/// Caches every commit reachable from HEAD and the refs, so the message index covers the whole history.
+///
+/// Already cached commits are skipped cheaply. `on_progress` gets the number of commits processed and the total;
+/// returning `false` stops early. Returns the number of commits walked.
+pub fn cache_all_commits(
+    repo: &Repository,
+    cache: &GitIndexCache,
+    mut on_progress: impl FnMut(usize, usize) -> bool,
+) -> Result<usize, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME)?;
+    if let Err(e) = walk.push_head() {
+        // Unborn HEAD, the refs may still have history.
+        warn!("Not walking HEAD: {}", e);
+    }
+    walk.push_glob("*")?;
+    let oids = walk.collect::<Result<Vec<Oid>, _>>()?;
+
+    for (i, batch) in oids.chunks(BATCH_SIZE).enumerate() {
+        if !on_progress(i * BATCH_SIZE, oids.len()) {
+            return Ok(i * BATCH_SIZE);
+        }
+        load_commits(repo, Some(cache), batch)?;
+    }
+    on_progress(oids.len(), oids.len());
+    Ok(oids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+
+    #[test]
+    fn test_caches_commits_of_every_ref() {
+        let repo = init_repo("commit_cache");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let root = repo.find_commit(commit_files(&repo, &[], "root")).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = root.tree().unwrap();
+        // Only reachable from a tag, not from HEAD or a branch.
+        let tagged = repo.commit(None, &signature, &signature, "tagged", &tree, &[&root]).unwrap();
+        repo.reference("refs/tags/v1", tagged, false, "test").unwrap();
+
+        let cache = GitIndexCache::open(&dir).unwrap();
+        assert!(cache.get_commits(&[tagged]).unwrap().is_empty());
+
+        assert_eq!(cache_all_commits(&repo, &cache, |_, _| true).unwrap(), 2);
+
+        let cached = cache.get_commits(&[root.id(), tagged]).unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[&tagged].message, "tagged");
+        assert_eq!(cached[&tagged].parents, vec![root.id().to_string()]);
+        assert!(!cached[&tagged].is_merge());
+        assert_eq!(cached[&root.id()].author(), "Test <test@example.com>");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,254 @@
+use git2::{Commit, ErrorCode, Oid, Repository, Signature};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::signature::Verification;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the epoch and the UTC offset in minutes the time was recorded with.
+    pub time: i64,
+    pub offset_minutes: i32,
+}
+
+impl From<&Signature<'_>> for Person {
+    fn from(signature: &Signature) -> Self {
+        Self {
+            name: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+            time: signature.when().seconds(),
+            offset_minutes: signature.when().offset_minutes(),
+        }
+    }
+}
+
+/// A `Key: value` trailer from the end of the message, e.g. `Signed-off-by`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum SignatureFormat {
+    OpenPgp,
+    Ssh,
+    X509,
+    Unknown,
+}
+
+impl SignatureFormat {
+    pub fn detect(signature: &[u8]) -> Self {
+        if signature.starts_with(b"-----BEGIN PGP SIGNATURE-----") {
+            SignatureFormat::OpenPgp
+        } else if signature.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+            SignatureFormat::Ssh
+        } else if signature.starts_with(b"-----BEGIN SIGNED MESSAGE-----") {
+            SignatureFormat::X509
+        } else {
+            SignatureFormat::Unknown
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct CommitSignature {
+    pub format: SignatureFormat,
+    /// Filled in by [`super::signature::verify_commit`] when the caller asks for verification.
+    #[serde(default)]
+    pub verification: Option<Verification>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct DiffStats {
+    pub files_changed: u32,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct CommitDetails {
+    pub oid: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Person,
+    pub committer: Person,
+    /// First paragraph of the message.
+    pub summary: String,
+    /// Rest of the message, trailers included.
+    pub body: Option<String>,
+    pub trailers: Vec<Trailer>,
+    /// `None` for unsigned commits.
+    pub signature: Option<CommitSignature>,
+    /// Changes against the first parent, or against the empty tree for root commits.
+    pub stats: DiffStats,
+}
+
+impl CommitDetails {
+    /// Values of the trailers with the given key, compared case-insensitively like git does.
+    pub fn trailer_values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.trailers
+            .iter()
+            .filter(move |t| t.key.eq_ignore_ascii_case(key))
+            .map(|t| t.value.as_str())
+    }
+}
+
+pub fn commit_details(repo: &Repository, rev: &str) -> Result<CommitDetails, git2::Error> {
+    let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+    let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+    let trailers = match git2::message_trailers_strs(&message) {
+        Ok(trailers) => trailers
+            .iter()
+            .map(|(key, value)| Trailer {
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let author = Person::from(&commit.author());
+    let committer = Person::from(&commit.committer());
+
+    Ok(CommitDetails {
+        oid: commit.id().to_string(),
+        tree: commit.tree_id().to_string(),
+        parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+        author,
+        committer,
+        summary: commit.summary().unwrap_or("").to_string(),
+        body: commit.body().map(str::to_string),
+        trailers,
+        signature: signature(repo, commit.id())?,
+        stats: diff_stats(repo, &commit)?,
+    })
+}
+
+fn signature(repo: &Repository, oid: Oid) -> Result<Option<CommitSignature>, git2::Error> {
+    match repo.extract_signature(&oid, None) {
+        Ok((signature, _)) => Ok(Some(CommitSignature {
+            format: SignatureFormat::detect(&signature),
+            verification: None,
+        })),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn diff_stats(repo: &Repository, commit: &Commit) -> Result<DiffStats, git2::Error> {
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    let stats = diff.stats()?;
+    Ok(DiffStats {
+        files_changed: stats.files_changed() as u32,
+        insertions: stats.insertions() as u32,
+        deletions: stats.deletions() as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files_as, init_repo};
+    use std::fs;
+
+    fn commit(repo: &Repository, files: &[(&str, &str)], message: &str) -> Oid {
+        let author = Signature::new("Alice", "alice@example.com", &git2::Time::new(1000, 120)).unwrap();
+        let committer = Signature::new("Bob", "bob@example.com", &git2::Time::new(2000, -60)).unwrap();
+        commit_files_as(repo, &author, &committer, files, message)
+    }
+
+    #[test]
+    fn test_commit_details() {
+        let repo = init_repo("commit_details");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let root = commit(&repo, &[("a.txt", "1\n2\n3\n"), ("b.txt", "b\n")], "Initial commit");
+        let oid = commit(
+            &repo,
+            &[("a.txt", "1\nTWO\n3\n4\n")],
+            "Rework the parser\nacross two lines\n\nLonger explanation.\n\n\
+             Co-authored-by: Carol <carol@example.com>\n\
+             Signed-off-by: Alice <alice@example.com>\n\
+             signed-off-by: Bob <bob@example.com>\n",
+        );
+
+        let details = commit_details(&repo, "HEAD").unwrap();
+
+        assert_eq!(details.oid, oid.to_string());
+        assert_eq!(details.parents, vec![root.to_string()]);
+        assert_eq!(
+            details.author,
+            Person {
+                name: "Alice".to_string(),
+                email: "alice@example.com".to_string(),
+                time: 1000,
+                offset_minutes: 120,
+            }
+        );
+        assert_eq!(
+            (details.committer.name.as_str(), details.committer.offset_minutes),
+            ("Bob", -60)
+        );
+        assert_eq!(details.summary, "Rework the parser across two lines");
+        let body = details.body.as_deref().unwrap();
+        assert!(body.starts_with("Longer explanation.\n\nCo-authored-by: Carol"));
+        assert_eq!(details.trailers.len(), 3);
+        assert_eq!(
+            details.trailers[0],
+            Trailer {
+                key: "Co-authored-by".to_string(),
+                value: "Carol <carol@example.com>".to_string(),
+            }
+        );
+        assert_eq!(
+            details.trailer_values("Signed-off-by").collect::<Vec<_>>(),
+            vec!["Alice <alice@example.com>", "Bob <bob@example.com>"]
+        );
+        assert!(details.signature.is_none());
+        assert_eq!(
+            (
+                details.stats.files_changed,
+                details.stats.insertions,
+                details.stats.deletions
+            ),
+            (1, 2, 1)
+        );
+
+        let details = commit_details(&repo, &root.to_string()).unwrap();
+        assert_eq!(details.summary, "Initial commit");
+        assert_eq!(details.body, None);
+        assert!(details.trailers.is_empty());
+        assert_eq!(
+            (
+                details.stats.files_changed,
+                details.stats.insertions,
+                details.stats.deletions
+            ),
+            (2, 4, 0)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detect_signature_format() {
+        assert_eq!(
+            SignatureFormat::detect(b"-----BEGIN PGP SIGNATURE-----\n"),
+            SignatureFormat::OpenPgp
+        );
+        assert_eq!(
+            SignatureFormat::detect(b"-----BEGIN SSH SIGNATURE-----\n"),
+            SignatureFormat::Ssh
+        );
+        assert_eq!(
+            SignatureFormat::detect(b"-----BEGIN SIGNED MESSAGE-----\n"),
+            SignatureFormat::X509
+        );
+        assert_eq!(SignatureFormat::detect(b"garbage"), SignatureFormat::Unknown);
+    }
+}
@@ -0,0 +1,364 @@
+use std::fs;
+use std::path::PathBuf;
+
+use git2::{Branch, Config, ConfigLevel, Repository};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// A git configuration file, ordered from least to most specific like git's precedence.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Type)]
+pub enum ConfigScope {
+    /// `/etc/gitconfig`, or the ProgramData file on Windows.
+    System,
+    /// `$XDG_CONFIG_HOME/git/config`.
+    Xdg,
+    /// `~/.gitconfig`.
+    Global,
+    /// `.git/config`.
+    Local,
+    /// `.git/config.worktree`, only read when `extensions.worktreeConfig` is enabled.
+    Worktree,
+}
+
+impl ConfigScope {
+    fn from_level(level: ConfigLevel) -> Option<Self> {
+        match level {
+            ConfigLevel::ProgramData | ConfigLevel::System => Some(ConfigScope::System),
+            ConfigLevel::XDG => Some(ConfigScope::Xdg),
+            ConfigLevel::Global => Some(ConfigScope::Global),
+            ConfigLevel::Local => Some(ConfigScope::Local),
+            ConfigLevel::Worktree => Some(ConfigScope::Worktree),
+            ConfigLevel::App | ConfigLevel::Highest => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct ScopedValue {
+    pub scope: ConfigScope,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct ConfigValue {
+    pub name: String,
+    /// The value git uses: the last one set in the most specific scope.
+    pub value: String,
+    pub scope: ConfigScope,
+    /// Every value of the key from least to most specific scope, the effective one last. Multi-valued keys such as
+    /// `remote.origin.fetch` list all of theirs.
+    pub values: Vec<ScopedValue>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum PullRebase {
+    False,
+    True,
+    Merges,
+    Interactive,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum AutoCrlf {
+    False,
+    True,
+    Input,
+}
+
+/// Keys the settings screen edits, with typed values.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+#[serde(tag = "key", content = "value")]
+pub enum CommonSetting {
+    UserName(String),
+    UserEmail(String),
+    PullRebase(PullRebase),
+    CoreAutocrlf(AutoCrlf),
+    InitDefaultBranch(String),
+}
+
+impl CommonSetting {
+    pub fn key(&self) -> &'static str {
+        match self {
+            CommonSetting::UserName(_) => "user.name",
+            CommonSetting::UserEmail(_) => "user.email",
+            CommonSetting::PullRebase(_) => "pull.rebase",
+            CommonSetting::CoreAutocrlf(_) => "core.autocrlf",
+            CommonSetting::InitDefaultBranch(_) => "init.defaultBranch",
+        }
+    }
+
+    /// The value as written to the config file, or why it is rejected.
+    pub fn validated_value(&self) -> Result<String, ConfigError> {
+        let invalid = |reason: &str| ConfigError::Invalid {
+            key: self.key().to_string(),
+            reason: reason.to_string(),
+        };
+        match self {
+            CommonSetting::UserName(name) => {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(invalid("the name cannot be empty"));
+                }
+                if name.contains(['<', '>', '\n']) {
+                    return Err(invalid("the name cannot contain '<', '>' or line breaks"));
+                }
+                Ok(name.to_string())
+            }
+            CommonSetting::UserEmail(email) => {
+                let email = email.trim();
+                let valid = email
+                    .split_once('@')
+                    .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty());
+                if !valid || email.contains(|c: char| c.is_whitespace() || c == '<' || c == '>') {
+                    return Err(invalid("not an email address"));
+                }
+                Ok(email.to_string())
+            }
+            CommonSetting::PullRebase(rebase) => Ok(match rebase {
+                PullRebase::False => "false",
+                PullRebase::True => "true",
+                PullRebase::Merges => "merges",
+                PullRebase::Interactive => "interactive",
+            }
+            .to_string()),
+            CommonSetting::CoreAutocrlf(autocrlf) => Ok(match autocrlf {
+                AutoCrlf::False => "false",
+                AutoCrlf::True => "true",
+                AutoCrlf::Input => "input",
+            }
+            .to_string()),
+            CommonSetting::InitDefaultBranch(branch) => {
+                let branch = branch.trim();
+                if !Branch::name_is_valid(branch)? {
+                    return Err(invalid("not a valid branch name"));
+                }
+                Ok(branch.to_string())
+            }
+        }
+    }
+}
+
+/// The common settings as currently in effect, `None` when unset in every scope.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Type)]
+pub struct CommonSettings {
+    pub user_name: Option<ConfigValue>,
+    pub user_email: Option<ConfigValue>,
+    pub pull_rebase: Option<ConfigValue>,
+    pub core_autocrlf: Option<ConfigValue>,
+    pub init_default_branch: Option<ConfigValue>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Invalid value for {key}: {reason}")]
+    Invalid { key: String, reason: String },
+    #[error("The {0:?} scope needs a repository")]
+    NoRepository(ConfigScope),
+    #[error("Worktree config is disabled, set extensions.worktreeConfig first")]
+    WorktreeConfigDisabled,
+    #[error("Cannot locate the {0:?} config file")]
+    NoFile(ConfigScope),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Every key set in any scope with its effective value. Without a repository only the user and system files are read.
+pub fn list_config(repo: Option<&Repository>) -> Result<Vec<ConfigValue>, ConfigError> {
+    let config = effective_config(repo)?;
+    let mut values: Vec<ConfigValue> = Vec::new();
+    let mut entries = config.entries(None)?;
+    while let Some(entry) = entries.next() {
+        let entry = entry?;
+        let (Some(name), Some(scope)) = (entry.name(), ConfigScope::from_level(entry.level())) else {
+            continue;
+        };
+        let value = ScopedValue {
+            scope,
+            value: entry.value().unwrap_or_default().to_string(),
+        };
+        match values.iter_mut().find(|v| v.name == name) {
+            Some(existing) => existing.values.push(value),
+            None => values.push(ConfigValue {
+                name: name.to_string(),
+                value: String::new(),
+                scope,
+                values: vec![value],
+            }),
+        }
+    }
+    for value in &mut values {
+        // Stable, so values within one scope keep their file order.
+        value.values.sort_by_key(|v| v.scope);
+        let effective = value.values.last().cloned().expect("every key has a value");
+        value.value = effective.value;
+        value.scope = effective.scope;
+    }
+    values.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(values)
+}
+
+pub fn get_value(repo: Option<&Repository>, name: &str) -> Result<Option<ConfigValue>, ConfigError> {
+    // Section and key names are case-insensitive, libgit2 lists them lowercased.
+    let normalized = normalize_name(name);
+    Ok(list_config(repo)?.into_iter().find(|value| value.name == normalized))
+}
+
+pub fn common_settings(repo: Option<&Repository>) -> Result<CommonSettings, ConfigError> {
+    let values = list_config(repo)?;
+    let find = |name: &str| values.iter().find(|value| value.name == normalize_name(name)).cloned();
+    Ok(CommonSettings {
+        user_name: find("user.name"),
+        user_email: find("user.email"),
+        pull_rebase: find("pull.rebase"),
+        core_autocrlf: find("core.autocrlf"),
+        init_default_branch: find("init.defaultBranch"),
+    })
+}
+
+/// Writes a validated common setting to `scope`.
+pub fn set_common(repo: Option<&Repository>, scope: ConfigScope, setting: &CommonSetting) -> Result<(), ConfigError> {
+    let value = setting.validated_value()?;
+    set_value(repo, scope, setting.key(), &value)
+}
+
+/// Writes a raw value to `scope`, replacing a single existing value.
+pub fn set_value(repo: Option<&Repository>, scope: ConfigScope, name: &str, value: &str) -> Result<(), ConfigError> {
+    let mut config = open_scope(repo, scope)?;
+    config.set_str(name, value)?;
+    Ok(())
+}
+
+/// Removes `name` from `scope`, all of its values for multi-valued keys.
+pub fn unset_value(repo: Option<&Repository>, scope: ConfigScope, name: &str) -> Result<(), ConfigError> {
+    let mut config = open_scope(repo, scope)?;
+    match config.remove_multivar(name, ".*") {
+        Err(e) if e.code() != git2::ErrorCode::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn effective_config(repo: Option<&Repository>) -> Result<Config, git2::Error> {
+    match repo {
+        Some(repo) => repo.config()?.snapshot(),
+        None => Config::open_default()?.snapshot(),
+    }
+}
+
+/// Opens the file of `scope` for writing, creating it (and its directory) on first write.
+fn open_scope(repo: Option<&Repository>, scope: ConfigScope) -> Result<Config, ConfigError> {
+    let path = match scope {
+        ConfigScope::System => Config::find_system().map_err(|_| ConfigError::NoFile(scope))?,
+        ConfigScope::Xdg => match Config::find_xdg() {
+            Ok(path) => path,
+            Err(_) => xdg_config_home().ok_or(ConfigError::NoFile(scope))?.join("git/config"),
+        },
+        ConfigScope::Global => match Config::find_global() {
+            Ok(path) => path,
+            Err(_) => home_dir().ok_or(ConfigError::NoFile(scope))?.join(".gitconfig"),
+        },
+        // Linked worktrees share the config of the main repository.
+        ConfigScope::Local => repo.ok_or(ConfigError::NoRepository(scope))?.commondir().join("config"),
+        ConfigScope::Worktree => {
+            let repo = repo.ok_or(ConfigError::NoRepository(scope))?;
+            if !repo.config()?.get_bool("extensions.worktreeConfig").unwrap_or(false) {
+                return Err(ConfigError::WorktreeConfigDisabled);
+            }
+            repo.path().join("config.worktree")
+        }
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(Config::open(&path)?)
+}
+
+/// Lowercases the section and key of `section.subsection.key`, keeping the case-sensitive subsection.
+fn normalize_name(name: &str) -> String {
+    match (name.find('.'), name.rfind('.')) {
+        (Some(first), Some(last)) => format!(
+            "{}{}{}",
+            name[..first].to_lowercase(),
+            &name[first..last],
+            name[last..].to_lowercase()
+        ),
+        _ => name.to_lowercase(),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn xdg_config_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home_dir().map(|home| home.join(".config")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+
+    #[test]
+    fn test_common_settings_are_validated() {
+        assert_eq!(
+            CommonSetting::UserEmail(" jane@example.com ".to_string())
+                .validated_value()
+                .unwrap(),
+            "jane@example.com"
+        );
+        assert!(CommonSetting::UserEmail("jane".to_string()).validated_value().is_err());
+        assert!(CommonSetting::UserName("Jane <jane>".to_string()).validated_value().is_err());
+        assert!(CommonSetting::InitDefaultBranch("main branch".to_string())
+            .validated_value()
+            .is_err());
+        assert_eq!(
+            CommonSetting::PullRebase(PullRebase::Merges).validated_value().unwrap(),
+            "merges"
+        );
+    }
+
+    #[test]
+    fn test_local_value_overrides_and_unsets() {
+        let repo = init_repo("config");
+
+        set_value(Some(&repo), ConfigScope::Local, "gitultra.Test-Key", "local").unwrap();
+        let value = get_value(Some(&repo), "GitUltra.test-key").unwrap().unwrap();
+        assert_eq!(value.value, "local");
+        assert_eq!(value.scope, ConfigScope::Local);
+
+        assert!(matches!(
+            set_value(Some(&repo), ConfigScope::Worktree, "gitultra.test-key", "worktree"),
+            Err(ConfigError::WorktreeConfigDisabled)
+        ));
+
+        unset_value(Some(&repo), ConfigScope::Local, "gitultra.test-key").unwrap();
+        assert!(get_value(Some(&repo), "gitultra.test-key").unwrap().is_none());
+
+        fs::remove_dir_all(repo.workdir().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_local_value_from_linked_worktree() {
+        let temp_dir = std::env::temp_dir().join("gitultra_config_worktree_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let repo = Repository::init(temp_dir.join("main")).unwrap();
+        commit_files(&repo, &[], "init");
+        repo.worktree("linked", &temp_dir.join("linked"), None).unwrap();
+        let linked = Repository::open(temp_dir.join("linked")).unwrap();
+
+        set_value(Some(&linked), ConfigScope::Local, "gitultra.test-key", "shared").unwrap();
+        assert!(!linked.path().join("config").exists());
+        let value = get_value(Some(&repo), "gitultra.test-key").unwrap().unwrap();
+        assert_eq!(value.value, "shared");
+        assert_eq!(value.scope, ConfigScope::Local);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
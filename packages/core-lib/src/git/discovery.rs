@@ -0,0 +1,206 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Repository, RepositoryOpenFlags};
+use glob::Pattern;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Directory names skipped by default while scanning.
+pub const DEFAULT_IGNORES: &[&str] = &["node_modules", "target", ".cache", "vendor", "dist", "build"];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct DiscoveryOptions {
+    pub root: PathBuf,
+    /// Maximum number of directory levels below `root` to visit.
+    pub max_depth: u32,
+    /// Glob patterns matched against directory names and paths relative to `root`.
+    pub ignore: Vec<String>,
+    /// Keep scanning inside repositories that were already found (e.g. for nested repos).
+    #[serde(default)]
+    pub descend_into_repos: bool,
+}
+
+impl DiscoveryOptions {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            max_depth: 4,
+            ignore: DEFAULT_IGNORES.iter().map(|s| s.to_string()).collect(),
+            descend_into_repos: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum DiscoveredKind {
+    Repository,
+    Worktree,
+    Bare,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct DiscoveredRepo {
+    /// Working directory, or the git directory for bare repositories.
+    pub path: PathBuf,
+    pub git_dir: PathBuf,
+    pub kind: DiscoveredKind,
+    /// Short name of the checked out branch, if HEAD points to one.
+    pub head: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiscoveryError {
+    #[error("Invalid ignore pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Walks `options.root` and reports every repository found to `on_found`.
+///
+/// The walk stops early when `on_found` returns `false`. Returns the number of repositories found.
+pub fn scan(
+    options: &DiscoveryOptions,
+    mut on_found: impl FnMut(DiscoveredRepo) -> bool,
+) -> Result<usize, DiscoveryError> {
+    let ignore = options.ignore.iter().map(|p| Pattern::new(p)).collect::<Result<Vec<_>, _>>()?;
+
+    let root = fs::canonicalize(&options.root)?;
+    let mut found = 0;
+
+    // The chosen folder may itself live inside a repository.
+    if open_exact(&root).is_none() {
+        if let Ok(repo) = Repository::discover(&root) {
+            found += 1;
+            if !on_found(describe(&repo)) {
+                return Ok(found);
+            }
+        }
+    }
+
+    let mut stack = vec![(root.clone(), 0)];
+    while let Some((dir, depth)) = stack.pop() {
+        if let Some(repo) = open_exact(&dir) {
+            found += 1;
+            if !on_found(describe(&repo)) {
+                return Ok(found);
+            }
+            if !options.descend_into_repos {
+                continue;
+            }
+        }
+
+        if depth >= options.max_depth {
+            continue;
+        }
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Skipping unreadable directory {:?}: {}", dir, e);
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            // Symlinks are skipped to avoid cycles.
+            if !file_type.is_dir() {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_name() == OsStr::new(".git") || is_ignored(&ignore, &root, &path) {
+                debug!("Ignoring {:?}", path);
+                continue;
+            }
+            stack.push((path, depth + 1));
+        }
+    }
+
+    Ok(found)
+}
+
+fn is_ignored(ignore: &[Pattern], root: &Path, path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    ignore.iter().any(|p| p.matches(&name) || p.matches_path(relative))
+}
+
+fn open_exact(dir: &Path) -> Option<Repository> {
+    Repository::open_ext(dir, RepositoryOpenFlags::NO_SEARCH, &[] as &[&OsStr]).ok()
+}
+
+fn describe(repo: &Repository) -> DiscoveredRepo {
+    let kind = if repo.is_bare() {
+        DiscoveredKind::Bare
+    } else if repo.is_worktree() {
+        DiscoveredKind::Worktree
+    } else {
+        DiscoveredKind::Repository
+    };
+
+    let git_dir = repo.path().to_path_buf();
+    let path = match repo.workdir() {
+        Some(workdir) => workdir.to_path_buf(),
+        None => git_dir.clone(),
+    };
+    let head = repo
+        .head()
+        .ok()
+        .filter(|h| h.is_branch())
+        .and_then(|h| h.shorthand().map(str::to_string));
+
+    DiscoveredRepo {
+        path: fs::canonicalize(&path).unwrap_or(path),
+        git_dir,
+        kind,
+        head,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_repos_and_skips_ignored() {
+        let temp_dir = std::env::temp_dir().join("gitultra_discovery_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("services/nested")).unwrap();
+
+        Repository::init(temp_dir.join("services/api")).unwrap();
+        Repository::init(temp_dir.join("services/nested/worker")).unwrap();
+        Repository::init_bare(temp_dir.join("mirror.git")).unwrap();
+        Repository::init(temp_dir.join("node_modules/dep")).unwrap();
+        Repository::init(temp_dir.join("a/b/c/d/e/too-deep")).unwrap();
+
+        let mut found = Vec::new();
+        let count = scan(&DiscoveryOptions::new(temp_dir.clone()), |repo| {
+            found.push(repo);
+            true
+        })
+        .unwrap();
+
+        let mut names: Vec<_> = found
+            .iter()
+            .map(|r| (r.path.file_name().unwrap().to_string_lossy().into_owned(), r.kind))
+            .collect();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(count, 3);
+        assert_eq!(
+            names,
+            vec![
+                ("api".to_string(), DiscoveredKind::Repository),
+                ("mirror.git".to_string(), DiscoveredKind::Bare),
+                ("worker".to_string(), DiscoveredKind::Repository),
+            ]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Delta, Diff, DiffFindOptions, DiffOptions, ObjectType, Patch, Repository, Sort, Tree};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+#[serde(default)]
+pub struct FileHistoryOptions {
+    /// Revision to start from, `HEAD` when `None`.
+    pub rev: Option<String>,
+    /// Follow a single file across renames, like `git log --follow`. Ignored for directories and globs.
+    pub follow_renames: bool,
+    pub skip: u32,
+    pub limit: Option<u32>,
+}
+
+impl Default for FileHistoryOptions {
+    fn default() -> Self {
+        Self {
+            rev: None,
+            follow_renames: true,
+            skip: 0,
+            limit: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+}
+
+impl ChangeStatus {
+    pub(crate) fn from_delta(delta: Delta) -> Self {
+        match delta {
+            Delta::Added | Delta::Untracked => ChangeStatus::Added,
+            Delta::Deleted => ChangeStatus::Deleted,
+            Delta::Renamed => ChangeStatus::Renamed,
+            Delta::Copied => ChangeStatus::Copied,
+            Delta::Typechange => ChangeStatus::TypeChanged,
+            _ => ChangeStatus::Modified,
+        }
+    }
+}
+
+/// How a single path changed in a commit.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct PathChange {
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub status: ChangeStatus,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+/// A commit that touched the requested path, with the changes limited to that path.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct FileRevision {
+    pub oid: String,
+    pub summary: String,
+    pub author: String,
+    pub timestamp: i64,
+    pub parents: Vec<String>,
+    pub changes: Vec<PathChange>,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+/// Lists the commits touching `pathspec` (a file, a directory or a glob), newest first.
+///
+/// When following renames the tracked path switches to the old name at the commit that renamed the file, so
+/// later (older) revisions report the name the file had back then.
+pub fn file_history(
+    repo: &Repository,
+    pathspec: &Path,
+    options: &FileHistoryOptions,
+) -> Result<Vec<FileRevision>, git2::Error> {
+    let start = match &options.rev {
+        Some(rev) => repo.revparse_single(rev)?.peel_to_commit()?,
+        None => repo.head()?.peel_to_commit()?,
+    };
+    let follow = options.follow_renames && is_single_file(&start, pathspec);
+
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME | Sort::TOPOLOGICAL)?;
+    walk.push(start.id())?;
+
+    let mut current = pathspec.to_path_buf();
+    let mut skipped = 0;
+    let mut revisions = Vec::new();
+    for oid in walk {
+        if options.limit.is_some_and(|limit| revisions.len() as u32 >= limit) {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+
+        // Like git's history simplification, skip merges that kept the path of one of their parents.
+        if commit.parent_count() > 1 && treesame_to_any_parent(repo, &commit, &tree, &current)? {
+            continue;
+        }
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+
+        let mut diff = diff_path(repo, parent_tree.as_ref(), &tree, &current)?;
+        if diff.deltas().len() == 0 {
+            continue;
+        }
+
+        let mut renamed_from = None;
+        if follow && diff.deltas().all(|d| d.status() == Delta::Added) && parent_tree.is_some() {
+            if let Some((rename_diff, old_path)) = find_rename(repo, parent_tree.as_ref(), &tree, &current)? {
+                diff = rename_diff;
+                renamed_from = Some(old_path);
+            }
+        }
+
+        if skipped < options.skip {
+            skipped += 1;
+        } else {
+            revisions.push(revision(&commit, &diff, follow.then_some(current.as_path()))?);
+        }
+        if let Some(old_path) = renamed_from {
+            current = old_path;
+        }
+    }
+
+    Ok(revisions)
+}
+
+fn is_single_file(commit: &Commit, path: &Path) -> bool {
+    let has_glob = path.to_string_lossy().contains(['*', '?', '[']);
+    if has_glob {
+        return false;
+    }
+    match commit.tree().and_then(|t| t.get_path(path)) {
+        Ok(entry) => entry.kind() == Some(ObjectType::Blob),
+        // The file may have been deleted; treat it as a file.
+        Err(_) => true,
+    }
+}
+
+fn diff_path<'a>(
+    repo: &'a Repository,
+    old: Option<&Tree>,
+    new: &Tree,
+    pathspec: &Path,
+) -> Result<Diff<'a>, git2::Error> {
+    let mut opts = DiffOptions::new();
+    opts.pathspec(pathspec);
+    repo.diff_tree_to_tree(old, Some(new), Some(&mut opts))
+}
+
+fn treesame_to_any_parent(repo: &Repository, commit: &Commit, tree: &Tree, path: &Path) -> Result<bool, git2::Error> {
+    for parent in commit.parents() {
+        if diff_path(repo, Some(&parent.tree()?), tree, path)?.deltas().len() == 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Runs a full diff with rename detection to find where `path` was renamed from in this commit.
+fn find_rename<'a>(
+    repo: &'a Repository,
+    old: Option<&Tree>,
+    new: &Tree,
+    path: &Path,
+) -> Result<Option<(Diff<'a>, PathBuf)>, git2::Error> {
+    let mut diff = repo.diff_tree_to_tree(old, Some(new), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    let old_path = diff.deltas().find_map(|d| {
+        let renamed = d.status() == Delta::Renamed && d.new_file().path() == Some(path);
+        renamed.then(|| d.old_file().path().map(Path::to_path_buf)).flatten()
+    });
+    Ok(old_path.map(|old_path| (diff, old_path)))
+}
+
+/// Builds the revision entry, keeping only the deltas for `followed` if given.
+fn revision(commit: &Commit, diff: &Diff, followed: Option<&Path>) -> Result<FileRevision, git2::Error> {
+    let mut changes = Vec::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let new_path = delta.new_file().path().map(Path::to_path_buf);
+        if followed.is_some_and(|followed| new_path.as_deref() != Some(followed)) {
+            continue;
+        }
+        let (insertions, deletions) = match Patch::from_diff(diff, idx)? {
+            Some(patch) => {
+                let (_, insertions, deletions) = patch.line_stats()?;
+                (insertions as u32, deletions as u32)
+            }
+            None => (0, 0),
+        };
+        let status = ChangeStatus::from_delta(delta.status());
+        changes.push(PathChange {
+            old_path: (status != ChangeStatus::Added)
+                .then(|| delta.old_file().path().map(Path::to_path_buf))
+                .flatten(),
+            new_path: (status != ChangeStatus::Deleted).then_some(new_path).flatten(),
+            status,
+            insertions,
+            deletions,
+        });
+    }
+
+    Ok(FileRevision {
+        oid: commit.id().to_string(),
+        summary: commit.summary().unwrap_or("").to_string(),
+        author: commit.author().to_string(),
+        timestamp: commit.time().seconds(),
+        parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+        insertions: changes.iter().map(|c| c.insertions).sum(),
+        deletions: changes.iter().map(|c| c.deletions).sum(),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+
+    fn lines(count: usize, changed: &str) -> String {
+        let mut lines = (1..=count).map(|i| format!("line {}\n", i)).collect::<Vec<_>>();
+        lines[1] = format!("{}\n", changed);
+        lines.concat()
+    }
+
+    #[test]
+    fn test_file_history_follows_renames_with_per_path_stats() {
+        let repo = init_repo("history");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let added = commit_files(&repo, &[("old.txt", &lines(10, "line 2")), ("other.txt", "a\n")], "add").to_string();
+        let edited = commit_files(
+            &repo,
+            &[("old.txt", &lines(10, "edited")), ("other.txt", "b\nc\n")],
+            "edit",
+        )
+        .to_string();
+        fs::remove_file(dir.join("old.txt")).unwrap();
+        let renamed = commit_files(&repo, &[("new.txt", &(lines(10, "edited") + "appended\n"))], "rename").to_string();
+        commit_files(&repo, &[("other.txt", "d\n")], "unrelated");
+
+        let history = file_history(&repo, Path::new("new.txt"), &FileHistoryOptions::default()).unwrap();
+
+        let oids = history.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>();
+        assert_eq!(oids, vec![renamed.as_str(), edited.as_str(), added.as_str()]);
+
+        let rename = &history[0].changes;
+        assert_eq!(rename.len(), 1);
+        assert_eq!(rename[0].status, ChangeStatus::Renamed);
+        assert_eq!(rename[0].old_path.as_deref(), Some(Path::new("old.txt")));
+        assert_eq!(rename[0].new_path.as_deref(), Some(Path::new("new.txt")));
+        assert_eq!((history[0].insertions, history[0].deletions), (1, 0));
+
+        // Older revisions report the old name, and only its stats, not those of other.txt.
+        let edit = &history[1].changes;
+        assert_eq!(edit.len(), 1);
+        assert_eq!(edit[0].status, ChangeStatus::Modified);
+        assert_eq!(edit[0].new_path.as_deref(), Some(Path::new("old.txt")));
+        assert_eq!((history[1].insertions, history[1].deletions), (1, 1));
+
+        assert_eq!(history[2].changes[0].status, ChangeStatus::Added);
+        assert_eq!(history[2].changes[0].old_path, None);
+        assert_eq!((history[2].insertions, history[2].deletions), (10, 0));
+
+        let options = FileHistoryOptions {
+            follow_renames: false,
+            ..Default::default()
+        };
+        let history = file_history(&repo, Path::new("new.txt"), &options).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].changes[0].status, ChangeStatus::Added);
+        assert_eq!((history[0].insertions, history[0].deletions), (11, 0));
+
+        let options = FileHistoryOptions {
+            skip: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let history = file_history(&repo, Path::new("new.txt"), &options).unwrap();
+        assert_eq!(
+            history.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(),
+            vec![edited.as_str()]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
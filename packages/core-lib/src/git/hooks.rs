@@ -0,0 +1,557 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use git2::{Oid, Repository};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Lines of output kept in a [`HookError::Rejected`] message.
+const REJECTION_LINES: usize = 20;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum HookKind {
+    PreCommit,
+    PrepareCommitMsg,
+    CommitMsg,
+    PostCommit,
+    PrePush,
+    PostCheckout,
+    PostMerge,
+}
+
+impl HookKind {
+    pub const ALL: [HookKind; 7] = [
+        HookKind::PreCommit,
+        HookKind::PrepareCommitMsg,
+        HookKind::CommitMsg,
+        HookKind::PostCommit,
+        HookKind::PrePush,
+        HookKind::PostCheckout,
+        HookKind::PostMerge,
+    ];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            HookKind::PreCommit => "pre-commit",
+            HookKind::PrepareCommitMsg => "prepare-commit-msg",
+            HookKind::CommitMsg => "commit-msg",
+            HookKind::PostCommit => "post-commit",
+            HookKind::PrePush => "pre-push",
+            HookKind::PostCheckout => "post-checkout",
+            HookKind::PostMerge => "post-merge",
+        }
+    }
+
+    /// Whether the hook exiting with an error aborts the operation. The `post-` hooks run after the fact and only
+    /// report.
+    pub fn can_abort(self) -> bool {
+        !matches!(
+            self,
+            HookKind::PostCommit | HookKind::PostCheckout | HookKind::PostMerge
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct HookInfo {
+    pub kind: HookKind,
+    /// Where the hook is looked up, whether or not it exists.
+    pub path: PathBuf,
+    pub installed: bool,
+    /// The hook file exists but is not executable, so git and GitUltra skip it.
+    pub not_executable: bool,
+    /// A `<name>.sample` file is next to it, as `git init` leaves them.
+    pub sample: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum HookStream {
+    Stdout,
+    Stderr,
+}
+
+/// The result of a hook that ran to completion.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct HookRun {
+    pub kind: HookKind,
+    /// `None` when the hook was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Stdout and stderr lines, interleaved in the order they were read.
+    pub output: Vec<String>,
+    pub duration_ms: u64,
+}
+
+impl HookRun {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// A ref update about to be pushed, fed to the `pre-push` hook.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct PushUpdate {
+    /// `None` when the remote ref is deleted.
+    pub local_ref: Option<String>,
+    pub local_oid: String,
+    pub remote_ref: String,
+    /// `None` when the remote ref does not exist yet.
+    pub remote_oid: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HookError {
+    #[error("The {} hook failed{}{}", .hook.file_name(), exit_status(.code), tail(.output))]
+    Rejected {
+        hook: HookKind,
+        code: Option<i32>,
+        output: Vec<String>,
+    },
+    #[error("Cannot run the {} hook: {source}", .hook.file_name())]
+    Spawn { hook: HookKind, source: io::Error },
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+fn exit_status(code: &Option<i32>) -> String {
+    code.map(|code| format!(" with exit code {}", code)).unwrap_or_default()
+}
+
+fn tail(output: &[String]) -> String {
+    let lines = &output[output.len().saturating_sub(REJECTION_LINES)..];
+    match lines {
+        [] => String::new(),
+        _ => format!(":\n{}", lines.join("\n")),
+    }
+}
+
+/// The directory hooks are read from: `core.hooksPath` when set, `hooks` in the common git directory otherwise.
+///
+/// A relative `core.hooksPath` is relative to the working tree root, where hooks run, like git resolves it.
+pub fn hooks_dir(repo: &Repository) -> Result<PathBuf, git2::Error> {
+    match repo.config()?.get_path("core.hooksPath") {
+        Ok(path) if path.is_relative() => Ok(run_dir(repo).join(path)),
+        Ok(path) => Ok(path),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(repo.commondir().join("hooks")),
+        Err(e) => Err(e),
+    }
+}
+
+/// Describes each supported hook of `repo`, installed or not.
+pub fn list_hooks(repo: &Repository) -> Result<Vec<HookInfo>, git2::Error> {
+    let dir = hooks_dir(repo)?;
+    Ok(HookKind::ALL
+        .iter()
+        .map(|&kind| {
+            let path = dir.join(kind.file_name());
+            let installed = path.is_file();
+            HookInfo {
+                kind,
+                not_executable: installed && !is_executable(&path),
+                installed,
+                sample: dir.join(format!("{}.sample", kind.file_name())).is_file(),
+                path,
+            }
+        })
+        .collect())
+}
+
+/// The hook of `kind` if it is installed and executable; like git, other files are ignored.
+pub fn find_hook(repo: &Repository, kind: HookKind) -> Result<Option<PathBuf>, git2::Error> {
+    let path = hooks_dir(repo)?.join(kind.file_name());
+    Ok((path.is_file() && is_executable(&path)).then_some(path))
+}
+
+/// Runs the hook of `kind` with `args`, `stdin` and the extra environment `env`, from the working tree root.
+///
+/// Output lines are passed to `on_output` as they are printed. Returns `None` when the hook is not installed. A
+/// failing hook is not an error here, see [`HookRun::succeeded`].
+pub fn run_hook(
+    repo: &Repository,
+    kind: HookKind,
+    args: &[&str],
+    stdin: Option<&[u8]>,
+    env: &[(&str, &Path)],
+    mut on_output: impl FnMut(HookStream, &str),
+) -> Result<Option<HookRun>, HookError> {
+    let Some(path) = find_hook(repo, kind)? else {
+        return Ok(None);
+    };
+    debug!("Running {:?} {:?}", path, args);
+    let started = Instant::now();
+    let mut child = hook_command(&path)
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(run_dir(repo))
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| HookError::Spawn { hook: kind, source })?;
+
+    let writer = match (child.stdin.take(), stdin) {
+        (Some(mut pipe), Some(input)) => {
+            let input = input.to_vec();
+            Some(thread::spawn(move || pipe.write_all(&input)))
+        }
+        _ => None,
+    };
+    let (sender, receiver) = mpsc::channel();
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| read_lines(pipe, HookStream::Stdout, sender.clone())),
+        child.stderr.take().map(|pipe| read_lines(pipe, HookStream::Stderr, sender)),
+    ];
+    let mut output = Vec::new();
+    for (stream, line) in receiver {
+        on_output(stream, &line);
+        output.push(line);
+    }
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    let status = child.wait()?;
+    if let Some(writer) = writer {
+        // The hook may exit without reading its input, a broken pipe is not an error then.
+        if let Ok(Err(e)) = writer.join() {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                return Err(e.into());
+            }
+        }
+    }
+
+    let run = HookRun {
+        kind,
+        exit_code: status.code(),
+        output,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    if !run.succeeded() {
+        warn!("The {} hook failed with {:?}", kind.file_name(), run.exit_code);
+    }
+    Ok(Some(run))
+}
+
+/// Runs the hooks of a commit made with `message`, like `git commit -m`: `pre-commit`, `prepare-commit-msg` and
+/// `commit-msg`. Returns the message as the hooks left it.
+///
+/// `no_verify` skips `pre-commit` and `commit-msg`, like `git commit --no-verify`. The hooks may change the index,
+/// re-read it before committing.
+pub fn run_commit_hooks(
+    repo: &Repository,
+    message: &str,
+    no_verify: bool,
+    mut on_output: impl FnMut(HookKind, HookStream, &str),
+) -> Result<String, HookError> {
+    let index_file = repo.path().join("index");
+    let env = [("GIT_INDEX_FILE", index_file.as_path()), ("GIT_EDITOR", Path::new(":"))];
+    if !no_verify {
+        let run = run_hook(repo, HookKind::PreCommit, &[], None, &env, |stream, line| {
+            on_output(HookKind::PreCommit, stream, line)
+        })?;
+        check(run)?;
+    }
+
+    let message_hooks = [HookKind::PrepareCommitMsg, HookKind::CommitMsg]
+        .into_iter()
+        .filter(|&kind| kind != HookKind::CommitMsg || !no_verify)
+        .filter_map(|kind| find_hook(repo, kind).map(|hook| hook.map(|_| kind)).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    if message_hooks.is_empty() {
+        return Ok(message.to_string());
+    }
+    // The hooks edit the message in place, in the same file git uses.
+    let file = repo.path().join("COMMIT_EDITMSG");
+    fs::write(&file, message)?;
+    let file_arg = file.to_string_lossy();
+    for kind in message_hooks {
+        let args: &[&str] = match kind {
+            HookKind::PrepareCommitMsg => &[&file_arg, "message"],
+            _ => &[&file_arg],
+        };
+        let run = run_hook(repo, kind, args, None, &env, |stream, line| {
+            on_output(kind, stream, line)
+        })?;
+        check(run)?;
+    }
+    Ok(fs::read_to_string(&file)?)
+}
+
+/// Runs `post-commit` after a commit was made. Its outcome does not affect the commit.
+pub fn run_post_commit(
+    repo: &Repository,
+    on_output: impl FnMut(HookStream, &str),
+) -> Result<Option<HookRun>, HookError> {
+    let index_file = repo.path().join("index");
+    run_hook(
+        repo,
+        HookKind::PostCommit,
+        &[],
+        None,
+        &[("GIT_INDEX_FILE", index_file.as_path())],
+        on_output,
+    )
+}
+
+/// Runs `pre-push` before pushing `updates` to `remote` at `url`, failing with [`HookError::Rejected`] if it
+/// refuses the push.
+pub fn run_pre_push(
+    repo: &Repository,
+    remote: &str,
+    url: &str,
+    updates: &[PushUpdate],
+    on_output: impl FnMut(HookStream, &str),
+) -> Result<(), HookError> {
+    let zero = Oid::zero().to_string();
+    let stdin: String = updates
+        .iter()
+        .map(|update| {
+            format!(
+                "{} {} {} {}\n",
+                update.local_ref.as_deref().unwrap_or("(delete)"),
+                update.local_ref.as_ref().map_or(zero.as_str(), |_| update.local_oid.as_str()),
+                update.remote_ref,
+                update.remote_oid.as_deref().unwrap_or(&zero),
+            )
+        })
+        .collect();
+    let run = run_hook(
+        repo,
+        HookKind::PrePush,
+        &[remote, url],
+        Some(stdin.as_bytes()),
+        &[],
+        on_output,
+    )?;
+    check(run)
+}
+
+/// Runs `post-checkout` after HEAD moved from `old` to `new`. `branch` tells a branch switch from a checkout of
+/// files.
+pub fn run_post_checkout(
+    repo: &Repository,
+    old: Oid,
+    new: Oid,
+    branch: bool,
+    on_output: impl FnMut(HookStream, &str),
+) -> Result<Option<HookRun>, HookError> {
+    let (old, new) = (old.to_string(), new.to_string());
+    let flag = if branch { "1" } else { "0" };
+    run_hook(repo, HookKind::PostCheckout, &[&old, &new, flag], None, &[], on_output)
+}
+
+/// Runs `post-merge` after a merge updated the working tree, `squash` telling a squash merge.
+pub fn run_post_merge(
+    repo: &Repository,
+    squash: bool,
+    on_output: impl FnMut(HookStream, &str),
+) -> Result<Option<HookRun>, HookError> {
+    let flag = if squash { "1" } else { "0" };
+    run_hook(repo, HookKind::PostMerge, &[flag], None, &[], on_output)
+}
+
+/// Turns a failed run of a hook that can abort into [`HookError::Rejected`].
+fn check(run: Option<HookRun>) -> Result<(), HookError> {
+    match run {
+        Some(run) if !run.succeeded() && run.kind.can_abort() => Err(HookError::Rejected {
+            hook: run.kind,
+            code: run.exit_code,
+            output: run.output,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Hooks run from the root of the working tree, or from the git directory of bare repositories.
+fn run_dir(repo: &Repository) -> &Path {
+    repo.workdir().unwrap_or_else(|| repo.path())
+}
+
+fn read_lines(
+    pipe: impl Read + Send + 'static,
+    stream: HookStream,
+    sender: mpsc::Sender<(HookStream, String)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).split(b'\n') {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = String::from_utf8_lossy(&line).trim_end_matches('\r').to_string();
+            if sender.send((stream, line)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Windows has no executable bit, every hook file counts.
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn hook_command(path: &Path) -> Command {
+    Command::new(path)
+}
+
+/// Hooks are shell scripts on Windows too, run by the `sh` that ships with Git for Windows.
+#[cfg(not(unix))]
+fn hook_command(path: &Path) -> Command {
+    let mut command = Command::new("sh");
+    command.arg(path);
+    command
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn install(repo: &Repository, kind: HookKind, script: &str) {
+        let path = hooks_dir(repo).unwrap().join(kind.file_name());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_commit_hooks_run_in_order_and_can_reject() {
+        let dir = std::env::temp_dir().join("gitultra_hooks_test");
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        repo.config().unwrap().set_str("core.hooksPath", ".githooks").unwrap();
+        assert_eq!(hooks_dir(&repo).unwrap(), dir.join(".githooks"));
+
+        install(
+            &repo,
+            HookKind::PreCommit,
+            "echo checking; echo \"index $GIT_INDEX_FILE\" >&2\n",
+        );
+        install(
+            &repo,
+            HookKind::CommitMsg,
+            "printf '\\nSigned-off-by: Hook' >> \"$1\"\n",
+        );
+        let mut lines = Vec::new();
+        let message = run_commit_hooks(&repo, "Subject", false, |kind, stream, line| {
+            lines.push((kind, stream, line.to_string()))
+        })
+        .unwrap();
+        assert_eq!(message, "Subject\nSigned-off-by: Hook");
+        assert!(lines.contains(&(HookKind::PreCommit, HookStream::Stdout, "checking".to_string())));
+        let index = format!("index {}", repo.path().join("index").display());
+        assert!(lines.contains(&(HookKind::PreCommit, HookStream::Stderr, index)));
+        assert_eq!(
+            run_commit_hooks(&repo, "Subject", true, |_, _, _| {}).unwrap(),
+            "Subject"
+        );
+
+        install(&repo, HookKind::PreCommit, "echo 'lint failed' >&2\nexit 3\n");
+        match run_commit_hooks(&repo, "Subject", false, |_, _, _| {}) {
+            Err(HookError::Rejected { hook, code, output }) => {
+                assert_eq!(
+                    (hook, code, output),
+                    (HookKind::PreCommit, Some(3), vec!["lint failed".to_string()])
+                );
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+
+        install(&repo, HookKind::PostCheckout, "echo \"$1 $2 $3\"; exit 1\n");
+        let mut checked_out = Vec::new();
+        let run = run_post_checkout(&repo, Oid::zero(), Oid::zero(), true, |_, line| {
+            checked_out.push(line.to_string())
+        })
+        .unwrap()
+        .unwrap();
+        // post-checkout cannot undo the checkout, its failure is only reported.
+        assert!(!run.succeeded());
+        assert_eq!(checked_out, vec![format!("{} {} 1", Oid::zero(), Oid::zero())]);
+
+        let installed = [HookKind::PreCommit, HookKind::CommitMsg, HookKind::PostCheckout];
+        let hooks = list_hooks(&repo).unwrap();
+        assert!(hooks.iter().all(|hook| hook.installed == installed.contains(&hook.kind)));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_push_and_merge_hooks_get_their_arguments() {
+        let dir = std::env::temp_dir().join("gitultra_push_hooks_test");
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+
+        install(&repo, HookKind::PrePush, "echo \"$1 $2\"; cat\n");
+        let updates = [
+            PushUpdate {
+                local_ref: Some("refs/heads/main".into()),
+                local_oid: "1".repeat(40),
+                remote_ref: "refs/heads/main".into(),
+                remote_oid: None,
+            },
+            PushUpdate {
+                local_ref: None,
+                local_oid: Oid::zero().to_string(),
+                remote_ref: "refs/heads/old".into(),
+                remote_oid: Some("2".repeat(40)),
+            },
+        ];
+        let mut pushed = Vec::new();
+        run_pre_push(&repo, "origin", "https://example.com/repo.git", &updates, |_, line| {
+            pushed.push(line.to_string())
+        })
+        .unwrap();
+        assert_eq!(
+            pushed,
+            vec![
+                "origin https://example.com/repo.git".to_string(),
+                format!("refs/heads/main {} refs/heads/main {}", "1".repeat(40), Oid::zero()),
+                format!("(delete) {} refs/heads/old {}", Oid::zero(), "2".repeat(40)),
+            ]
+        );
+
+        install(
+            &repo,
+            HookKind::PrePush,
+            "cat > /dev/null; echo 'protected branch' >&2; exit 1\n",
+        );
+        match run_pre_push(&repo, "origin", "url", &updates, |_, _| {}) {
+            Err(HookError::Rejected { hook, code, output }) => {
+                assert_eq!(
+                    (hook, code, output),
+                    (HookKind::PrePush, Some(1), vec!["protected branch".to_string()])
+                );
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+
+        install(&repo, HookKind::PostMerge, "echo \"squash $1\"\n");
+        let mut merged = Vec::new();
+        for squash in [false, true] {
+            let run = run_post_merge(&repo, squash, |_, line| merged.push(line.to_string()))
+                .unwrap()
+                .unwrap();
+            assert!(run.succeeded());
+        }
+        assert_eq!(merged, vec!["squash 0".to_string(), "squash 1".to_string()]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,11 @@
 use cache_generated::gitultra::git;
 use git2::IndexEntry;
-use redb::{AccessGuard, Database, Error, ReadableTable, TableDefinition};
+use redb::{AccessGuard, Database, Error, ReadableTable, TableDefinition, TableHandle};
 use std::path::{Path, PathBuf};
 
+use super::journal::JOURNAL_TABLE;
+use super::signature::SIGNATURE_TABLE;
+
 extern crate flatbuffers;
 // import generated code
 #[allow(dead_code, unused_imports)]
@@ -11,7 +14,8 @@ mod cache_generated;
 pub use cache_generated::gitultra::git::*;
 
 const INDEX_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("git_index");
-const CACHE_VERSION: u32 = 1;
+const META_TABLE: TableDefinition<&str, u32> = TableDefinition::new("meta");
+const CACHE_VERSION: u32 = 2;
 
 pub struct GitIndexCache {
     db: Database,
@@ -19,11 +23,35 @@ pub struct GitIndexCache {
 }
 
 impl GitIndexCache {
+    /// Opens the cache database in the git directory of `repo_path`.
+    ///
+    /// When [`CACHE_VERSION`] changed, the derived tables are dropped to be rebuilt. The undo journal and the verified
+    /// signatures are kept: the journal cannot be rebuilt and verifying every signature again is slow.
     pub fn open(repo_path: &Path) -> Result<Self, redb::Error> {
-        let cache_path = repo_path.join(".git/gitultra_index.redb");
-        let db = Database::create(cache_path)?;
+        // Submodules and linked worktrees have a `.git` file pointing at their git directory.
+        let git_dir = match git2::Repository::open(repo_path) {
+            Ok(repo) => repo.path().to_path_buf(),
+            Err(_) => repo_path.join(".git"),
+        };
+        let db = Database::create(git_dir.join("gitultra_index.redb"))?;
 
         let write_txn = db.begin_write()?;
+        let version = match write_txn.open_table(META_TABLE) {
+            Ok(meta) => meta.get("version")?.map(|v| v.value()),
+            Err(_) => None,
+        };
+        if version != Some(CACHE_VERSION) {
+            // Layout changed, drop the derived tables and let them be rebuilt.
+            let tables: Vec<_> = write_txn.list_tables()?.filter(|table| !is_durable(table.name())).collect();
+            for table in tables {
+                write_txn.delete_table(table)?;
+            }
+            let tables: Vec<_> = write_txn.list_multimap_tables()?.collect();
+            for table in tables {
+                write_txn.delete_multimap_table(table)?;
+            }
+            write_txn.open_table(META_TABLE)?.insert("version", CACHE_VERSION)?;
+        }
         {
             let _ = write_txn.open_table(INDEX_TABLE)?;
         }
@@ -47,6 +75,20 @@ impl GitIndexCache {
     pub fn update_index(&self, entries: &[IndexEntry]) -> Result<(), CacheError> {
         todo!()
     }
+    /// Drops all cached index data, e.g. after `.git/index` changed on disk.
+    pub fn invalidate(&self) -> Result<(), CacheError> {
+        let write_txn = self.db.begin_write()?;
+        write_txn.delete_table(INDEX_TABLE)?;
+        write_txn.open_table(INDEX_TABLE)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// The underlying database, shared with the other caches stored next to the index.
+    pub(crate) fn database(&self) -> &Database {
+        &self.db
+    }
+
     fn cache_key(&self) -> Result<String, CacheError> {
         todo!()
     }
@@ -76,6 +118,11 @@ impl GitIndexCache {
     }
 }
 
+/// Tables holding data that must survive a [`CACHE_VERSION`] change.
+fn is_durable(name: &str) -> bool {
+    name == JOURNAL_TABLE.name() || name == SIGNATURE_TABLE.name()
+}
+
 struct CachedIndex {
     version: u32,
     entries: Vec<IndexEntry>,
@@ -93,8 +140,70 @@ pub enum CacheError {
     Table(#[from] redb::TableError),
     #[error("Transaction error: {0}")]
     Transaction(#[from] redb::TransactionError),
+    #[error("Storage error: {0}")]
+    Backend(#[from] redb::StorageError),
+    #[error("Commit error: {0}")]
+    Commit(#[from] redb::CommitError),
     #[error("Serialization error: {0}")]
     Serialization(#[from] flatbuffers::InvalidFlatbuffer),
+    #[error("Serialization error: {0}")]
+    Json(#[from] serde_json::Error),
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::commit_cache::CachedCommit;
+    use crate::git::journal::{self, OperationKind};
+    use crate::git::signature::{TrustLevel, Verification};
+    use crate::git::test_support::{commit_files, init_repo};
+
+    #[test]
+    fn test_version_change_keeps_journal_and_signatures() {
+        let repo = init_repo("index_cache");
+        let temp_dir = repo.workdir().unwrap().to_path_buf();
+        let cache = GitIndexCache::open(&temp_dir).unwrap();
+
+        let oid = journal::record(
+            &repo,
+            Some(&cache),
+            OperationKind::Commit,
+            "commit: first",
+            false,
+            || Ok::<_, git2::Error>(commit_files(&repo, &[], "first")),
+        )
+        .unwrap();
+        let verification = Verification {
+            trust: TrustLevel::Bad,
+            format: None,
+            signer: None,
+            key: None,
+            output: String::new(),
+        };
+        cache.put_verification(oid, &verification).unwrap();
+        cache
+            .put_commits(&[CachedCommit::from_commit(&repo.find_commit(oid).unwrap())])
+            .unwrap();
+
+        // Pretend the cache was written by an older version.
+        let write_txn = cache.database().begin_write().unwrap();
+        write_txn
+            .open_table(META_TABLE)
+            .unwrap()
+            .insert("version", CACHE_VERSION - 1)
+            .unwrap();
+        write_txn.commit().unwrap();
+        drop(cache);
+        let cache = GitIndexCache::open(&temp_dir).unwrap();
+
+        assert_eq!(cache.operations(None).unwrap().len(), 1);
+        assert_eq!(cache.get_verification(oid).unwrap(), Some(verification));
+        assert!(cache.get_commits(&[oid]).unwrap().is_empty());
+        assert!(cache.search_messages("first", None).unwrap().is_empty());
+
+        drop(cache);
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
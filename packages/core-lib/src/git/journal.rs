@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use git2::build::CheckoutBuilder;
+use git2::{Index, Oid, Repository, Status, StatusOptions};
+use log::warn;
+use redb::{ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::index_cache::{CacheError, GitIndexCache};
+use super::sparse::{self, sparse_status};
+
+/// Kept across cache version changes, see [`GitIndexCache::open`].
+pub(crate) const JOURNAL_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("journal");
+
+/// Operations kept in the journal; older ones are dropped when new ones are recorded.
+const JOURNAL_LIMIT: u64 = 500;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum OperationKind {
+    Commit,
+    Reset,
+    CreateBranch,
+    Checkout,
+    Tag,
+    ApplyPatch,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Type)]
+#[serde(tag = "kind", content = "target")]
+pub enum HeadState {
+    /// Full name of the branch HEAD points to, which may not exist yet.
+    Branch(String),
+    Detached(String),
+}
+
+/// A ref the operation created, moved or deleted. `None` means the ref did not exist.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct RefChange {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Trees of the index and of the tracked files in the working tree, like a stash without untracked files.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Type)]
+pub struct WorktreeSnapshot {
+    pub index_tree: String,
+    pub worktree_tree: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum OperationState {
+    Done,
+    Undone,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub description: String,
+    pub time: i64,
+    pub state: OperationState,
+    pub head_before: HeadState,
+    pub head_after: HeadState,
+    pub refs: Vec<RefChange>,
+    /// Only recorded for operations that touch the working tree.
+    pub worktree_before: Option<WorktreeSnapshot>,
+    pub worktree_after: Option<WorktreeSnapshot>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    #[error("Nothing to redo")]
+    NothingToRedo,
+    #[error("{0} changed since the operation, refusing to restore it")]
+    Diverged(String),
+    #[error("Restoring would overwrite the untracked file {0}")]
+    UntrackedOverwritten(String),
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+struct RepoState {
+    head: HeadState,
+    refs: BTreeMap<String, Oid>,
+    worktree: Option<WorktreeSnapshot>,
+}
+
+/// State captured by [`begin`] before a mutating operation runs.
+pub struct PendingOperation {
+    kind: OperationKind,
+    description: String,
+    before: RepoState,
+}
+
+/// Captures HEAD, the local refs and, if `touches_worktree`, the index and working tree before an operation.
+pub fn begin(
+    repo: &Repository,
+    kind: OperationKind,
+    description: &str,
+    touches_worktree: bool,
+) -> Result<PendingOperation, git2::Error> {
+    Ok(PendingOperation {
+        kind,
+        description: description.to_string(),
+        before: capture(repo, touches_worktree)?,
+    })
+}
+
+impl PendingOperation {
+    /// Records what the operation changed. Operations that changed nothing are not recorded.
+    pub fn finish(self, repo: &Repository, cache: &GitIndexCache) -> Result<Option<Operation>, JournalError> {
+        let after = capture(repo, self.before.worktree.is_some())?;
+        let names: BTreeSet<&String> = self.before.refs.keys().chain(after.refs.keys()).collect();
+        let mut refs = Vec::new();
+        for name in names {
+            let before = self.before.refs.get(name);
+            let after = after.refs.get(name);
+            if before != after {
+                refs.push(RefChange {
+                    name: name.clone(),
+                    before: before.map(Oid::to_string),
+                    after: after.map(Oid::to_string),
+                });
+            }
+        }
+        if refs.is_empty() && self.before.head == after.head && self.before.worktree == after.worktree {
+            return Ok(None);
+        }
+
+        let mut operation = Operation {
+            id: 0,
+            kind: self.kind,
+            description: self.description,
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            state: OperationState::Done,
+            head_before: self.before.head,
+            head_after: after.head,
+            refs,
+            worktree_before: self.before.worktree,
+            worktree_after: after.worktree,
+        };
+        operation.id = cache.append_operation(&operation)?;
+        Ok(Some(operation))
+    }
+}
+
+/// Runs `operation` and records it in the journal of `cache`.
+///
+/// Journaling is best effort: failing to capture or record the state is logged and never fails the operation.
+pub fn record<T, E>(
+    repo: &Repository,
+    cache: Option<&GitIndexCache>,
+    kind: OperationKind,
+    description: &str,
+    touches_worktree: bool,
+    operation: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let Some(cache) = cache else {
+        return operation();
+    };
+    let pending = match begin(repo, kind, description, touches_worktree) {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!("Not journaling {:?}: {}", kind, e);
+            return operation();
+        }
+    };
+    let result = operation()?;
+    if let Err(e) = pending.finish(repo, cache) {
+        warn!("Failed to journal {:?}: {}", kind, e);
+    }
+    Ok(result)
+}
+
+/// Reverts the most recent operation that is not undone yet.
+///
+/// Refuses if the refs, HEAD or the working tree changed since, so nothing done afterwards is lost.
+pub fn undo_last(repo: &Repository, cache: &GitIndexCache) -> Result<Operation, JournalError> {
+    let mut operation = cache
+        .operations(None)?
+        .into_iter()
+        .find(|op| op.state == OperationState::Done)
+        .ok_or(JournalError::NothingToUndo)?;
+    let message = format!("undo: {}", operation.description);
+    let refs: Vec<_> = operation
+        .refs
+        .iter()
+        .map(|change| (change.name.as_str(), &change.after, &change.before))
+        .collect();
+    restore(
+        repo,
+        &refs,
+        (&operation.head_after, &operation.head_before),
+        (&operation.worktree_after, &operation.worktree_before),
+        &message,
+    )?;
+    operation.state = OperationState::Undone;
+    cache.put_operation(&operation)?;
+    Ok(operation)
+}
+
+/// Re-applies the most recently undone operation.
+pub fn redo(repo: &Repository, cache: &GitIndexCache) -> Result<Operation, JournalError> {
+    let mut operation = cache
+        .operations(None)?
+        .into_iter()
+        .rev()
+        .find(|op| op.state == OperationState::Undone)
+        .ok_or(JournalError::NothingToRedo)?;
+    let message = format!("redo: {}", operation.description);
+    let refs: Vec<_> = operation
+        .refs
+        .iter()
+        .map(|change| (change.name.as_str(), &change.before, &change.after))
+        .collect();
+    restore(
+        repo,
+        &refs,
+        (&operation.head_before, &operation.head_after),
+        (&operation.worktree_before, &operation.worktree_after),
+        &message,
+    )?;
+    operation.state = OperationState::Done;
+    cache.put_operation(&operation)?;
+    Ok(operation)
+}
+
+impl GitIndexCache {
+    /// Journaled operations, newest first.
+    pub fn operations(&self, limit: Option<usize>) -> Result<Vec<Operation>, CacheError> {
+        let read_txn = self.database().begin_read()?;
+        let table = match read_txn.open_table(JOURNAL_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut operations = Vec::new();
+        for entry in table.iter()?.rev().take(limit.unwrap_or(usize::MAX)) {
+            let (_, value) = entry?;
+            operations.push(serde_json::from_slice(value.value())?);
+        }
+        Ok(operations)
+    }
+
+    /// Appends `operation` under a new id and returns it. Undone operations can no longer be redone afterwards.
+    fn append_operation(&self, operation: &Operation) -> Result<u64, CacheError> {
+        let write_txn = self.database().begin_write()?;
+        let id = {
+            let mut table = write_txn.open_table(JOURNAL_TABLE)?;
+            let mut undone = Vec::new();
+            for entry in table.iter()?.rev() {
+                let (key, value) = entry?;
+                let recorded: Operation = serde_json::from_slice(value.value())?;
+                if recorded.state != OperationState::Undone {
+                    break;
+                }
+                undone.push(key.value());
+            }
+            for key in undone {
+                table.remove(key)?;
+            }
+
+            let id = table.last()?.map(|(key, _)| key.value() + 1).unwrap_or(1);
+            let operation = Operation {
+                id,
+                ..operation.clone()
+            };
+            table.insert(id, serde_json::to_vec(&operation)?.as_slice())?;
+            table.retain(|key, _| key + JOURNAL_LIMIT > id)?;
+            id
+        };
+        write_txn.commit()?;
+        Ok(id)
+    }
+
+    fn put_operation(&self, operation: &Operation) -> Result<(), CacheError> {
+        let write_txn = self.database().begin_write()?;
+        {
+            let mut table = write_txn.open_table(JOURNAL_TABLE)?;
+            table.insert(operation.id, serde_json::to_vec(operation)?.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Moves the refs, HEAD and working tree from the `from` to the `to` side of an operation.
+fn restore(
+    repo: &Repository,
+    refs: &[(&str, &Option<String>, &Option<String>)],
+    (from_head, to_head): (&HeadState, &HeadState),
+    (from_worktree, to_worktree): (&Option<WorktreeSnapshot>, &Option<WorktreeSnapshot>),
+    message: &str,
+) -> Result<(), JournalError> {
+    for (name, from, _) in refs {
+        let current = repo.refname_to_id(name).ok().map(|oid| oid.to_string());
+        if current != **from {
+            return Err(JournalError::Diverged(name.to_string()));
+        }
+    }
+    if head_state(repo)? != *from_head {
+        return Err(JournalError::Diverged("HEAD".to_string()));
+    }
+    let worktree = match (from_worktree, to_worktree) {
+        (Some(from), Some(to)) => {
+            if snapshot(repo)? != *from {
+                return Err(JournalError::Diverged("The working tree".to_string()));
+            }
+            check_untracked(repo, to)?;
+            Some(to)
+        }
+        _ => None,
+    };
+
+    // The checkout compares against the current HEAD, so the working tree is restored before moving any ref.
+    if let Some(snapshot) = worktree {
+        let tree = repo.find_tree(Oid::from_str(&snapshot.worktree_tree)?)?;
+        repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().force()))?;
+    }
+    for (name, _, to) in refs {
+        match to {
+            Some(oid) => {
+                repo.reference(name, Oid::from_str(oid)?, true, message)?;
+            }
+            None => repo.find_reference(name)?.delete()?,
+        }
+    }
+    if from_head != to_head {
+        match to_head {
+            HeadState::Branch(name) => repo.set_head(name)?,
+            HeadState::Detached(oid) => repo.set_head_detached(Oid::from_str(oid)?)?,
+        }
+    }
+    if let Some(snapshot) = worktree {
+        let mut index = repo.index()?;
+        index.read_tree(&repo.find_tree(Oid::from_str(&snapshot.index_tree)?)?)?;
+        index.write()?;
+        sparse::restore_after_checkout(repo)?;
+    }
+    Ok(())
+}
+
+fn capture(repo: &Repository, worktree: bool) -> Result<RepoState, git2::Error> {
+    let mut refs = BTreeMap::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        // Remote tracking refs belong to the remote, fetching is not an operation to undo.
+        if reference.is_remote() {
+            continue;
+        }
+        if let (Some(name), Some(oid)) = (reference.name(), reference.target()) {
+            refs.insert(name.to_string(), oid);
+        }
+    }
+    Ok(RepoState {
+        head: head_state(repo)?,
+        refs,
+        worktree: if worktree { Some(snapshot(repo)?) } else { None },
+    })
+}
+
+fn head_state(repo: &Repository) -> Result<HeadState, git2::Error> {
+    let head = repo.find_reference("HEAD")?;
+    match (head.symbolic_target(), head.target()) {
+        (Some(name), _) => Ok(HeadState::Branch(name.to_string())),
+        (None, Some(oid)) => Ok(HeadState::Detached(oid.to_string())),
+        (None, None) => Err(git2::Error::from_str("HEAD is invalid")),
+    }
+}
+
+/// Writes the index and the tracked files of the working tree as trees.
+fn snapshot(repo: &Repository) -> Result<WorktreeSnapshot, git2::Error> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("repository has no working tree"))?;
+    let mut index = repo.index()?;
+    index.read(false)?;
+    let index_tree = index.write_tree()?;
+
+    let mut worktree = Index::new()?;
+    worktree.read_tree(&repo.find_tree(index_tree)?)?;
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let status = sparse_status(&index, &entry);
+        let path = Path::new(entry.path().ok_or_else(|| git2::Error::from_str("path is not valid utf-8"))?);
+        if status.contains(Status::WT_DELETED) {
+            worktree.remove(path, 0)?;
+        } else if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
+            let Some(mut index_entry) = worktree.get_path(path, 0) else {
+                continue;
+            };
+            let full_path = workdir.join(path);
+            let metadata = fs::symlink_metadata(&full_path).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+            if metadata.file_type().is_symlink() {
+                let target = fs::read_link(&full_path).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+                index_entry.id = repo.blob(target.to_string_lossy().as_bytes())?;
+                index_entry.mode = 0o120000;
+            } else {
+                index_entry.id = repo.blob_path(&full_path)?;
+                index_entry.mode = file_mode(&metadata);
+            }
+            index_entry.file_size = metadata.len() as u32;
+            worktree.add(&index_entry)?;
+        }
+    }
+    Ok(WorktreeSnapshot {
+        index_tree: index_tree.to_string(),
+        worktree_tree: worktree.write_tree_to(repo)?.to_string(),
+    })
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o100644
+}
+
+/// Fails if an untracked file is in the way of a file of `snapshot`.
+fn check_untracked(repo: &Repository, snapshot: &WorktreeSnapshot) -> Result<(), JournalError> {
+    let tree = repo.find_tree(Oid::from_str(&snapshot.worktree_tree)?)?;
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        if !entry.status().contains(Status::WT_NEW) {
+            continue;
+        }
+        if let Some(path) = entry.path() {
+            if tree.get_path(Path::new(path)).is_ok() {
+                return Err(JournalError::UntrackedOverwritten(path.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+
+    #[test]
+    fn test_undo_and_redo_restore_refs_and_working_tree() {
+        let repo = init_repo("journal");
+        let temp_dir = repo.workdir().unwrap().to_path_buf();
+        let cache = GitIndexCache::open(&temp_dir).unwrap();
+
+        let first = commit_files(&repo, &[("file.txt", "one\n")], "first");
+        fs::write(temp_dir.join("file.txt"), "two\n").unwrap();
+        let second = record(
+            &repo,
+            Some(&cache),
+            OperationKind::Commit,
+            "commit: second",
+            false,
+            || Ok::<_, git2::Error>(commit_files(&repo, &[], "second")),
+        )
+        .unwrap();
+
+        // A hard reset that also throws away an uncommitted change.
+        fs::write(temp_dir.join("file.txt"), "three\n").unwrap();
+        record(&repo, Some(&cache), OperationKind::Reset, "reset: first", true, || {
+            repo.reset(&repo.find_object(first, None)?, git2::ResetType::Hard, None)
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.join("file.txt")).unwrap(), "one\n");
+
+        undo_last(&repo, &cache).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(second));
+        assert_eq!(fs::read_to_string(temp_dir.join("file.txt")).unwrap(), "three\n");
+
+        undo_last(&repo, &cache).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(first));
+        assert!(matches!(undo_last(&repo, &cache), Err(JournalError::NothingToUndo)));
+
+        redo(&repo, &cache).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(second));
+
+        // Changing the working tree behind the journal's back makes redoing the reset unsafe.
+        fs::write(temp_dir.join("file.txt"), "four\n").unwrap();
+        assert!(matches!(redo(&repo, &cache), Err(JournalError::Diverged(_))));
+
+        drop(cache);
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
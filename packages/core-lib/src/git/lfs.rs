@@ -0,0 +1,453 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use git2::{AttrCheckFlags, Delta, Diff, DiffFile, DiffOptions, ErrorCode, Oid, Patch, Repository};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specta::Type;
+
+use super::history::ChangeStatus;
+use super::sparse;
+
+const POINTER_VERSION: &str = "https://git-lfs.github.com/spec/v1";
+/// Written by pre-release versions of Git LFS, still accepted by it.
+const LEGACY_POINTER_VERSION: &str = "https://hawser.github.com/spec/v1";
+/// Pointer files are always smaller than this, larger blobs need not be parsed.
+const MAX_POINTER_SIZE: u64 = 1024;
+/// Real content larger than this is not diffed as text.
+const MAX_DIFF_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The content of a pointer file, standing in for an object stored in Git LFS.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Type)]
+pub struct LfsPointer {
+    /// SHA-256 of the real content, in hex.
+    pub oid: String,
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// Parses a pointer file: a `version` line first, then `key value` lines sorted by key, among them
+    /// `oid sha256:<hex>` and `size <bytes>`.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() as u64 >= MAX_POINTER_SIZE || !data.ends_with(b"\n") {
+            return None;
+        }
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.lines();
+        let version = lines.next()?.strip_prefix("version ")?;
+        if version != POINTER_VERSION && version != LEGACY_POINTER_VERSION {
+            return None;
+        }
+        let (mut oid, mut size, mut last_key) = (None, None, "");
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            if key <= last_key {
+                return None;
+            }
+            last_key = key;
+            match key {
+                "oid" => {
+                    let hex = value.strip_prefix("sha256:")?;
+                    let valid = hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+                    oid = valid.then(|| hex.to_string());
+                }
+                "size" => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(LfsPointer { oid: oid?, size: size? })
+    }
+
+    /// The pointer of `content`, as `git lfs clean` computes it.
+    pub fn from_content(mut content: impl Read) -> io::Result<Self> {
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = content.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        let oid = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(LfsPointer { oid, size })
+    }
+
+    /// The pointer file committed in place of the content.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!(
+            "version {}\noid sha256:{}\nsize {}\n",
+            POINTER_VERSION, self.oid, self.size
+        )
+        .into_bytes()
+    }
+}
+
+/// What is known about an LFS object, as shown in status and diff views.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Type)]
+pub struct LfsInfo {
+    pub oid: String,
+    pub size: u64,
+    /// The object is in the local store, so its content can be shown.
+    pub present: bool,
+}
+
+/// The local object store of Git LFS, `.git/lfs/objects` unless `lfs.storage` moves it.
+///
+/// Objects are files named by their SHA-256, fanned out by its first two byte pairs.
+pub struct LfsStore {
+    objects: PathBuf,
+}
+
+impl LfsStore {
+    pub fn open(repo: &Repository) -> Result<Self, git2::Error> {
+        let root = match repo.config()?.get_path("lfs.storage") {
+            Ok(path) => repo.commondir().join(path),
+            Err(e) if e.code() == ErrorCode::NotFound => repo.commondir().join("lfs"),
+            Err(e) => return Err(e),
+        };
+        Ok(LfsStore {
+            objects: root.join("objects"),
+        })
+    }
+
+    pub fn object_path(&self, oid: &str) -> PathBuf {
+        self.objects.join(&oid[..2]).join(&oid[2..4]).join(oid)
+    }
+
+    /// Whether the object of `pointer` is stored completely.
+    pub fn contains(&self, pointer: &LfsPointer) -> bool {
+        fs::metadata(self.object_path(&pointer.oid)).is_ok_and(|metadata| metadata.len() == pointer.size)
+    }
+
+    pub fn info(&self, pointer: &LfsPointer) -> LfsInfo {
+        LfsInfo {
+            oid: pointer.oid.clone(),
+            size: pointer.size,
+            present: self.contains(pointer),
+        }
+    }
+
+    pub fn open_object(&self, pointer: &LfsPointer) -> io::Result<File> {
+        File::open(self.object_path(&pointer.oid))
+    }
+
+    pub fn read(&self, pointer: &LfsPointer) -> io::Result<Vec<u8>> {
+        fs::read(self.object_path(&pointer.oid))
+    }
+
+    /// Stores `content` like `git lfs clean` does, returning the pointer to commit in its place.
+    pub fn insert(&self, content: &[u8]) -> io::Result<LfsPointer> {
+        let pointer = LfsPointer::from_content(content)?;
+        let path = self.object_path(&pointer.oid);
+        if !self.contains(&pointer) {
+            let dir = path.parent().expect("object paths have a parent");
+            fs::create_dir_all(dir)?;
+            // Written aside first, so readers never see a partial object.
+            let partial = dir.join(format!("{}.part", pointer.oid));
+            File::create(&partial)?.write_all(content)?;
+            fs::rename(&partial, &path)?;
+        }
+        Ok(pointer)
+    }
+}
+
+/// Whether `path` is stored in LFS, i.e. has the `filter=lfs` attribute.
+pub fn is_lfs_path(repo: &Repository, path: &Path) -> Result<bool, git2::Error> {
+    Ok(repo.get_attr(path, "filter", AttrCheckFlags::FILE_THEN_INDEX)? == Some("lfs"))
+}
+
+/// The pointer stored in the blob `oid`, if it is one.
+pub fn blob_pointer(repo: &Repository, oid: Oid) -> Result<Option<LfsPointer>, git2::Error> {
+    let odb = repo.odb()?;
+    let (size, _) = odb.read_header(oid)?;
+    if size as u64 >= MAX_POINTER_SIZE {
+        return Ok(None);
+    }
+    let object = odb.read(oid)?;
+    Ok(LfsPointer::parse(object.data()))
+}
+
+/// Whether the working tree file `path` (relative to the working tree root) stands for the object of `pointer`:
+/// either it is that pointer file, or it is an LFS path smudged to that content.
+///
+/// Content is only hashed when it has the size of the object, large files rarely do unless unchanged.
+pub fn worktree_matches(repo: &Repository, path: &Path, pointer: &LfsPointer) -> Result<bool, LfsError> {
+    let Some(full) = repo.workdir().map(|root| root.join(path)) else {
+        return Ok(false);
+    };
+    let file = File::open(&full)?;
+    let size = file.metadata()?.len();
+    if size < MAX_POINTER_SIZE {
+        if let Some(parsed) = LfsPointer::parse(&fs::read(&full)?) {
+            return Ok(parsed == *pointer);
+        }
+    }
+    if size != pointer.size || !is_lfs_path(repo, path)? {
+        return Ok(false);
+    }
+    Ok(LfsPointer::from_content(io::BufReader::new(file))? == *pointer)
+}
+
+/// Which changes [`lfs_diffs`] compares.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+#[serde(tag = "kind", content = "value")]
+pub enum LfsDiffRange {
+    /// The working tree against the index, untracked files included.
+    Unstaged,
+    /// The index against HEAD.
+    Staged,
+    /// A commit against its first parent.
+    Commit(String),
+}
+
+/// A changed LFS file, described by its objects rather than by its pointer text.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct LfsDiff {
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub status: ChangeStatus,
+    pub old: Option<LfsInfo>,
+    pub new: Option<LfsInfo>,
+    /// Unified diff of the real content, when the objects of both sides are available and it is text.
+    pub patch: Option<String>,
+    pub binary: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LfsError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// One side of a changed file: its pointer, if it is an LFS file, and the real content if it is at hand.
+struct Side {
+    pointer: Option<LfsPointer>,
+    content: Option<Vec<u8>>,
+}
+
+/// Lists the LFS files changed in `range`, optionally limited to `pathspec`, with diffs of their real content.
+///
+/// LFS files whose smudged working tree content matches the pointer in the index are not reported: they only
+/// look modified because libgit2 does not run the LFS filter.
+pub fn lfs_diffs(repo: &Repository, range: &LfsDiffRange, pathspec: Option<&Path>) -> Result<Vec<LfsDiff>, LfsError> {
+    let mut options = DiffOptions::new();
+    if let Some(pathspec) = pathspec {
+        options.pathspec(pathspec);
+    }
+    let diff = match range {
+        LfsDiffRange::Unstaged => {
+            options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            repo.diff_index_to_workdir(None, Some(&mut options))?
+        }
+        LfsDiffRange::Staged => {
+            let head = match repo.head() {
+                Ok(head) => Some(head.peel_to_tree()?),
+                Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+                Err(e) => return Err(e.into()),
+            };
+            repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))?
+        }
+        LfsDiffRange::Commit(rev) => {
+            let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+            let parent = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(e) if e.code() == ErrorCode::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), Some(&mut options))?
+        }
+    };
+    let workdir_side = matches!(range, LfsDiffRange::Unstaged);
+    collect_diffs(repo, &diff, workdir_side)
+}
+
+fn collect_diffs(repo: &Repository, diff: &Diff, workdir_side: bool) -> Result<Vec<LfsDiff>, LfsError> {
+    let store = LfsStore::open(repo)?;
+    // Files left out by a sparse checkout are missing from the working tree on purpose, not deleted.
+    let index = if workdir_side { Some(repo.index()?) } else { None };
+    let mut diffs = Vec::new();
+    for delta in diff.deltas() {
+        let (old_file, new_file) = (delta.old_file(), delta.new_file());
+        if let (Some(index), Delta::Deleted, Some(path)) = (&index, delta.status(), old_file.path()) {
+            if sparse::is_excluded(index, path) {
+                continue;
+            }
+        }
+        let lfs_path = [old_file.path(), new_file.path()]
+            .into_iter()
+            .flatten()
+            .try_fold(false, |found, path| {
+                Ok::<_, git2::Error>(found || is_lfs_path(repo, path)?)
+            })?;
+        let old = side(repo, &store, &old_file, false, lfs_path)?;
+        let new = side(repo, &store, &new_file, workdir_side, lfs_path)?;
+        let old_pointer = old.as_ref().and_then(|side| side.pointer.clone());
+        let new_pointer = new.as_ref().and_then(|side| side.pointer.clone());
+        if old_pointer.is_none() && new_pointer.is_none() {
+            continue;
+        }
+        if delta.status() == Delta::Modified && old_pointer == new_pointer {
+            continue;
+        }
+
+        let old_content = old.as_ref().map(|side| side.content.as_deref());
+        let new_content = new.as_ref().map(|side| side.content.as_deref());
+        let (mut patch, mut binary) = (None, false);
+        // A missing side is empty, a side whose object is not stored locally cannot be diffed.
+        if let (Some(old_content), Some(new_content)) =
+            (old_content.unwrap_or(Some(&[])), new_content.unwrap_or(Some(&[])))
+        {
+            let mut rendered = Patch::from_buffers(
+                old_content,
+                old_file.path(),
+                new_content,
+                new_file.path(),
+                Some(&mut DiffOptions::new()),
+            )?;
+            let text = rendered.to_buf()?;
+            binary = rendered.delta().flags().is_binary();
+            if !binary {
+                patch = Some(String::from_utf8_lossy(&text).into_owned());
+            }
+        }
+        diffs.push(LfsDiff {
+            old_path: old.as_ref().and(old_file.path()).map(Path::to_path_buf),
+            new_path: new.as_ref().and(new_file.path()).map(Path::to_path_buf),
+            status: ChangeStatus::from_delta(delta.status()),
+            old: old_pointer.map(|pointer| store.info(&pointer)),
+            new: new_pointer.map(|pointer| store.info(&pointer)),
+            patch,
+            binary,
+        });
+    }
+    Ok(diffs)
+}
+
+/// Reads one side of a changed file, from the working tree when `workdir` is set. `lfs_path` marks files whose
+/// content counts as LFS content even when it is not a pointer.
+fn side(
+    repo: &Repository,
+    store: &LfsStore,
+    file: &DiffFile,
+    workdir: bool,
+    lfs_path: bool,
+) -> Result<Option<Side>, LfsError> {
+    if !file.exists() {
+        return Ok(None);
+    }
+    let full = match (workdir, file.path(), repo.workdir()) {
+        (true, Some(path), Some(root)) => root.join(path),
+        _ => return Ok(Some(stored_side(store, blob_pointer(repo, file.id())?)?)),
+    };
+    let size = fs::metadata(&full)?.len();
+    let pointer = match size < MAX_POINTER_SIZE {
+        true => LfsPointer::parse(&fs::read(&full)?),
+        false => None,
+    };
+    if pointer.is_some() || !lfs_path {
+        return Ok(Some(stored_side(store, pointer)?));
+    }
+    // Smudged content, hashed to the pointer it would be committed as.
+    let pointer = LfsPointer::from_content(io::BufReader::new(File::open(&full)?))?;
+    let content = (size <= MAX_DIFF_SIZE).then(|| fs::read(&full)).transpose()?;
+    Ok(Some(Side {
+        pointer: Some(pointer),
+        content,
+    }))
+}
+
+/// A side given by its pointer, its content read from the store when present.
+fn stored_side(store: &LfsStore, pointer: Option<LfsPointer>) -> io::Result<Side> {
+    let content = match &pointer {
+        Some(pointer) if pointer.size <= MAX_DIFF_SIZE && store.contains(pointer) => Some(store.read(pointer)?),
+        _ => None,
+    };
+    Ok(Side { pointer, content })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::status;
+    use crate::git::test_support::{commit_files, init_repo};
+
+    #[test]
+    fn test_parses_pointers() {
+        let pointer = LfsPointer::from_content(&b"hello\n"[..]).unwrap();
+        assert_eq!(
+            pointer.oid,
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+        assert_eq!(LfsPointer::parse(&pointer.to_bytes()), Some(pointer.clone()));
+
+        let with_extension = format!(
+            "version {}\next-0-foo sha256:{}\noid sha256:{}\nsize 6\n",
+            POINTER_VERSION, pointer.oid, pointer.oid
+        );
+        assert_eq!(LfsPointer::parse(with_extension.as_bytes()), Some(pointer.clone()));
+
+        let unsorted = format!("version {}\nsize 6\noid sha256:{}\n", POINTER_VERSION, pointer.oid);
+        assert_eq!(LfsPointer::parse(unsorted.as_bytes()), None);
+        assert_eq!(LfsPointer::parse(b"hello\n"), None);
+    }
+
+    #[test]
+    fn test_reports_lfs_files_by_object() {
+        let repo = init_repo("lfs");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let store = LfsStore::open(&repo).unwrap();
+
+        // Commit a pointer, with the smudged content in the working tree, like `git lfs` leaves it.
+        let pointer = store.insert(b"one\ntwo\n").unwrap();
+        let pointer_text = String::from_utf8(pointer.to_bytes()).unwrap();
+        let attributes = "*.txt filter=lfs diff=lfs merge=lfs -text\n";
+        let files = [(".gitattributes", attributes), ("data.txt", pointer_text.as_str())];
+        commit_files(&repo, &files, "lfs");
+        fs::write(dir.join("data.txt"), "one\ntwo\n").unwrap();
+        assert!(status::file_statuses(&repo).unwrap().is_empty());
+        assert!(lfs_diffs(&repo, &LfsDiffRange::Unstaged, None).unwrap().is_empty());
+        // Without the smudge filter the pointer file itself is checked out.
+        fs::write(dir.join("data.txt"), pointer.to_bytes()).unwrap();
+        assert!(status::file_statuses(&repo).unwrap().is_empty());
+
+        // Same size as the object, so only hashing tells it changed.
+        fs::write(dir.join("data.txt"), "one\ntwx\n").unwrap();
+        let statuses = status::file_statuses(&repo).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].unstaged, Some(ChangeStatus::Modified));
+
+        fs::write(dir.join("data.txt"), "one\nthree\n").unwrap();
+        let statuses = status::file_statuses(&repo).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].unstaged, Some(ChangeStatus::Modified));
+        assert_eq!(statuses[0].lfs, Some(store.info(&pointer)));
+
+        let diffs = lfs_diffs(&repo, &LfsDiffRange::Unstaged, None).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].old, Some(store.info(&pointer)));
+        assert!(diffs[0].old.as_ref().unwrap().present);
+        let patch = diffs[0].patch.as_deref().unwrap();
+        assert!(patch.contains("-two\n+three\n"), "{}", patch);
+
+        // Committed pointers whose objects are missing are still described, without content.
+        let missing = LfsPointer::from_content(&b"elsewhere"[..]).unwrap();
+        let missing_text = String::from_utf8(missing.to_bytes()).unwrap();
+        commit_files(&repo, &[("data.txt", &missing_text)], "missing");
+        let diffs = lfs_diffs(&repo, &LfsDiffRange::Commit("HEAD".into()), None).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].new, Some(store.info(&missing)));
+        assert!(!diffs[0].new.as_ref().unwrap().present);
+        assert!(diffs[0].patch.is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use git2::Oid;
+use redb::{MultimapTable, MultimapTableDefinition, ReadOnlyMultimapTable, StorageError};
+
+use super::index_cache::{CacheError, GitIndexCache};
+
+/// Inverted index of commit messages: lowercase token → raw OID bytes of the commits using it.
+pub(crate) const MESSAGE_INDEX_TABLE: MultimapTableDefinition<&str, &[u8]> =
+    MultimapTableDefinition::new("message_index");
+
+/// Tokens shorter than this are not indexed; they match too many commits to be useful.
+const MIN_TOKEN_LEN: usize = 2;
+/// Longer tokens (hashes, URLs) are indexed by their prefix only.
+const MAX_TOKEN_LEN: usize = 64;
+
+/// Splits a message into lowercase alphanumeric tokens, without duplicates.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() >= MIN_TOKEN_LEN)
+        .map(|t| t.chars().take(MAX_TOKEN_LEN).flat_map(char::to_lowercase).collect::<String>())
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+/// Adds the tokens of `message` to the index. Called for every commit written to the commit cache.
+pub(crate) fn index_message(
+    table: &mut MultimapTable<&str, &[u8]>,
+    oid: Oid,
+    message: &str,
+) -> Result<(), StorageError> {
+    for token in tokenize(message) {
+        table.insert(token.as_str(), oid.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// A parsed message query: OR of groups, each an AND of terms.
+///
+/// Terms are separated by whitespace, groups by `|` or `OR`. A trailing `*` makes a term a prefix match, e.g.
+/// `fix* parser | crash` finds commits mentioning `crash`, or `parser` together with a word starting with `fix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageQuery {
+    pub groups: Vec<Vec<Term>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub token: String,
+    pub prefix: bool,
+}
+
+impl MessageQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut groups = vec![Vec::new()];
+        for word in query.split_whitespace() {
+            if word == "|" || word == "OR" {
+                groups.push(Vec::new());
+                continue;
+            }
+            for (i, part) in word.split('|').enumerate() {
+                if i > 0 {
+                    groups.push(Vec::new());
+                }
+                let prefix = part.ends_with('*');
+                let tokens = tokenize(part);
+                let last = tokens.len().saturating_sub(1);
+                for (j, token) in tokens.into_iter().enumerate() {
+                    groups.last_mut().unwrap().push(Term {
+                        token,
+                        prefix: prefix && j == last,
+                    });
+                }
+            }
+        }
+        groups.retain(|group| !group.is_empty());
+        Self { groups }
+    }
+}
+
+impl GitIndexCache {
+    /// Finds the cached commits whose message matches `query`, newest first.
+    ///
+    /// Only commits already in the commit cache are found; see [`super::commit_cache::cache_all_commits`].
+    pub fn search_messages(&self, query: &str, limit: Option<u32>) -> Result<Vec<String>, CacheError> {
+        let query = MessageQuery::parse(query);
+        let read_txn = self.database().begin_read()?;
+        let table = match read_txn.open_multimap_table(MESSAGE_INDEX_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut found = HashSet::new();
+        for group in &query.groups {
+            let mut matches: Option<HashSet<Oid>> = None;
+            for term in group {
+                let postings = postings(&table, term)?;
+                matches = Some(match matches {
+                    Some(matches) => matches.intersection(&postings).copied().collect(),
+                    None => postings,
+                });
+                if matches.as_ref().is_some_and(HashSet::is_empty) {
+                    break;
+                }
+            }
+            found.extend(matches.unwrap_or_default());
+        }
+
+        let oids: Vec<Oid> = found.into_iter().collect();
+        let mut commits: Vec<_> = self.get_commits(&oids)?.into_values().collect();
+        commits.sort_by_key(|c| std::cmp::Reverse(c.committer_time));
+        if let Some(limit) = limit {
+            commits.truncate(limit as usize);
+        }
+        Ok(commits.into_iter().map(|c| c.oid).collect())
+    }
+}
+
+fn postings(table: &ReadOnlyMultimapTable<&str, &[u8]>, term: &Term) -> Result<HashSet<Oid>, CacheError> {
+    let mut oids = HashSet::new();
+    if !term.prefix {
+        for value in table.get(term.token.as_str())? {
+            oids.insert(Oid::from_bytes(value?.value())?);
+        }
+        return Ok(oids);
+    }
+
+    for entry in table.range(term.token.as_str()..)? {
+        let (token, values) = entry?;
+        if !token.value().starts_with(term.token.as_str()) {
+            break;
+        }
+        for value in values {
+            oids.insert(Oid::from_bytes(value?.value())?);
+        }
+    }
+    Ok(oids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::commit_cache;
+    use git2::Repository;
+
+    #[test]
+    fn test_parse_query() {
+        let query = MessageQuery::parse("Fix* parser | crash OR foo-bar");
+        let term = |token: &str, prefix| Term {
+            token: token.to_string(),
+            prefix,
+        };
+        assert_eq!(
+            query.groups,
+            vec![
+                vec![term("fix", true), term("parser", false)],
+                vec![term("crash", false)],
+                vec![term("foo", false), term("bar", false)],
+            ]
+        );
+        assert_eq!(tokenize("Fix: the FIX of a fix"), vec!["fix", "the", "of"]);
+    }
+
+    #[test]
+    fn test_index_and_search() {
+        let dir = std::env::temp_dir().join("gitultra_message_index_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        let mut parent = None;
+        let mut commit = |message: &str, time: i64, update_ref: Option<&str>| {
+            let signature = git2::Signature::new("Test", "test@example.com", &git2::Time::new(time, 0)).unwrap();
+            let parents = parent.iter().collect::<Vec<_>>();
+            let oid = repo
+                .commit(update_ref, &signature, &signature, message, &tree, &parents)
+                .unwrap();
+            parent = Some(repo.find_commit(oid).unwrap());
+            oid.to_string()
+        };
+        let parser = commit("Fix parser crash", 1000, Some("HEAD"));
+        let lexer = commit("Add lexer\n\nFixes the tokenizer", 2000, Some("HEAD"));
+        // Only on a branch that is not checked out.
+        let docs = commit("Document the parser", 3000, None);
+        repo.reference("refs/heads/docs", Oid::from_str(&docs).unwrap(), false, "test")
+            .unwrap();
+
+        let cache = GitIndexCache::open(&dir).unwrap();
+        commit_cache::cache_all_commits(&repo, &cache, |_, _| true).unwrap();
+
+        assert_eq!(
+            cache.search_messages("parser", None).unwrap(),
+            vec![docs.clone(), parser.clone()]
+        );
+        assert_eq!(
+            cache.search_messages("PARSER crash", None).unwrap(),
+            vec![parser.clone()]
+        );
+        assert_eq!(
+            cache.search_messages("fix*", None).unwrap(),
+            vec![lexer.clone(), parser.clone()]
+        );
+        assert_eq!(
+            cache.search_messages("tokenizer | document", None).unwrap(),
+            vec![docs.clone(), lexer.clone()]
+        );
+        assert_eq!(cache.search_messages("parser", Some(1)).unwrap(), vec![docs.clone()]);
+        assert!(cache.search_messages("lexer crash", None).unwrap().is_empty());
+        assert!(cache.search_messages("", None).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
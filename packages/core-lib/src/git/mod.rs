@@ -3,7 +3,35 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
-mod index_cache;
+pub mod archive;
+pub mod blame;
+pub mod branch;
+pub mod bundle;
+pub mod commit;
+pub mod commit_cache;
+pub mod commit_details;
+pub mod config;
+pub mod discovery;
+pub mod history;
+pub mod hooks;
+pub mod index_cache;
+pub mod journal;
+pub mod lfs;
+pub mod message_index;
+pub mod partial_clone;
+pub mod patch;
+pub mod reflog;
+pub mod remote;
+pub mod search;
+pub mod signature;
+pub mod signing;
+pub mod sparse;
+pub mod status;
+pub mod submodule;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod watcher;
+pub mod worktree;
 
 #[derive(Deserialize, Serialize, Debug, Type)]
 pub struct CommitNode {
@@ -12,6 +40,9 @@ pub struct CommitNode {
     pub message: String,
     pub parents: Vec<String>,
     pub timestamp: i64,
+    /// Signature trust, if the commit was verified before. See [`signature::annotate_trust`].
+    #[serde(default)]
+    pub trust: Option<signature::TrustLevel>,
 }
 
 pub fn open_repo(path: &PathBuf) -> Result<Repository, git2::Error> {
@@ -30,22 +61,42 @@ pub fn get_commits(repo: &Repository) -> Result<Vec<git2::Commit>, git2::Error>
 
 
 pub fn get_commit_graph(repo: &Repository) -> Result<Vec<CommitNode>, git2::Error> {
+    walk_commit_graph(repo, |_| true)
+}
+
+/// Like [`get_commit_graph`], calling `on_progress` with the number of commits walked so far.
+///
+/// Returning `false` from `on_progress` aborts the walk with an [`git2::ErrorCode::User`] error.
+pub fn walk_commit_graph(
+    repo: &Repository,
+    mut on_progress: impl FnMut(usize) -> bool,
+) -> Result<Vec<CommitNode>, git2::Error> {
     let mut walk = repo.revwalk()?;
     walk.set_sorting(git2::Sort::TIME | git2::Sort::TOPOLOGICAL)?;
     walk.push_head()?;
 
-    walk.map(|oid| {
-        let oid = oid?;
-        let commit = repo.find_commit(oid)?.to_owned();
-        let x = CommitNode {
-            oid: oid.to_string(),
-            author: commit.author().to_string(),
-            message: commit.message().unwrap_or("").to_string(),
-            parents: commit.parent_ids().map(|p| p.to_string()).collect(),
-            timestamp: commit.time().seconds(),
-        }; 
-        Ok(x)
-    }).collect()
+    walk.enumerate()
+        .map(|(i, oid)| {
+            if i % 1000 == 0 && !on_progress(i) {
+                return Err(git2::Error::new(
+                    git2::ErrorCode::User,
+                    git2::ErrorClass::None,
+                    "commit graph walk cancelled",
+                ));
+            }
+            let oid = oid?;
+            let commit = repo.find_commit(oid)?.to_owned();
+            let x = CommitNode {
+                oid: oid.to_string(),
+                author: commit.author().to_string(),
+                message: commit.message().unwrap_or("").to_string(),
+                parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+                timestamp: commit.time().seconds(),
+                trust: None,
+            };
+            Ok(x)
+        })
+        .collect()
 }
 
 pub fn get_branches(repo: &Repository) -> Vec<git2::Branch> {
@@ -0,0 +1,117 @@
+use git2::{Config, ErrorCode, Oid, Repository};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// A remote a partial clone was made from, which promises the objects its filter left out.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Type)]
+pub struct PromisorRemote {
+    pub name: String,
+    pub url: Option<String>,
+    /// `remote.<name>.partialCloneFilter`, like `blob:none` or `tree:0`. `None` when the remote is only marked as a
+    /// promisor.
+    pub filter: Option<String>,
+}
+
+/// An object the partial clone does not have yet. Git fetches such objects from the promisor remote when a command
+/// needs them; libgit2 cannot.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "Object {oid} is not in this partial clone yet and cannot be fetched here; \
+     check the files out once with git to fetch it from {remote}"
+)]
+pub struct MissingObject {
+    pub oid: String,
+    pub remote: String,
+}
+
+/// The promisor remotes of `repo`, empty unless it is a partial clone.
+///
+/// A remote counts when it has `remote.<name>.promisor` set, a `partialCloneFilter`, or is named by the
+/// `extensions.partialClone` that older versions of git wrote.
+pub fn promisor_remotes(repo: &Repository) -> Result<Vec<PromisorRemote>, git2::Error> {
+    let config = repo.config()?.snapshot()?;
+    let extension = config_string(&config, "extensions.partialClone")?;
+    let mut promisors = Vec::new();
+    for name in repo.remotes()?.iter().flatten() {
+        let promisor = match config.get_bool(&format!("remote.{}.promisor", name)) {
+            Ok(value) => value,
+            Err(e) if e.code() == ErrorCode::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        let filter = config_string(&config, &format!("remote.{}.partialCloneFilter", name))?;
+        if promisor || filter.is_some() || extension.as_deref() == Some(name) {
+            promisors.push(PromisorRemote {
+                name: name.to_string(),
+                url: config_string(&config, &format!("remote.{}.url", name))?,
+                filter,
+            });
+        }
+    }
+    Ok(promisors)
+}
+
+/// The first of `oids` that a partial clone of `repo` does not have yet, with the remote promising it.
+///
+/// Outside a partial clone a missing object means a corrupt repository, which libgit2 reports by itself; this returns
+/// `None` then.
+pub fn missing_promised_object(repo: &Repository, oids: &[Oid]) -> Result<Option<MissingObject>, git2::Error> {
+    let odb = repo.odb()?;
+    let Some(missing) = oids.iter().find(|oid| !odb.exists(**oid)) else {
+        return Ok(None);
+    };
+    Ok(promisor_remotes(repo)?.into_iter().next().map(|remote| MissingObject {
+        oid: missing.to_string(),
+        remote: remote.name,
+    }))
+}
+
+fn config_string(config: &Config, name: &str) -> Result<Option<String>, git2::Error> {
+    match config.get_string(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+
+    #[test]
+    fn test_promisor_remotes_and_missing_objects() {
+        let repo = init_repo("partial_clone");
+        let oid = commit_files(&repo, &[("file.txt", "content\n")], "file");
+        let blob = repo.head().unwrap().peel_to_tree().unwrap().get_name("file.txt").unwrap().id();
+        repo.remote("origin", "https://example.com/origin.git").unwrap();
+        repo.remote("backup", "https://example.com/backup.git").unwrap();
+        repo.remote("legacy", "https://example.com/legacy.git").unwrap();
+        assert!(promisor_remotes(&repo).unwrap().is_empty());
+
+        let hex = blob.to_string();
+        let object = repo.path().join("objects").join(&hex[..2]).join(&hex[2..]);
+        fs::remove_file(object).unwrap();
+        assert_eq!(missing_promised_object(&repo, &[oid, blob]).unwrap(), None);
+
+        let mut config = repo.config().unwrap();
+        config.set_bool("remote.origin.promisor", true).unwrap();
+        config.set_str("remote.origin.partialclonefilter", "blob:none").unwrap();
+        config.set_str("extensions.partialClone", "legacy").unwrap();
+        let remotes = promisor_remotes(&repo).unwrap();
+        let remotes = remotes
+            .iter()
+            .map(|r| (r.name.as_str(), r.filter.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(remotes, vec![("legacy", None), ("origin", Some("blob:none"))]);
+
+        assert_eq!(missing_promised_object(&repo, &[oid]).unwrap(), None);
+        let missing = missing_promised_object(&repo, &[oid, blob]).unwrap().unwrap();
+        assert_eq!(
+            (missing.oid.as_str(), missing.remote.as_str()),
+            (hex.as_str(), "legacy")
+        );
+
+        let _ = fs::remove_dir_all(repo.workdir().unwrap());
+    }
+}
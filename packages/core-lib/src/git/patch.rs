@@ -0,0 +1,769 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use git2::{
+    ApplyLocation, ApplyOptions, Commit, Diff, Email, EmailCreateOptions, ErrorCode, Oid, Repository, Signature, Time,
+    Tree,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::commit::{self, advance_head, CommitContent, CommitError, CommitIdentity};
+use super::commit_details::Person;
+use super::signature::GpgPrograms;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct PatchExportOptions {
+    /// Replaces `PATCH` in the subject prefix, e.g. `RFC PATCH`.
+    pub subject_prefix: Option<String>,
+    /// Version of the series, giving `[PATCH v2 1/3]` subjects and `v2-` file names.
+    pub reroll: Option<u32>,
+    /// Write one mbox file instead of one numbered file per commit.
+    pub single_file: bool,
+}
+
+/// One commit formatted as an email, like `git format-patch` output.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct FormattedPatch {
+    pub oid: String,
+    pub summary: String,
+    /// Suggested file name, e.g. `0001-fix-the-thing.patch`.
+    pub file_name: String,
+    pub content: String,
+}
+
+/// A patch read from a patch file or an mbox series.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct MailPatch {
+    pub summary: String,
+    /// The full commit message, summary included.
+    pub message: String,
+    /// `None` for plain diffs without mail headers, which cannot be applied as commits.
+    pub author: Option<Person>,
+    pub diff: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum PatchTarget {
+    /// Change the files only, like `git apply`.
+    Worktree,
+    /// Change the index only, like `git apply --cached`.
+    Index,
+    /// Change both, like `git apply --index`.
+    Both,
+    /// Commit each patch with its author and message, like `git am`.
+    Commit,
+}
+
+/// Part of a patch that does not apply.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct PatchConflict {
+    pub path: PathBuf,
+    /// Header of the failing hunk, e.g. `@@ -10,6 +10,7 @@`. `None` when the file itself cannot be patched because
+    /// it is missing, already exists or differs in binary content.
+    pub hunk: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct PatchCheck {
+    /// Position of the patch in the series.
+    pub index: usize,
+    pub summary: String,
+    pub applies: bool,
+    pub conflicts: Vec<PatchConflict>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct AppliedPatch {
+    pub summary: String,
+    /// The created commit, when applied as a commit.
+    pub oid: Option<String>,
+}
+
+/// Outcome of [`apply_patches`]. Like `git am`, applying stops at the first patch that does not apply.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct ApplyOutcome {
+    pub applied: Vec<AppliedPatch>,
+    pub failed: Option<PatchCheck>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("No commits in {0}")]
+    EmptyRange(String),
+    #[error("No patches found")]
+    NoPatches,
+    #[error("Patch {0} has no author, it can only be applied to the working tree or index")]
+    NoAuthor(usize),
+    #[error("The index has staged changes, commit or unstage them first")]
+    DirtyIndex,
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Commit failed: {0}")]
+    Commit(#[from] CommitError),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Formats the commits of `range` as emails, oldest first.
+///
+/// `range` is either `from..to`, or a single revision to format just that commit. Merge commits are skipped, like
+/// `git format-patch` does.
+pub fn format_patches(
+    repo: &Repository,
+    range: &str,
+    options: &PatchExportOptions,
+) -> Result<Vec<FormattedPatch>, PatchError> {
+    let commits = range_commits(repo, range)?;
+    if commits.is_empty() {
+        return Err(PatchError::EmptyRange(range.to_string()));
+    }
+
+    let mut email_options = EmailCreateOptions::new();
+    if let Some(prefix) = &options.subject_prefix {
+        email_options.subject_prefix(prefix.as_str());
+    }
+    if let Some(reroll) = options.reroll {
+        email_options.reroll_number(reroll as usize);
+    }
+    let count = commits.len();
+    commits
+        .iter()
+        .enumerate()
+        .map(|(i, commit)| {
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(e) if e.code() == ErrorCode::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+            let summary = commit.summary().unwrap_or_default();
+            let body = commit.body().unwrap_or_default();
+            let email = Email::from_diff(
+                &diff,
+                i + 1,
+                count,
+                &commit.id(),
+                summary,
+                body,
+                &commit.author(),
+                &mut email_options,
+            )?;
+            let version = options.reroll.map(|v| format!("v{}-", v)).unwrap_or_default();
+            Ok(FormattedPatch {
+                oid: commit.id().to_string(),
+                summary: summary.to_string(),
+                file_name: format!("{}{:04}-{}.patch", version, i + 1, file_slug(summary)),
+                content: String::from_utf8_lossy(email.as_slice()).into_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Writes the patches of `range` to `out`: a single mbox file with `single_file`, numbered files in the directory
+/// `out` otherwise. Returns the written files.
+pub fn export_patches(
+    repo: &Repository,
+    range: &str,
+    options: &PatchExportOptions,
+    out: &Path,
+) -> Result<Vec<PathBuf>, PatchError> {
+    let patches = format_patches(repo, range, options)?;
+    if options.single_file {
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mbox: String = patches.iter().map(|patch| patch.content.as_str()).collect();
+        fs::write(out, mbox)?;
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    fs::create_dir_all(out)?;
+    patches
+        .iter()
+        .map(|patch| {
+            let path = out.join(&patch.file_name);
+            fs::write(&path, &patch.content)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Reads patch files in the given order, each holding a plain diff, a single email or an mbox series.
+pub fn read_patches(paths: &[PathBuf]) -> Result<Vec<MailPatch>, PatchError> {
+    let mut patches = Vec::new();
+    for path in paths {
+        patches.extend(parse_patches(&String::from_utf8_lossy(&fs::read(path)?)));
+    }
+    if patches.is_empty() {
+        return Err(PatchError::NoPatches);
+    }
+    Ok(patches)
+}
+
+/// Splits `content` into patches: one per email of an mbox, or a single one for a plain diff.
+pub fn parse_patches(content: &str) -> Vec<MailPatch> {
+    let mut messages: Vec<String> = Vec::new();
+    for line in content.split_inclusive('\n') {
+        if is_mbox_separator(line) || messages.is_empty() {
+            messages.push(String::new());
+        }
+        if !is_mbox_separator(line) {
+            messages.last_mut().unwrap().push_str(line);
+        }
+    }
+    messages.iter().filter_map(|message| parse_message(message)).collect()
+}
+
+/// Checks which patches and hunks would fail without changing anything.
+///
+/// Patches of a series are checked on top of each other, up to and including the first that does not apply. For the
+/// working tree each patch is checked on its own, since the working tree cannot be patched in memory.
+pub fn check_patches(
+    repo: &Repository,
+    patches: &[MailPatch],
+    target: PatchTarget,
+) -> Result<Vec<PatchCheck>, PatchError> {
+    let mut base = match target {
+        PatchTarget::Worktree => None,
+        PatchTarget::Index | PatchTarget::Both => Some(repo.find_tree(repo.index()?.write_tree()?)?),
+        PatchTarget::Commit => Some(head_tree(repo)?),
+    };
+
+    let mut checks = Vec::new();
+    for (index, patch) in patches.iter().enumerate() {
+        let diff = Diff::from_buffer(patch.diff.as_bytes())?;
+        let result = match &base {
+            Some(tree) => repo
+                .apply_to_tree(tree, &diff, None)
+                .and_then(|mut index| index.write_tree_to(repo))
+                .map(Some),
+            None => repo
+                .apply(&diff, ApplyLocation::WorkDir, Some(ApplyOptions::new().check(true)))
+                .map(|_| None),
+        };
+        let check = match result {
+            Ok(tree) => {
+                if let Some(tree) = tree {
+                    base = Some(repo.find_tree(tree)?);
+                }
+                PatchCheck {
+                    index,
+                    summary: patch.summary.clone(),
+                    applies: true,
+                    conflicts: Vec::new(),
+                }
+            }
+            Err(_) => PatchCheck {
+                index,
+                summary: patch.summary.clone(),
+                applies: false,
+                conflicts: conflicts(repo, &diff, base.as_ref())?,
+            },
+        };
+        let applies = check.applies;
+        checks.push(check);
+        if !applies {
+            break;
+        }
+    }
+    Ok(checks)
+}
+
+/// Applies the patches in order to `target`, stopping at the first one that does not apply.
+///
+/// Commits are made by `identity` when given, otherwise by the configured user, and signed per `commit.gpgsign`.
+pub fn apply_patches(
+    repo: &Repository,
+    patches: &[MailPatch],
+    target: PatchTarget,
+    identity: Option<&CommitIdentity>,
+    programs: &GpgPrograms,
+) -> Result<ApplyOutcome, PatchError> {
+    if target == PatchTarget::Commit {
+        if let Some(index) = patches.iter().position(|patch| patch.author.is_none()) {
+            return Err(PatchError::NoAuthor(index));
+        }
+        let staged = repo.diff_tree_to_index(Some(&head_tree(repo)?), None, None)?;
+        if staged.deltas().len() > 0 {
+            return Err(PatchError::DirtyIndex);
+        }
+    }
+
+    let location = match target {
+        PatchTarget::Worktree => ApplyLocation::WorkDir,
+        PatchTarget::Index => ApplyLocation::Index,
+        PatchTarget::Both | PatchTarget::Commit => ApplyLocation::Both,
+    };
+    let mut applied = Vec::new();
+    for (index, patch) in patches.iter().enumerate() {
+        let diff = Diff::from_buffer(patch.diff.as_bytes())?;
+        if repo.apply(&diff, location, None).is_err() {
+            let base = match target {
+                PatchTarget::Worktree => None,
+                _ => Some(repo.find_tree(repo.index()?.write_tree()?)?),
+            };
+            let failed = PatchCheck {
+                index,
+                summary: patch.summary.clone(),
+                applies: false,
+                conflicts: conflicts(repo, &diff, base.as_ref())?,
+            };
+            return Ok(ApplyOutcome {
+                applied,
+                failed: Some(failed),
+            });
+        }
+        let oid = match (target, &patch.author) {
+            (PatchTarget::Commit, Some(author)) => {
+                Some(commit_patch(repo, patch, author, identity, programs)?.to_string())
+            }
+            _ => None,
+        };
+        applied.push(AppliedPatch {
+            summary: patch.summary.clone(),
+            oid,
+        });
+    }
+    Ok(ApplyOutcome { applied, failed: None })
+}
+
+fn commit_patch(
+    repo: &Repository,
+    patch: &MailPatch,
+    author: &Person,
+    identity: Option<&CommitIdentity>,
+    programs: &GpgPrograms,
+) -> Result<Oid, CommitError> {
+    let author = Signature::new(
+        &author.name,
+        &author.email,
+        &Time::new(author.time, author.offset_minutes),
+    )?;
+    let committer = commit::committer(repo, identity)?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e.into()),
+    };
+    let parents: Vec<_> = parent.iter().collect();
+    let content = CommitContent {
+        author: &author,
+        committer: &committer,
+        message: &patch.message,
+        tree: &tree,
+        parents: &parents,
+    };
+    let oid = commit::write_commit(repo, &content, None, identity, programs)?;
+    let reflog = format!("am: {}", patch.summary);
+    advance_head(repo, oid, parent.as_ref().map(Commit::id), &reflog)?;
+    Ok(oid)
+}
+
+/// Finds the files and hunks of `diff` that do not apply, trying each on its own.
+fn conflicts(repo: &Repository, diff: &Diff, base: Option<&Tree>) -> Result<Vec<PatchConflict>, git2::Error> {
+    let try_apply = |delta: usize, hunk: Option<usize>| {
+        let mut current_delta = 0;
+        let mut current_hunk = 0;
+        let mut options = ApplyOptions::new();
+        options.delta_callback(|_| {
+            current_delta += 1;
+            current_delta == delta + 1
+        });
+        // Hunks of skipped files are not reported, so the count restarts with every file.
+        options.hunk_callback(|_| {
+            current_hunk += 1;
+            hunk == Some(current_hunk - 1)
+        });
+        match base {
+            Some(tree) => repo.apply_to_tree(tree, diff, Some(&mut options)).is_ok(),
+            None => repo.apply(diff, ApplyLocation::WorkDir, Some(options.check(true))).is_ok(),
+        }
+    };
+
+    let mut conflicts = Vec::new();
+    for (delta_index, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path()
+            .or(delta.old_file().path())
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        let patch = git2::Patch::from_diff(diff, delta_index)?;
+        if !try_apply(delta_index, None) {
+            conflicts.push(PatchConflict { path, hunk: None });
+        } else if let Some(patch) = &patch {
+            for hunk in 0..patch.num_hunks() {
+                if !try_apply(delta_index, Some(hunk)) {
+                    let (header, _) = patch.hunk(hunk)?;
+                    conflicts.push(PatchConflict {
+                        path: path.clone(),
+                        hunk: Some(String::from_utf8_lossy(header.header()).trim_end().to_string()),
+                    });
+                }
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+fn head_tree(repo: &Repository) -> Result<Tree<'_>, git2::Error> {
+    match repo.head() {
+        Ok(head) => head.peel_to_tree(),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => repo.find_tree(repo.treebuilder(None)?.write()?),
+        Err(e) => Err(e),
+    }
+}
+
+fn range_commits<'r>(repo: &'r Repository, range: &str) -> Result<Vec<Commit<'r>>, git2::Error> {
+    let spec = repo.revparse(range)?;
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    match (spec.from(), spec.to()) {
+        (Some(from), Some(to)) => {
+            walk.push(to.id())?;
+            walk.hide(from.id())?;
+        }
+        (Some(single), None) => return Ok(vec![single.peel_to_commit()?]),
+        _ => return Err(git2::Error::from_str("invalid revision range")),
+    }
+    walk.map(|oid| repo.find_commit(oid?))
+        .filter(|commit| !matches!(commit, Ok(commit) if commit.parent_count() > 1))
+        .collect()
+}
+
+/// Turns a summary into a file name part the way `git format-patch` does.
+fn file_slug(summary: &str) -> String {
+    let mut slug = String::new();
+    for c in summary.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches(|c| c == '-' || c == '.');
+    slug.chars().take(52).collect::<String>().trim_end_matches('-').to_string()
+}
+
+/// Mbox messages start with `From <oid or address> Mon Sep 17 00:00:00 2001`, unlike the `From:` header or a body
+/// line starting with "From".
+fn is_mbox_separator(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    words.len() >= 7
+        && words[0] == "From"
+        && words.iter().any(|word| word.matches(':').count() == 2)
+        && words[words.len() - 1].len() == 4
+        && words[words.len() - 1].bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse_message(message: &str) -> Option<MailPatch> {
+    let (headers, body) = match message.split_once("\n\n") {
+        Some((headers, body)) if looks_like_headers(headers) => (headers, body),
+        _ => ("", message),
+    };
+    let diff_start = ["diff --git ", "--- "]
+        .iter()
+        .filter_map(|start| line_start(body, start))
+        .min()?;
+    let diff = strip_signature(&body[diff_start..]);
+
+    let mut subject = String::new();
+    let mut from = None;
+    let mut date = None;
+    for header in unfold(headers) {
+        match header.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("subject") => subject = decode_words(value.trim()),
+            Some((name, value)) if name.eq_ignore_ascii_case("from") => from = Some(decode_words(value.trim())),
+            Some((name, value)) if name.eq_ignore_ascii_case("date") => date = parse_date(value.trim()),
+            _ => {}
+        }
+    }
+    let summary = strip_subject_prefix(&subject).to_string();
+
+    // The message body ends at the `---` line before the diffstat.
+    let text = &body[..diff_start];
+    let text = line_start(text, "---\n").map_or(text, |end| &text[..end]);
+    let text = text.trim();
+    let message = match (summary.is_empty(), text.is_empty()) {
+        (true, _) => String::new(),
+        (false, true) => format!("{}\n", summary),
+        (false, false) => format!("{}\n\n{}\n", summary, text),
+    };
+
+    let author = from.map(|from| {
+        let (name, email) = match from.rsplit_once('<') {
+            Some((name, email)) => (name.trim().trim_matches('"'), email.trim_end_matches('>')),
+            None => ("", from.as_str()),
+        };
+        let (time, offset_minutes) = date.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            (now.as_secs() as i64, 0)
+        });
+        Person {
+            name: name.to_string(),
+            email: email.trim().to_string(),
+            time,
+            offset_minutes,
+        }
+    });
+    Some(MailPatch {
+        summary,
+        message,
+        author,
+        diff: diff.to_string(),
+    })
+}
+
+fn looks_like_headers(block: &str) -> bool {
+    block.lines().next().is_some_and(|line| {
+        line.split_once(':')
+            .is_some_and(|(name, _)| !name.is_empty() && !name.contains(' '))
+    })
+}
+
+/// Byte offset of the first line of `text` starting with `prefix`.
+fn line_start(text: &str, prefix: &str) -> Option<usize> {
+    if text.starts_with(prefix) {
+        return Some(0);
+    }
+    text.find(&format!("\n{}", prefix)).map(|pos| pos + 1)
+}
+
+/// Drops the `-- ` signature with the git version that ends format-patch output.
+fn strip_signature(diff: &str) -> &str {
+    for marker in ["\n-- \n", "\n--\n"] {
+        if let Some(pos) = diff.rfind(marker) {
+            let rest = &diff[pos + marker.len()..];
+            let mut lines = rest.lines().filter(|line| !line.trim().is_empty());
+            let version = lines.next().is_some_and(|line| !line.starts_with(['+', '-', ' ', '@', '\\']));
+            if version && lines.next().is_none() {
+                return &diff[..pos + 1];
+            }
+        }
+    }
+    diff
+}
+
+/// Joins folded header lines.
+fn unfold(headers: &str) -> Vec<String> {
+    let mut unfolded: Vec<String> = Vec::new();
+    for line in headers.lines() {
+        match unfolded.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => unfolded.push(line.to_string()),
+        }
+    }
+    unfolded
+}
+
+/// Removes `[PATCH v2 1/3]` style prefixes and `Re:` from a subject.
+fn strip_subject_prefix(subject: &str) -> &str {
+    let mut subject = subject.trim();
+    loop {
+        if let Some(rest) = subject.strip_prefix('[') {
+            match rest.split_once(']') {
+                Some((_, rest)) => subject = rest.trim_start(),
+                None => return subject,
+            }
+        } else if subject.len() >= 3 && subject[..3].eq_ignore_ascii_case("re:") {
+            subject = subject[3..].trim_start();
+        } else {
+            return subject;
+        }
+    }
+}
+
+/// Decodes RFC 2047 `=?UTF-8?q?...?=` words as written by git for non-ASCII names and subjects.
+fn decode_words(value: &str) -> String {
+    let mut decoded = Vec::new();
+    let mut rest = value;
+    let mut previous_encoded = false;
+    while let Some(start) = rest.find("=?") {
+        let encoded = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+        let [_charset, encoding, tail] = encoded[..] else { break };
+        let Some(end) = tail.find("?=") else { break };
+        if !encoding.eq_ignore_ascii_case("q") {
+            break;
+        }
+        // Whitespace between two encoded words is not part of the text.
+        let between = &rest[..start];
+        if !(previous_encoded && between.trim().is_empty()) {
+            decoded.extend_from_slice(between.as_bytes());
+        }
+        let mut bytes = tail[..end].bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'_' => decoded.push(b' '),
+                b'=' => {
+                    let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                    decoded.extend(u8::from_str_radix(&hex, 16).ok());
+                }
+                b => decoded.push(b),
+            }
+        }
+        rest = &tail[end + 2..];
+        previous_encoded = true;
+    }
+    decoded.extend_from_slice(rest.as_bytes());
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parses an RFC 2822 date such as `Tue, 5 Mar 2024 10:15:00 +0100` into seconds and offset minutes.
+fn parse_date(value: &str) -> Option<(i64, i32)> {
+    let value = value.split_once(',').map_or(value, |(_, date)| date);
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [day, month, year, time, zone, ..] = parts[..] else {
+        return None;
+    };
+    let month = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|m| month.to_ascii_lowercase().starts_with(m))? as i64
+        + 1;
+    let (day, year): (i64, i64) = (day.parse().ok()?, year.parse().ok()?);
+    let mut clock = time.split(':').map(|part| part.parse::<i64>());
+    let (hours, minutes) = (clock.next()?.ok()?, clock.next()?.ok()?);
+    let seconds = clock.next().transpose().ok()?.unwrap_or(0);
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let zone: i32 = zone.trim_start_matches(['+', '-']).parse().ok()?;
+    let offset_minutes = sign * (zone / 100 * 60 + zone % 100);
+
+    // Days since the epoch of a proleptic Gregorian date, from Howard Hinnant's days_from_civil.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let local = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some((local - offset_minutes as i64 * 60, offset_minutes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, commit_files_as, init_repo};
+
+    #[test]
+    fn test_parse_mail_headers() {
+        assert_eq!(parse_date("Tue, 5 Mar 2024 10:15:00 +0100"), Some((1709630100, 60)));
+        assert_eq!(parse_date("Thu, 1 Jan 1970 00:00:00 -0030"), Some((1800, -30)));
+        assert_eq!(strip_subject_prefix("[PATCH v2 1/3] Fix the thing"), "Fix the thing");
+        assert_eq!(decode_words("=?UTF-8?q?J=C3=B6rg?= Meier"), "Jörg Meier");
+        assert_eq!(file_slug("Fix: the [thing] in foo.rs"), "Fix-the-thing-in-foo.rs");
+    }
+
+    #[test]
+    fn test_export_and_apply_series() {
+        let temp_dir = std::env::temp_dir().join("gitultra_patch_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let repo = Repository::init(temp_dir.join("source")).unwrap();
+        let signature = Signature::new("Jörg", "jorg@example.com", &Time::new(1709630100, 60)).unwrap();
+        for (i, content) in ["one\n", "one\ntwo\n", "one\ntwo\nthree\n"].iter().enumerate() {
+            let message = format!("Change {}\n\nBody of change {}.\n", i, i);
+            commit_files_as(&repo, &signature, &signature, &[("file.txt", content)], &message);
+        }
+
+        let options = PatchExportOptions {
+            single_file: true,
+            ..Default::default()
+        };
+        let mbox = temp_dir.join("series.mbox");
+        export_patches(&repo, "HEAD~2..HEAD", &options, &mbox).unwrap();
+        let patches = read_patches(&[mbox]).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].summary, "Change 1");
+
+        // Applying the series on a copy of the first commit reproduces the history.
+        let target = Repository::init(temp_dir.join("target")).unwrap();
+        let mut config = target.config().unwrap();
+        config.set_str("user.name", "Committer").unwrap();
+        config.set_str("user.email", "committer@example.com").unwrap();
+        commit_files_as(&target, &signature, &signature, &[("file.txt", "one\n")], "Change 0\n");
+
+        let checks = check_patches(&target, &patches, PatchTarget::Commit).unwrap();
+        assert!(checks.iter().all(|check| check.applies));
+        let identity = CommitIdentity {
+            name: "Work".to_string(),
+            email: "work@example.com".to_string(),
+            signing_key: None,
+        };
+        let programs = GpgPrograms::default();
+        let outcome = apply_patches(&target, &patches, PatchTarget::Commit, Some(&identity), &programs).unwrap();
+        assert!(outcome.failed.is_none());
+        let head = target.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.tree_id(), repo.head().unwrap().peel_to_tree().unwrap().id());
+        assert_eq!(head.message(), Some("Change 2\n\nBody of change 2.\n"));
+        assert_eq!(head.author().name(), Some("Jörg"));
+        assert_eq!(head.author().when().seconds(), 1709630100);
+        assert_eq!(head.committer().name(), Some("Work"));
+
+        // The series no longer applies once the file was rewritten.
+        fs::write(temp_dir.join("target/file.txt"), "uno\n").unwrap();
+        let checks = check_patches(&target, &patches, PatchTarget::Worktree).unwrap();
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].applies);
+        assert_eq!(
+            checks[0].conflicts,
+            vec![PatchConflict {
+                path: PathBuf::from("file.txt"),
+                hunk: Some("@@ -1 +1,2 @@".to_string()),
+            }]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_signs_commits() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let repo = init_repo("patch_sign");
+        let temp_dir = repo.workdir().unwrap().to_path_buf();
+        commit_files(&repo, &[("file.txt", "one\n")], "Change\n");
+        let head = commit_files(&repo, &[("file.txt", "one\ntwo\n")], "Change\n");
+        let patches = parse_patches(&format_patches(&repo, "HEAD", &PatchExportOptions::default()).unwrap()[0].content);
+        let base = repo.find_commit(head).unwrap().parent(0).unwrap();
+        repo.reset(base.as_object(), git2::ResetType::Hard, None).unwrap();
+
+        // Stands in for gpg: reports success and prints a fixed signature.
+        let program = temp_dir.join(".git/fake-gpg");
+        fs::write(
+            &program,
+            "#!/bin/sh\ncat > /dev/null\necho '[GNUPG:] SIG_CREATED D' >&2\n\
+             printf -- '-----BEGIN PGP SIGNATURE-----\\nsig\\n-----END PGP SIGNATURE-----\\n'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        config.set_bool("commit.gpgsign", true).unwrap();
+        let programs = GpgPrograms {
+            openpgp: Some(program.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+
+        let outcome = apply_patches(&repo, &patches, PatchTarget::Commit, None, &programs).unwrap();
+        let oid = Oid::from_str(outcome.applied[0].oid.as_deref().unwrap()).unwrap();
+        let (gpg_signature, _) = repo.extract_signature(&oid, None).unwrap();
+        assert_eq!(
+            gpg_signature.as_str(),
+            Some("-----BEGIN PGP SIGNATURE-----\nsig\n-----END PGP SIGNATURE-----\n")
+        );
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use git2::{ObjectType, Oid, Repository, ResetType};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::commit_details::Person;
+use super::sparse;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct ReflogEntry {
+    /// Position in the reflog, 0 being the newest entry, as in `HEAD@{0}`.
+    pub index: usize,
+    pub old_oid: String,
+    pub new_oid: String,
+    pub committer: Person,
+    pub message: Option<String>,
+}
+
+/// A commit that no ref reaches anymore, e.g. the old tip of a rebased or deleted branch.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct LostCommit {
+    pub oid: String,
+    pub summary: String,
+    pub author: Person,
+    pub committer: Person,
+    /// Whether a reflog still mentions the commit. Commits only kept alive by the object database are lost
+    /// for good on the next `git gc`.
+    pub in_reflog: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum ResetMode {
+    /// Only moves the branch, keeping index and working tree.
+    Soft,
+    /// Moves the branch and resets the index, keeping the working tree.
+    Mixed,
+    /// Moves the branch and discards all changes to tracked files.
+    Hard,
+}
+
+impl From<ResetMode> for ResetType {
+    fn from(mode: ResetMode) -> Self {
+        match mode {
+            ResetMode::Soft => ResetType::Soft,
+            ResetMode::Mixed => ResetType::Mixed,
+            ResetMode::Hard => ResetType::Hard,
+        }
+    }
+}
+
+/// Lists the reflog of `name`, newest entry first.
+///
+/// `name` is `HEAD`, a full ref name, or anything git resolves as a short name (`main`, `origin/main`).
+pub fn reflog(repo: &Repository, name: &str) -> Result<Vec<ReflogEntry>, git2::Error> {
+    let reflog = repo.reflog(&full_refname(repo, name)?)?;
+    Ok(reflog
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| ReflogEntry {
+            index,
+            old_oid: entry.id_old().to_string(),
+            new_oid: entry.id_new().to_string(),
+            committer: Person::from(&entry.committer()),
+            message: entry.message().map(str::to_string),
+        })
+        .collect())
+}
+
+/// Creates the branch `branch` at the commit recorded by entry `index` of the reflog of `name`.
+pub fn create_branch_from_entry(repo: &Repository, name: &str, index: usize, branch: &str) -> Result<Oid, git2::Error> {
+    let commit = repo.find_commit(entry_oid(repo, name, index)?)?;
+    repo.branch(branch, &commit, false)?;
+    Ok(commit.id())
+}
+
+/// Resets the current branch (or detached HEAD) to the commit recorded by entry `index` of the reflog of `name`,
+/// like `git reset <mode> name@{index}`.
+pub fn reset_to_entry(repo: &Repository, name: &str, index: usize, mode: ResetMode) -> Result<Oid, git2::Error> {
+    let target = repo.find_object(entry_oid(repo, name, index)?, Some(ObjectType::Commit))?;
+    repo.reset(&target, mode.into(), None)?;
+    if mode != ResetMode::Soft {
+        sparse::restore_after_checkout(repo)?;
+    }
+    Ok(target.id())
+}
+
+/// Finds commits in the object database that no ref, HEAD or worktree HEAD reaches, newest first.
+///
+/// Only the tips of lost histories are returned: recovering a tip with a branch recovers its ancestors too.
+pub fn lost_commits(repo: &Repository) -> Result<Vec<LostCommit>, git2::Error> {
+    let odb = repo.odb()?;
+    let mut commits = Vec::new();
+    odb.foreach(|oid| {
+        if let Ok((_, ObjectType::Commit)) = odb.read_header(*oid) {
+            commits.push(*oid);
+        }
+        true
+    })?;
+
+    let reachable = reachable_commits(repo)?;
+    let unreachable: HashSet<Oid> = commits.into_iter().filter(|oid| !reachable.contains(oid)).collect();
+    let mut tips = Vec::new();
+    let mut has_child = HashSet::new();
+    for oid in &unreachable {
+        let commit = repo.find_commit(*oid)?;
+        has_child.extend(commit.parent_ids().filter(|parent| unreachable.contains(parent)));
+        tips.push(commit);
+    }
+    tips.retain(|commit| !has_child.contains(&commit.id()));
+    tips.sort_by_key(|commit| std::cmp::Reverse(commit.committer().when().seconds()));
+
+    let logged = reflog_oids(repo)?;
+    Ok(tips
+        .iter()
+        .map(|commit| LostCommit {
+            oid: commit.id().to_string(),
+            summary: commit.summary().unwrap_or("").to_string(),
+            author: Person::from(&commit.author()),
+            committer: Person::from(&commit.committer()),
+            in_reflog: logged.contains(&commit.id()),
+        })
+        .collect())
+}
+
+fn full_refname(repo: &Repository, name: &str) -> Result<String, git2::Error> {
+    if name == "HEAD" || name.starts_with("refs/") {
+        return Ok(name.to_string());
+    }
+    let reference = repo.resolve_reference_from_short_name(name)?;
+    reference
+        .name()
+        .map(str::to_string)
+        .ok_or_else(|| git2::Error::from_str("ref name is not valid utf-8"))
+}
+
+fn entry_oid(repo: &Repository, name: &str, index: usize) -> Result<Oid, git2::Error> {
+    let reflog = repo.reflog(&full_refname(repo, name)?)?;
+    reflog
+        .get(index)
+        .map(|entry| entry.id_new())
+        .ok_or_else(|| git2::Error::from_str(&format!("{}@{{{}}} does not exist", name, index)))
+}
+
+fn reachable_commits(repo: &Repository) -> Result<HashSet<Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push_glob("*")?;
+    if let Ok(head) = repo.head().and_then(|head| head.peel_to_commit()) {
+        walk.push(head.id())?;
+    }
+    // Linked worktrees have their own HEAD, which may be detached on a commit no branch contains.
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = repo.find_worktree(name).and_then(|wt| Repository::open_from_worktree(&wt));
+        if let Ok(head) = worktree.and_then(|wt| wt.head()?.peel_to_commit().map(|commit| commit.id())) {
+            walk.push(head)?;
+        }
+    }
+    walk.collect()
+}
+
+fn reflog_oids(repo: &Repository) -> Result<HashSet<Oid>, git2::Error> {
+    let mut names = vec!["HEAD".to_string()];
+    for reference in repo.references()? {
+        names.extend(reference?.name().map(str::to_string));
+    }
+    let mut oids = HashSet::new();
+    for name in names {
+        if let Ok(reflog) = repo.reflog(&name) {
+            oids.extend(reflog.iter().map(|entry| entry.id_new()));
+        }
+    }
+    Ok(oids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+
+    #[test]
+    fn test_lost_commits_reports_tips_of_dropped_history() {
+        let repo = init_repo("reflog");
+
+        let base = commit_files(&repo, &[], "base");
+        commit_files(&repo, &[], "dropped 1");
+        let dropped = commit_files(&repo, &[], "dropped 2");
+        repo.reset(&repo.find_object(base, None).unwrap(), ResetType::Soft, None)
+            .unwrap();
+
+        let lost = lost_commits(&repo).unwrap();
+        assert_eq!(lost.len(), 1);
+        assert_eq!(lost[0].oid, dropped.to_string());
+        assert!(lost[0].in_reflog);
+
+        let entries = reflog(&repo, "HEAD").unwrap();
+        assert_eq!(entries[0].new_oid, base.to_string());
+        create_branch_from_entry(&repo, "HEAD", 1, "rescued").unwrap();
+        assert!(lost_commits(&repo).unwrap().is_empty());
+
+        fs::remove_dir_all(repo.workdir().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,470 @@
+use std::path::{Path, PathBuf};
+
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{
+    AutotagOption, Config, Cred, CredentialType, ErrorClass, ErrorCode, FetchOptions, Oid, PushOptions,
+    RemoteCallbacks, Repository,
+};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::bundle::{self, BundleError};
+use super::hooks::{self, HookError, HookStream, PushUpdate};
+use super::sparse;
+
+/// Transfer progress of a running fetch.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct FetchProgress {
+    pub remote: String,
+    pub total_objects: u32,
+    pub received_objects: u32,
+    pub indexed_objects: u32,
+    pub received_bytes: u64,
+}
+
+/// A remote ref that changed during a fetch.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct UpdatedRef {
+    pub name: String,
+    /// `None` when the ref did not exist before the fetch.
+    pub old_oid: Option<String>,
+    pub new_oid: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct FetchSummary {
+    pub remote: String,
+    pub updated_refs: Vec<UpdatedRef>,
+    pub received_objects: u32,
+    pub received_bytes: u64,
+}
+
+/// A ref the remote was asked to update during a push.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct PushedRef {
+    pub remote_ref: String,
+    /// Why the remote refused the update, `None` when it was accepted.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushError {
+    #[error(transparent)]
+    Hook(HookError),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+#[serde(tag = "kind")]
+pub enum PullOutcome {
+    UpToDate,
+    FastForward { old_oid: String, new_oid: String },
+}
+
+/// Builds remote callbacks that authenticate through the ssh-agent, git credential helpers and default credentials.
+///
+/// `config` is used to look up credential helpers, usually the repository config.
+pub fn remote_callbacks<'a>(config: Option<Config>) -> RemoteCallbacks<'a> {
+    let mut attempts = 0;
+    let ssh_key = config.as_ref().and_then(ssh_command_key);
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        // libgit2 keeps asking as long as we return credentials, so give up after a few rounds.
+        attempts += 1;
+        if attempts > 3 {
+            return Err(git2::Error::new(
+                ErrorCode::Auth,
+                ErrorClass::Net,
+                "authentication failed",
+            ));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if let Some(username) = username {
+                // Try the key picked in core.sshCommand first, the agent may hold keys of other accounts.
+                if let (Some(key), 1) = (&ssh_key, attempts) {
+                    return Cred::ssh_key(username, None, key, None);
+                }
+                return Cred::ssh_key_from_agent(username);
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(config) = &config {
+                if let Ok(cred) = Cred::credential_helper(config, url, username) {
+                    return Ok(cred);
+                }
+            }
+        }
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username.unwrap_or("git"));
+        }
+        Cred::default()
+    });
+    callbacks
+}
+
+/// The private key passed with `-i` in `core.sshCommand`, which libgit2 does not run itself.
+fn ssh_command_key(config: &Config) -> Option<PathBuf> {
+    let command = config.get_string("core.sshCommand").ok()?;
+    let mut args = shell_words(&command).into_iter();
+    while let Some(arg) = args.next() {
+        let key = match arg.strip_prefix("-i") {
+            Some("") => args.next()?,
+            Some(key) => key.to_string(),
+            None => continue,
+        };
+        return Some(match key.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+            None => PathBuf::from(key),
+        });
+    }
+    None
+}
+
+/// Splits a command line on whitespace, honoring single and double quotes.
+fn shell_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Fetches `remote_name` using its configured refspecs.
+///
+/// Remotes whose URL is a bundle file are read with [`bundle::fetch_bundle`], as libgit2 cannot fetch from bundles.
+/// `on_progress` is called while objects are transferred; returning `false` cancels the fetch.
+pub fn fetch_remote(
+    repo: &Repository,
+    remote_name: &str,
+    mut on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<FetchSummary, git2::Error> {
+    let mut remote = repo.find_remote(remote_name)?;
+    if let Some(path) = remote.url().and_then(bundle_path) {
+        let mut refspecs: Vec<String> = remote.fetch_refspecs()?.iter().flatten().map(str::to_string).collect();
+        refspecs.push("refs/tags/*:refs/tags/*".to_string());
+        return bundle::fetch_bundle(repo, &path, remote_name, &refspecs, on_progress).map_err(|e| match e {
+            BundleError::Git(e) => e,
+            e => git2::Error::from_str(&e.to_string()),
+        });
+    }
+    let mut updated_refs = Vec::new();
+
+    {
+        let mut callbacks = remote_callbacks(repo.config().ok());
+        callbacks.transfer_progress(|stats| {
+            on_progress(FetchProgress {
+                remote: remote_name.to_string(),
+                total_objects: stats.total_objects() as u32,
+                received_objects: stats.received_objects() as u32,
+                indexed_objects: stats.indexed_objects() as u32,
+                received_bytes: stats.received_bytes() as u64,
+            })
+        });
+        callbacks.update_tips(|name, old, new| {
+            debug!("{}: {} -> {}", name, old, new);
+            updated_refs.push(UpdatedRef {
+                name: name.to_string(),
+                old_oid: (!old.is_zero()).then(|| old.to_string()),
+                new_oid: new.to_string(),
+            });
+            true
+        });
+
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(callbacks);
+        options.download_tags(AutotagOption::Auto);
+        remote.fetch(&[] as &[&str], Some(&mut options), None)?;
+    }
+
+    let stats = remote.stats();
+    info!(
+        "Fetched {}: {} objects, {} refs updated",
+        remote_name,
+        stats.received_objects(),
+        updated_refs.len()
+    );
+
+    Ok(FetchSummary {
+        remote: remote_name.to_string(),
+        updated_refs,
+        received_objects: stats.received_objects() as u32,
+        received_bytes: stats.received_bytes() as u64,
+    })
+}
+
+/// Pushes `refspecs` to `remote_name`, like `git push <remote> <refspecs>`.
+///
+/// The `pre-push` hook gets the ref updates negotiated with the remote and can refuse the whole push, unless
+/// `no_verify` skips it; its output goes to `on_hook_output`. Refs the remote refuses are reported, not failed.
+pub fn push_remote(
+    repo: &Repository,
+    remote_name: &str,
+    refspecs: &[String],
+    no_verify: bool,
+    mut on_hook_output: impl FnMut(HookStream, &str),
+) -> Result<Vec<PushedRef>, PushError> {
+    let mut remote = repo.find_remote(remote_name)?;
+    let url = remote.pushurl().or(remote.url()).unwrap_or_default().to_string();
+    let mut hook_error = None;
+    let mut pushed = Vec::new();
+    let result = {
+        let mut callbacks = remote_callbacks(repo.config().ok());
+        callbacks.push_negotiation(|updates| {
+            if no_verify {
+                return Ok(());
+            }
+            let updates: Vec<_> = updates
+                .iter()
+                .map(|update| PushUpdate {
+                    local_ref: update
+                        .src_refname()
+                        .filter(|name| !name.is_empty() && !update.dst().is_zero())
+                        .map(str::to_string),
+                    local_oid: update.dst().to_string(),
+                    remote_ref: update.dst_refname().unwrap_or_default().to_string(),
+                    remote_oid: (!update.src().is_zero()).then(|| update.src().to_string()),
+                })
+                .collect();
+            hooks::run_pre_push(repo, remote_name, &url, &updates, &mut on_hook_output).map_err(|e| {
+                hook_error = Some(e);
+                git2::Error::from_str("the pre-push hook refused the push")
+            })
+        });
+        callbacks.push_update_reference(|name, status| {
+            pushed.push(PushedRef {
+                remote_ref: name.to_string(),
+                error: status.map(str::to_string),
+            });
+            Ok(())
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote.push(refspecs, Some(&mut options))
+    };
+    if let Some(e) = hook_error {
+        return Err(PushError::Hook(e));
+    }
+    result?;
+    info!("Pushed {} refs to {}", pushed.len(), remote_name);
+    Ok(pushed)
+}
+
+/// Fetches the upstream of the current branch and fast-forwards the branch to it, like `git pull --ff-only`.
+///
+/// Local changes are kept; the pull fails if they conflict or if the branch and its upstream diverged. Callers run
+/// the `post-merge` hook after a [`PullOutcome::FastForward`].
+pub fn pull_branch(
+    repo: &Repository,
+    on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<PullOutcome, git2::Error> {
+    let head = repo.head()?;
+    let Some(branch) = head.name().filter(|_| head.is_branch()).map(str::to_string) else {
+        return Err(git2::Error::from_str("HEAD is not on a branch"));
+    };
+    let remote = repo.branch_upstream_remote(&branch)?;
+    let remote = remote
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("remote name is not valid utf-8"))?;
+    fetch_remote(repo, remote, on_progress)?;
+
+    let upstream = repo.branch_upstream_name(&branch)?;
+    let upstream = upstream
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("upstream name is not valid utf-8"))?;
+    let target = repo.find_reference(upstream)?.peel_to_commit()?;
+    let old = head.peel_to_commit()?.id();
+    if old == target.id() || repo.graph_descendant_of(old, target.id())? {
+        return Ok(PullOutcome::UpToDate);
+    }
+    if !repo.graph_descendant_of(target.id(), old)? {
+        return Err(git2::Error::from_str(&format!(
+            "{} and {} diverged, cannot fast-forward",
+            branch, upstream
+        )));
+    }
+
+    let tree = target.tree()?;
+    sparse::prepare_checkout(repo, &tree)?;
+    let reflog = format!("pull: Fast-forward to {}", upstream);
+    let updated = repo
+        .checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))
+        .and_then(|()| repo.reference_matching(&branch, target.id(), true, old, &reflog).map(|_| ()));
+    // The sparse checkout is put back whether or not the fast-forward went through.
+    let restored = sparse::restore_after_checkout(repo);
+    updated.and(restored)?;
+    info!("Fast-forwarded {} to {}", branch, target.id());
+    Ok(PullOutcome::FastForward {
+        old_oid: old.to_string(),
+        new_oid: target.id().to_string(),
+    })
+}
+
+/// The bundle file a remote URL points to, if any.
+fn bundle_path(url: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(url.strip_prefix("file://").unwrap_or(url));
+    bundle::is_bundle(&path).then_some(path)
+}
+
+/// Fetches every configured remote of `repo`.
+pub fn fetch_all(
+    repo: &Repository,
+    mut on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<Vec<FetchSummary>, git2::Error> {
+    let remotes = repo.remotes()?;
+    remotes
+        .iter()
+        .flatten()
+        .map(|name| fetch_remote(repo, name, &mut on_progress))
+        .collect()
+}
+
+/// Whether a fetch failed because the remote rejected or never got usable credentials.
+pub fn is_auth_error(e: &git2::Error) -> bool {
+    e.code() == ErrorCode::Auth || (e.class() == ErrorClass::Ssh && e.message().contains("authentication"))
+}
+
+/// Counts the commits an updated ref brought in, i.e. those reachable from the new tip but not the old one.
+///
+/// For refs that did not exist before, commits already reachable from HEAD are not counted.
+pub fn count_incoming(repo: &Repository, updated: &UpdatedRef) -> Result<u32, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(Oid::from_str(&updated.new_oid)?)?;
+    match &updated.old_oid {
+        Some(old) => walk.hide(Oid::from_str(old)?)?,
+        None => {
+            if let Ok(head) = repo.head() {
+                if let Some(oid) = head.target() {
+                    walk.hide(oid)?;
+                }
+            }
+        }
+    }
+    Ok(walk.count() as u32)
+}
+
+/// Clones `url` into `path`, reporting transfer progress like [`fetch_remote`].
+///
+/// Returning `false` from `on_progress` cancels the clone.
+pub fn clone_repo(
+    url: &str,
+    path: &Path,
+    mut on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<Repository, git2::Error> {
+    let mut callbacks = remote_callbacks(Config::open_default().ok());
+    callbacks.transfer_progress(|stats| {
+        on_progress(FetchProgress {
+            remote: "origin".to_string(),
+            total_objects: stats.total_objects() as u32,
+            received_objects: stats.received_objects() as u32,
+            indexed_objects: stats.indexed_objects() as u32,
+            received_bytes: stats.received_bytes() as u64,
+        })
+    });
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    let repo = RepoBuilder::new().fetch_options(options).clone(url, path)?;
+    info!("Cloned {} into {:?}", url, path);
+    Ok(repo)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::git::test_support::commit_files;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn install_pre_push(repo: &Repository, script: &str) {
+        let path = hooks::hooks_dir(repo).unwrap().join("pre-push");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_push_runs_pre_push_and_pull_fast_forwards() {
+        let dir = std::env::temp_dir().join("gitultra_push_test");
+        let _ = fs::remove_dir_all(&dir);
+        let origin_path = dir.join("origin.git");
+        let origin = Repository::init_bare(&origin_path).unwrap();
+        let url = origin_path.to_str().unwrap();
+        let repo = Repository::init(dir.join("repo")).unwrap();
+        repo.remote("origin", url).unwrap();
+        let first = commit_files(&repo, &[("file.txt", "one\n")], "first");
+        let branch = repo.head().unwrap().name().unwrap().to_string();
+        let refspecs = [format!("{0}:{0}", branch)];
+
+        install_pre_push(&repo, "echo \"$1 $2\"; cat\n");
+        let mut lines = Vec::new();
+        let pushed = push_remote(&repo, "origin", &refspecs, false, |_, line| {
+            lines.push(line.to_string())
+        })
+        .unwrap();
+        assert_eq!(
+            pushed,
+            vec![PushedRef {
+                remote_ref: branch.clone(),
+                error: None,
+            }]
+        );
+        assert_eq!(
+            lines,
+            vec![
+                format!("origin {}", url),
+                format!("{} {} {} {}", branch, first, branch, Oid::zero()),
+            ]
+        );
+        assert_eq!(origin.refname_to_id(&branch).unwrap(), first);
+
+        // A refusing hook stops the push, unless it is skipped.
+        let second = commit_files(&repo, &[("file.txt", "one\ntwo\n")], "second");
+        install_pre_push(&repo, "cat > /dev/null; exit 1\n");
+        let refused = push_remote(&repo, "origin", &refspecs, false, |_, _| {});
+        assert!(matches!(refused, Err(PushError::Hook(HookError::Rejected { .. }))));
+        assert_eq!(origin.refname_to_id(&branch).unwrap(), first);
+        push_remote(&repo, "origin", &refspecs, true, |_, _| {}).unwrap();
+        assert_eq!(origin.refname_to_id(&branch).unwrap(), second);
+
+        let clone = Repository::init(dir.join("clone")).unwrap();
+        clone.remote("origin", url).unwrap();
+        fetch_remote(&clone, "origin", |_| true).unwrap();
+        let short = branch.trim_start_matches("refs/heads/");
+        let start = clone.find_commit(first).unwrap();
+        clone
+            .branch(short, &start, true)
+            .unwrap()
+            .set_upstream(Some(&format!("origin/{}", short)))
+            .unwrap();
+        clone.set_head(&branch).unwrap();
+        clone.checkout_head(Some(CheckoutBuilder::new().force())).unwrap();
+
+        assert_eq!(
+            pull_branch(&clone, |_| true).unwrap(),
+            PullOutcome::FastForward {
+                old_oid: first.to_string(),
+                new_oid: second.to_string(),
+            }
+        );
+        assert_eq!(clone.head().unwrap().target(), Some(second));
+        assert_eq!(fs::read_to_string(dir.join("clone/file.txt")).unwrap(), "one\ntwo\n");
+        assert_eq!(pull_branch(&clone, |_| true).unwrap(), PullOutcome::UpToDate);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,402 @@
+use std::path::PathBuf;
+
+use git2::{Commit, Diff, DiffOptions, Oid, Repository, Sort};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::commit_cache::{self, CachedCommit};
+use super::index_cache::GitIndexCache;
+
+/// Commits loaded from the cache per batch; progress is reported between batches.
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct CommitQuery {
+    /// Revisions to walk from, `HEAD` when empty. Supports `^rev` to exclude and `a..b` ranges.
+    pub revs: Vec<String>,
+    /// Regex matched against `Name <email>` of the author.
+    pub author: Option<String>,
+    /// Regex matched against `Name <email>` of the committer.
+    pub committer: Option<String>,
+    /// Regex matched against the full commit message.
+    pub message: Option<String>,
+    /// Match all regexes case-insensitively, like `git log -i`.
+    pub ignore_case: bool,
+    /// Committer time bounds in seconds since the epoch, inclusive.
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// Only commits touching these paths (pathspecs).
+    pub paths: Vec<PathBuf>,
+    pub pickaxe: Option<Pickaxe>,
+    pub merges: MergeFilter,
+    /// Follow only the first parent of merges, like `git log --first-parent`.
+    pub first_parent: bool,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub enum Pickaxe {
+    /// Commits changing the number of occurrences of the string, like `git log -S`.
+    Occurrences(String),
+    /// Commits adding or removing lines that match the regex, like `git log -G`.
+    Lines(String),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, Type)]
+pub enum MergeFilter {
+    #[default]
+    All,
+    MergesOnly,
+    NoMerges,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum MatchField {
+    Author,
+    Committer,
+    Message,
+}
+
+/// A match of one of the query's regexes, as a byte range into the field's text.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct Highlight {
+    pub field: MatchField,
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct CommitMatch {
+    pub oid: String,
+    pub highlights: Vec<Highlight>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Finds the commits matching `query`, newest first.
+///
+/// Metadata is filtered on the commit cache; only path and pickaxe filters need to diff trees. `on_progress`
+/// is called with the number of commits inspected so far, returning `false` cancels the search.
+pub fn search_commits(
+    repo: &Repository,
+    cache: Option<&GitIndexCache>,
+    query: &CommitQuery,
+    mut on_progress: impl FnMut(usize) -> bool,
+) -> Result<Vec<CommitMatch>, SearchError> {
+    let matcher = Matcher::new(query)?;
+
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME | Sort::TOPOLOGICAL)?;
+    if query.first_parent {
+        walk.simplify_first_parent()?;
+    }
+    if query.revs.is_empty() {
+        walk.push_head()?;
+    }
+    for rev in &query.revs {
+        if rev.contains("..") {
+            walk.push_range(rev)?;
+        } else if let Some(hidden) = rev.strip_prefix('^') {
+            walk.hide(repo.revparse_single(hidden)?.peel_to_commit()?.id())?;
+        } else {
+            walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?;
+        }
+    }
+    let oids = walk.collect::<Result<Vec<Oid>, _>>()?;
+
+    let mut matches = Vec::new();
+    for (batch_index, batch) in oids.chunks(BATCH_SIZE).enumerate() {
+        if !on_progress(batch_index * BATCH_SIZE) {
+            return Err(
+                git2::Error::new(git2::ErrorCode::User, git2::ErrorClass::None, "commit search cancelled").into(),
+            );
+        }
+        for commit in commit_cache::load_commits(repo, cache, batch)? {
+            let Some(highlights) = matcher.match_metadata(&commit) else {
+                continue;
+            };
+            if matcher.needs_diff() && !matcher.match_content(repo, &repo.find_commit(Oid::from_str(&commit.oid)?)?)? {
+                continue;
+            }
+            matches.push(CommitMatch {
+                oid: commit.oid,
+                highlights,
+            });
+            if query.limit.is_some_and(|limit| matches.len() as u32 >= limit) {
+                return Ok(matches);
+            }
+        }
+    }
+    Ok(matches)
+}
+
+struct Matcher<'q> {
+    query: &'q CommitQuery,
+    author: Option<Regex>,
+    committer: Option<Regex>,
+    message: Option<Regex>,
+    pickaxe: Option<regex::bytes::Regex>,
+}
+
+impl<'q> Matcher<'q> {
+    fn new(query: &'q CommitQuery) -> Result<Self, regex::Error> {
+        let build = |pattern: &str| RegexBuilder::new(pattern).case_insensitive(query.ignore_case).build();
+        let pickaxe = match &query.pickaxe {
+            Some(Pickaxe::Occurrences(needle)) => Some(regex::escape(needle)),
+            Some(Pickaxe::Lines(pattern)) => Some(pattern.clone()),
+            None => None,
+        };
+        Ok(Self {
+            query,
+            author: query.author.as_deref().map(build).transpose()?,
+            committer: query.committer.as_deref().map(build).transpose()?,
+            message: query.message.as_deref().map(build).transpose()?,
+            pickaxe: pickaxe
+                .map(|p| regex::bytes::RegexBuilder::new(&p).case_insensitive(query.ignore_case).build())
+                .transpose()?,
+        })
+    }
+
+    fn needs_diff(&self) -> bool {
+        !self.query.paths.is_empty() || self.pickaxe.is_some()
+    }
+
+    /// Applies the filters that only need cached metadata, returning the highlights on a match.
+    fn match_metadata(&self, commit: &CachedCommit) -> Option<Vec<Highlight>> {
+        let query = self.query;
+        let merge_ok = match query.merges {
+            MergeFilter::All => true,
+            MergeFilter::MergesOnly => commit.is_merge(),
+            MergeFilter::NoMerges => !commit.is_merge(),
+        };
+        if !merge_ok
+            || query.since.is_some_and(|since| commit.committer_time < since)
+            || query.until.is_some_and(|until| commit.committer_time > until)
+        {
+            return None;
+        }
+
+        let mut highlights = Vec::new();
+        let fields = [
+            (MatchField::Author, &self.author, commit.author()),
+            (MatchField::Committer, &self.committer, commit.committer()),
+            (MatchField::Message, &self.message, commit.message.clone()),
+        ];
+        for (field, regex, text) in fields {
+            let Some(regex) = regex else {
+                continue;
+            };
+            let before = highlights.len();
+            highlights.extend(regex.find_iter(&text).map(|m| Highlight {
+                field,
+                start: m.start() as u32,
+                end: m.end() as u32,
+            }));
+            if highlights.len() == before {
+                return None;
+            }
+        }
+        Some(highlights)
+    }
+
+    /// Applies the path and pickaxe filters, which need to diff the commit against its parents.
+    fn match_content(&self, repo: &Repository, commit: &Commit) -> Result<bool, git2::Error> {
+        let tree = commit.tree()?;
+        let mut opts = DiffOptions::new();
+        for path in &self.query.paths {
+            opts.pathspec(path);
+        }
+
+        // Like git's history simplification, a merge only touches the paths if it differs from every parent.
+        let considered = if self.query.first_parent { 1 } else { usize::MAX };
+        let parents: Vec<_> = commit.parents().take(considered).collect();
+        let mut diffs = Vec::new();
+        if parents.is_empty() {
+            diffs.push(repo.diff_tree_to_tree(None, Some(&tree), Some(&mut opts))?);
+        }
+        for parent in &parents {
+            let diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), Some(&mut opts))?;
+            if diff.deltas().len() == 0 {
+                return Ok(false);
+            }
+            diffs.push(diff);
+        }
+        if diffs.first().is_none_or(|diff| diff.deltas().len() == 0) {
+            return Ok(false);
+        }
+
+        match (&self.pickaxe, &self.query.pickaxe) {
+            // As with `git log`, merges have no diff to search without `-m`.
+            (Some(_), _) if parents.len() > 1 => Ok(false),
+            (Some(regex), Some(Pickaxe::Occurrences(_))) => changes_occurrences(repo, &diffs[0], regex),
+            (Some(regex), _) => changes_matching_lines(&diffs[0], regex),
+            (None, _) => Ok(true),
+        }
+    }
+}
+
+/// `-S`: does any file have a different number of matches before and after?
+fn changes_occurrences(repo: &Repository, diff: &Diff, regex: &regex::bytes::Regex) -> Result<bool, git2::Error> {
+    let count = |oid: Oid| -> Result<usize, git2::Error> {
+        if oid.is_zero() {
+            return Ok(0);
+        }
+        let blob = repo.find_blob(oid)?;
+        if blob.is_binary() {
+            return Ok(0);
+        }
+        Ok(regex.find_iter(blob.content()).count())
+    };
+    for delta in diff.deltas() {
+        if count(delta.old_file().id())? != count(delta.new_file().id())? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// `-G`: does any added or removed line match?
+fn changes_matching_lines(diff: &Diff, regex: &regex::bytes::Regex) -> Result<bool, git2::Error> {
+    let mut found = false;
+    let result = diff.foreach(
+        &mut |_, _| true,
+        None,
+        None,
+        Some(&mut |_, _, line| {
+            found = matches!(line.origin(), '+' | '-') && regex.is_match(line.content());
+            !found
+        }),
+    );
+    match result {
+        Err(_) if found => Ok(true),
+        Err(e) => Err(e),
+        Ok(()) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files_as, init_repo};
+    use std::fs;
+
+    fn commit(repo: &Repository, author: &str, time: i64, files: &[(&str, &str)], message: &str) -> String {
+        let email = format!("{}@example.com", author.to_lowercase());
+        let signature = git2::Signature::new(author, &email, &git2::Time::new(time, 0)).unwrap();
+        commit_files_as(repo, &signature, &signature, files, message).to_string()
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let repo = init_repo("search");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let first = commit(
+            &repo,
+            "Alice",
+            1000,
+            &[("src/parser.rs", "fn parse() {}\n")],
+            "Add parser",
+        );
+        let second = commit(
+            &repo,
+            "Bob",
+            2000,
+            &[("src/lexer.rs", "let token = 1;\n")],
+            "Fix lexer bug",
+        );
+        let third = commit(
+            &repo,
+            "Alice",
+            3000,
+            &[
+                ("src/parser.rs", "fn parse() { lex() }\n"),
+                ("src/lexer.rs", "let token = 2;\n"),
+            ],
+            "Refactor parser",
+        );
+        let cache = GitIndexCache::open(&dir).unwrap();
+        let search = |query: CommitQuery| -> Vec<String> {
+            let matches = search_commits(&repo, Some(&cache), &query, |_| true).unwrap();
+            matches.into_iter().map(|m| m.oid).collect()
+        };
+
+        let query = CommitQuery {
+            message: Some("PARSER".to_string()),
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert_eq!(search(query.clone()), vec![third.clone(), first.clone()]);
+        let matches = search_commits(&repo, Some(&cache), &query, |_| true).unwrap();
+        assert_eq!(
+            matches[0].highlights,
+            vec![Highlight {
+                field: MatchField::Message,
+                start: 9,
+                end: 15,
+            }]
+        );
+        let query = CommitQuery {
+            message: Some("PARSER".to_string()),
+            ..Default::default()
+        };
+        assert!(search(query).is_empty());
+
+        let query = CommitQuery {
+            author: Some("^Bob <".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![second.clone()]);
+
+        let query = CommitQuery {
+            since: Some(1500),
+            until: Some(3000),
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![third.clone(), second.clone()]);
+
+        let query = CommitQuery {
+            paths: vec!["src/parser.rs".into()],
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![third.clone(), first.clone()]);
+
+        // -S only counts changes in the number of occurrences, -G any changed line.
+        let query = CommitQuery {
+            pickaxe: Some(Pickaxe::Occurrences("token".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![second.clone()]);
+        let query = CommitQuery {
+            pickaxe: Some(Pickaxe::Lines("tok.n".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![third.clone(), second.clone()]);
+
+        let query = CommitQuery {
+            author: Some("Alice".to_string()),
+            paths: vec!["src/lexer.rs".into()],
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(search(query), vec![third.clone()]);
+
+        let query = CommitQuery {
+            message: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            search_commits(&repo, Some(&cache), &query, |_| true),
+            Err(SearchError::Pattern(_))
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
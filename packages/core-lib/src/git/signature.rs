@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::{fs, io, thread};
+
+use git2::{Config, ErrorCode, ObjectType, Oid, Repository};
+use log::warn;
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use uuid::Uuid;
+
+use super::commit_details::SignatureFormat;
+use super::index_cache::{CacheError, GitIndexCache};
+use super::CommitNode;
+
+/// Verification results keyed by raw OID bytes. Kept across cache version changes, see [`GitIndexCache::open`].
+pub(crate) const SIGNATURE_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("signatures");
+
+/// Namespace git uses for SSH signatures of commits and tags.
+const SSH_NAMESPACE: &str = "git";
+
+/// Overrides for the programs used to verify and create signatures.
+///
+/// `None` falls back to `gpg.<format>.program` (and `gpg.program` for OpenPGP) from the git config, then to
+/// `gpg`, `ssh-keygen` and `gpgsm`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Type)]
+#[serde(default)]
+pub struct GpgPrograms {
+    pub openpgp: Option<String>,
+    pub ssh: Option<String>,
+    pub x509: Option<String>,
+}
+
+impl GpgPrograms {
+    pub fn resolve(&self, config: &Config, format: SignatureFormat) -> String {
+        let (explicit, key, default) = match format {
+            SignatureFormat::Ssh => (&self.ssh, "gpg.ssh.program", "ssh-keygen"),
+            SignatureFormat::X509 => (&self.x509, "gpg.x509.program", "gpgsm"),
+            SignatureFormat::OpenPgp | SignatureFormat::Unknown => (&self.openpgp, "gpg.openpgp.program", "gpg"),
+        };
+        if let Some(program) = explicit {
+            return program.clone();
+        }
+        let configured = config.get_string(key).ok();
+        let configured = match format {
+            SignatureFormat::OpenPgp | SignatureFormat::Unknown => {
+                configured.or_else(|| config.get_string("gpg.program").ok())
+            }
+            _ => configured,
+        };
+        configured.unwrap_or_else(|| default.to_string())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum TrustLevel {
+    /// Valid signature from a trusted key or allowed SSH signer.
+    Good,
+    /// Valid signature, but the key is not trusted, expired or revoked.
+    Untrusted,
+    /// The signature does not match the signed content.
+    Bad,
+    /// The signature could not be checked, e.g. the public key or the program is missing.
+    Unknown,
+    Unsigned,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct Verification {
+    pub trust: TrustLevel,
+    pub format: Option<SignatureFormat>,
+    /// The key's user id for OpenPGP and X.509, the principal for SSH.
+    pub signer: Option<String>,
+    /// Key fingerprint or id as reported by the verification program.
+    pub key: Option<String>,
+    /// Human readable output of the verification program.
+    pub output: String,
+}
+
+impl Verification {
+    fn unsigned() -> Self {
+        Self {
+            trust: TrustLevel::Unsigned,
+            format: None,
+            signer: None,
+            key: None,
+            output: String::new(),
+        }
+    }
+
+    /// Whether the result is worth caching. Untrusted and unknown results change as soon as keys are imported or
+    /// trusted, so they are checked again every time. Good results are kept until
+    /// [`GitIndexCache::clear_verifications`], e.g. after a key was revoked or its trust lowered.
+    fn is_definitive(&self) -> bool {
+        matches!(self.trust, TrustLevel::Good | TrustLevel::Bad | TrustLevel::Unsigned)
+    }
+}
+
+pub fn verify_commit(repo: &Repository, oid: Oid, programs: &GpgPrograms) -> Result<Verification, git2::Error> {
+    match repo.extract_signature(&oid, None) {
+        Ok((signature, data)) => Ok(verify(&repo.config()?, programs, &signature, &data)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(Verification::unsigned()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Verifies an annotated tag, whose signature is appended to its message.
+pub fn verify_tag(repo: &Repository, oid: Oid, programs: &GpgPrograms) -> Result<Verification, git2::Error> {
+    let odb = repo.odb()?;
+    let object = odb.read(oid)?;
+    if object.kind() != ObjectType::Tag {
+        return Err(git2::Error::from_str("not an annotated tag"));
+    }
+    match split_tag_signature(object.data()) {
+        Some((data, signature)) => Ok(verify(&repo.config()?, programs, signature, data)),
+        None => Ok(Verification::unsigned()),
+    }
+}
+
+/// Like [`verify_commit`], reusing and storing definitive results in `cache`.
+pub fn verify_commit_cached(
+    repo: &Repository,
+    cache: Option<&GitIndexCache>,
+    oid: Oid,
+    programs: &GpgPrograms,
+) -> Result<Verification, git2::Error> {
+    if let Some(cache) = cache {
+        match cache.get_verification(oid) {
+            Ok(Some(verification)) => return Ok(verification),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read signature cache: {}", e),
+        }
+    }
+    let verification = verify_commit(repo, oid, programs)?;
+    if let (Some(cache), true) = (cache, verification.is_definitive()) {
+        if let Err(e) = cache.put_verification(oid, &verification) {
+            warn!("Failed to update signature cache: {}", e);
+        }
+    }
+    Ok(verification)
+}
+
+/// Fills [`CommitNode::trust`] from the cached verification results; nodes never verified are left alone.
+pub fn annotate_trust(nodes: &mut [CommitNode], cache: &GitIndexCache) -> Result<(), CacheError> {
+    let oids = nodes
+        .iter()
+        .map(|node| Oid::from_str(&node.oid))
+        .collect::<Result<Vec<_>, _>>()?;
+    let trust = cache.get_trust_levels(&oids)?;
+    for (node, oid) in nodes.iter_mut().zip(oids) {
+        if let Some(level) = trust.get(&oid) {
+            node.trust = Some(*level);
+        }
+    }
+    Ok(())
+}
+
+impl GitIndexCache {
+    pub fn get_verification(&self, oid: Oid) -> Result<Option<Verification>, CacheError> {
+        let read_txn = self.database().begin_read()?;
+        let table = match read_txn.open_table(SIGNATURE_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match table.get(oid.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_trust_levels(&self, oids: &[Oid]) -> Result<HashMap<Oid, TrustLevel>, CacheError> {
+        let read_txn = self.database().begin_read()?;
+        let table = match read_txn.open_table(SIGNATURE_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut levels = HashMap::new();
+        for oid in oids {
+            if let Some(value) = table.get(oid.as_bytes())? {
+                let verification: Verification = serde_json::from_slice(value.value())?;
+                levels.insert(*oid, verification.trust);
+            }
+        }
+        Ok(levels)
+    }
+
+    pub fn put_verification(&self, oid: Oid, verification: &Verification) -> Result<(), CacheError> {
+        let write_txn = self.database().begin_write()?;
+        {
+            let mut table = write_txn.open_table(SIGNATURE_TABLE)?;
+            table.insert(oid.as_bytes(), serde_json::to_vec(verification)?.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Forgets all verification results, e.g. after keys or allowed signers changed.
+    pub fn clear_verifications(&self) -> Result<(), CacheError> {
+        let write_txn = self.database().begin_write()?;
+        write_txn.delete_table(SIGNATURE_TABLE)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Verifies `signature` over `data`. Failures to run the program are reported as [`TrustLevel::Unknown`].
+fn verify(config: &Config, programs: &GpgPrograms, signature: &[u8], data: &[u8]) -> Verification {
+    let format = SignatureFormat::detect(signature);
+    let program = programs.resolve(config, format);
+    let result = TempFile::create(signature).and_then(|file| match format {
+        SignatureFormat::Ssh => verify_ssh(config, &program, file.path(), data),
+        _ => verify_gpg(&program, format, file.path(), data),
+    });
+    match result {
+        Ok(mut verification) => {
+            verification.format = Some(format);
+            verification
+        }
+        Err(e) => Verification {
+            trust: TrustLevel::Unknown,
+            format: Some(format),
+            signer: None,
+            key: None,
+            output: format!("Failed to run {}: {}", program, e),
+        },
+    }
+}
+
+/// Runs `gpg` (or `gpgsm`) like git does and interprets its machine readable status output.
+fn verify_gpg(program: &str, format: SignatureFormat, signature: &Path, data: &[u8]) -> io::Result<Verification> {
+    let output = run(program, &gpg_verify_args(format, signature), Some(data))?;
+    let mut verification = parse_gpg_status(&String::from_utf8_lossy(&output.stdout));
+    verification.output = String::from_utf8_lossy(&output.stderr).into_owned();
+    Ok(verification)
+}
+
+/// `gpgsm` rejects `--keyid-format`, git only passes it to `gpg`.
+fn gpg_verify_args(format: SignatureFormat, signature: &Path) -> Vec<&OsStr> {
+    let mut args = Vec::new();
+    if format == SignatureFormat::OpenPgp {
+        args.push(OsStr::new("--keyid-format=long"));
+    }
+    args.extend([
+        OsStr::new("--status-fd=1"),
+        OsStr::new("--verify"),
+        signature.as_os_str(),
+        OsStr::new("-"),
+    ]);
+    args
+}
+
+fn parse_gpg_status(status: &str) -> Verification {
+    let (mut good, mut bad, mut expired, mut trusted) = (false, false, false, None);
+    let mut signer = None;
+    let mut key_id = None;
+    let mut fingerprint = None;
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let mut parts = line.splitn(3, ' ');
+        let keyword = parts.next().unwrap_or_default();
+        match keyword {
+            "GOODSIG" | "BADSIG" | "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG" => {
+                good |= keyword == "GOODSIG";
+                bad |= keyword == "BADSIG";
+                expired |= matches!(keyword, "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG");
+                key_id = parts.next().map(str::to_string);
+                signer = parts.next().map(str::to_string);
+            }
+            "ERRSIG" => key_id = parts.next().map(str::to_string),
+            "VALIDSIG" => fingerprint = parts.next().map(str::to_string),
+            "TRUST_UNDEFINED" | "TRUST_NEVER" => trusted = Some(false),
+            "TRUST_MARGINAL" | "TRUST_FULLY" | "TRUST_ULTIMATE" => trusted = Some(true),
+            _ => {}
+        }
+    }
+
+    let trust = if bad {
+        TrustLevel::Bad
+    } else if good && trusted == Some(true) {
+        TrustLevel::Good
+    } else if good || expired {
+        TrustLevel::Untrusted
+    } else {
+        TrustLevel::Unknown
+    };
+    Verification {
+        trust,
+        format: None,
+        signer,
+        key: fingerprint.or(key_id),
+        output: String::new(),
+    }
+}
+
+/// Verifies an SSH signature against `gpg.ssh.allowedSignersFile` with `ssh-keygen -Y`, like git does.
+///
+/// Without an allowed signers file (or a matching principal) a valid signature is only [`TrustLevel::Untrusted`].
+fn verify_ssh(config: &Config, program: &str, signature: &Path, data: &[u8]) -> io::Result<Verification> {
+    let sig = signature.as_os_str();
+    let mut outputs = Vec::new();
+
+    if let Ok(allowed) = config.get_path("gpg.ssh.allowedSignersFile") {
+        let allowed = allowed.as_os_str();
+        let found = run(
+            program,
+            &[
+                OsStr::new("-Y"),
+                OsStr::new("find-principals"),
+                OsStr::new("-f"),
+                allowed,
+                OsStr::new("-s"),
+                sig,
+            ],
+            None,
+        )?;
+        let principals = String::from_utf8_lossy(&found.stdout).into_owned();
+        for principal in principals.lines().filter(|p| !p.is_empty()) {
+            let args = [
+                OsStr::new("-Y"),
+                OsStr::new("verify"),
+                OsStr::new("-n"),
+                OsStr::new(SSH_NAMESPACE),
+                OsStr::new("-f"),
+                allowed,
+                OsStr::new("-I"),
+                OsStr::new(principal),
+                OsStr::new("-s"),
+                sig,
+            ];
+            let output = run(program, &args, Some(data))?;
+            let text = combined_output(&output);
+            if output.status.success() {
+                return Ok(ssh_verification(TrustLevel::Good, Some(principal.to_string()), text));
+            }
+            outputs.push(text);
+        }
+        if !outputs.is_empty() {
+            return Ok(ssh_verification(TrustLevel::Bad, None, outputs.join("\n")));
+        }
+    }
+
+    let args = [
+        OsStr::new("-Y"),
+        OsStr::new("check-novalidate"),
+        OsStr::new("-n"),
+        OsStr::new(SSH_NAMESPACE),
+        OsStr::new("-s"),
+        sig,
+    ];
+    let output = run(program, &args, Some(data))?;
+    let trust = if output.status.success() {
+        TrustLevel::Untrusted
+    } else {
+        TrustLevel::Bad
+    };
+    Ok(ssh_verification(trust, None, combined_output(&output)))
+}
+
+fn ssh_verification(trust: TrustLevel, signer: Option<String>, output: String) -> Verification {
+    // e.g. `Good "git" signature for me@example.com with ED25519 key SHA256:...`
+    let key = output
+        .split(" key ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string);
+    Verification {
+        trust,
+        format: None,
+        signer,
+        key,
+        output,
+    }
+}
+
+/// Splits a raw tag object into the signed part and the signature appended to its message.
+fn split_tag_signature(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    const MARKERS: [&[u8]; 3] = [
+        b"-----BEGIN PGP SIGNATURE-----",
+        b"-----BEGIN SSH SIGNATURE-----",
+        b"-----BEGIN SIGNED MESSAGE-----",
+    ];
+    (0..data.len())
+        .rev()
+        .filter(|&i| i == 0 || data[i - 1] == b'\n')
+        .find(|&i| MARKERS.iter().any(|marker| data[i..].starts_with(marker)))
+        .map(|i| data.split_at(i))
+}
+
+/// Runs `program`, feeding `stdin` from a separate thread so large inputs cannot deadlock on full pipes.
+pub(crate) fn run(program: &str, args: &[&OsStr], stdin: Option<&[u8]>) -> io::Result<Output> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let writer = match (child.stdin.take(), stdin) {
+        (Some(mut pipe), Some(input)) => {
+            let input = input.to_vec();
+            Some(thread::spawn(move || pipe.write_all(&input)))
+        }
+        _ => None,
+    };
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        // The program may exit without reading everything, a broken pipe is not an error then.
+        if let Ok(Err(e)) = writer.join() {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn combined_output(output: &Output) -> String {
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    text.trim().to_string()
+}
+
+/// Data written to a temporary file for external programs, removed on drop.
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    pub(crate) fn create(contents: &[u8]) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("gitultra-{}", Uuid::new_v4()));
+        fs::write(&path, contents)?;
+        Ok(Self(path))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let status = "[GNUPG:] NEWSIG\n\
+                      [GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@example.com>\n\
+                      [GNUPG:] VALIDSIG FINGERPRINT 2024-01-01 1704067200 0 4 0 1 10 00 FINGERPRINT\n\
+                      [GNUPG:] TRUST_UNDEFINED 0 pgp\n";
+        let verification = parse_gpg_status(status);
+        assert_eq!(verification.trust, TrustLevel::Untrusted);
+        assert_eq!(verification.signer.as_deref(), Some("Jane Doe <jane@example.com>"));
+        assert_eq!(verification.key.as_deref(), Some("FINGERPRINT"));
+
+        let status = status.replace("TRUST_UNDEFINED", "TRUST_ULTIMATE");
+        assert_eq!(parse_gpg_status(&status).trust, TrustLevel::Good);
+        let bad = "[GNUPG:] BADSIG 0123456789ABCDEF Jane Doe <jane@example.com>\n";
+        assert_eq!(parse_gpg_status(bad).trust, TrustLevel::Bad);
+        let missing_key =
+            "[GNUPG:] ERRSIG 0123456789ABCDEF 1 10 00 1704067200 9 -\n[GNUPG:] NO_PUBKEY 0123456789ABCDEF\n";
+        assert_eq!(parse_gpg_status(missing_key).trust, TrustLevel::Unknown);
+    }
+
+    #[test]
+    fn test_keyid_format_only_for_gpg() {
+        let signature = Path::new("sig");
+
+        let args = gpg_verify_args(SignatureFormat::OpenPgp, signature);
+        assert_eq!(args, ["--keyid-format=long", "--status-fd=1", "--verify", "sig", "-"]);
+
+        let args = gpg_verify_args(SignatureFormat::X509, signature);
+        assert_eq!(args, ["--status-fd=1", "--verify", "sig", "-"]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+
+use git2::{Config, Signature};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::commit_details::SignatureFormat;
+use super::signature::{run, GpgPrograms, TempFile};
+
+/// The signing related git configuration: `commit.gpgsign`, `tag.gpgsign`, `gpg.format` and `user.signingkey`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct SigningConfig {
+    pub sign_commits: bool,
+    pub sign_tags: bool,
+    pub format: SignatureFormat,
+    pub key: Option<String>,
+}
+
+impl SigningConfig {
+    pub fn from_config(config: &Config) -> Result<Self, SigningError> {
+        let format = match config.get_string("gpg.format").ok().as_deref() {
+            None | Some("openpgp") => SignatureFormat::OpenPgp,
+            Some("ssh") => SignatureFormat::Ssh,
+            Some("x509") => SignatureFormat::X509,
+            Some(other) => return Err(SigningError::UnsupportedFormat(other.to_string())),
+        };
+        Ok(Self {
+            sign_commits: config.get_bool("commit.gpgsign").unwrap_or(false),
+            sign_tags: config.get_bool("tag.gpgsign").unwrap_or(false),
+            format,
+            key: config.get_string("user.signingkey").ok().filter(|key| !key.is_empty()),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SigningError {
+    #[error("Unsupported gpg.format {0:?}")]
+    UnsupportedFormat(String),
+    #[error("No signing key configured, set user.signingkey")]
+    NoKey,
+    #[error("Failed to run {program}: {source}")]
+    Program { program: String, source: io::Error },
+    #[error("{program} failed to sign: {output}")]
+    Failed { program: String, output: String },
+}
+
+/// Creates a detached signature of `data` the way git does for `signing.format`.
+///
+/// OpenPGP and X.509 default to the committer's identity when no key is configured; SSH requires a key, either
+/// a path to a key file or a literal public key (`ssh-ed25519 ...` or `key::...`) held by the agent.
+pub fn sign(
+    signing: &SigningConfig,
+    programs: &GpgPrograms,
+    config: &Config,
+    committer: &Signature,
+    data: &[u8],
+) -> Result<String, SigningError> {
+    let program = programs.resolve(config, signing.format);
+    let program_error = |source| SigningError::Program {
+        program: program.clone(),
+        source,
+    };
+
+    let output = match signing.format {
+        SignatureFormat::Ssh => {
+            let key = signing.key.as_deref().ok_or(SigningError::NoKey)?;
+            let literal = key.strip_prefix("key::").or_else(|| key.starts_with("ssh-").then_some(key));
+            let (key_file, _public_key) = match literal {
+                Some(public_key) => {
+                    let file = TempFile::create(public_key.as_bytes()).map_err(program_error)?;
+                    (file.path().to_path_buf(), Some(file))
+                }
+                None => (expand_home(key), None),
+            };
+            let mut args = vec![
+                OsStr::new("-Y"),
+                OsStr::new("sign"),
+                OsStr::new("-n"),
+                OsStr::new("git"),
+                OsStr::new("-f"),
+                key_file.as_os_str(),
+            ];
+            if literal.is_some() {
+                args.push(OsStr::new("-U"));
+            }
+            run(&program, &args, Some(data)).map_err(program_error)?
+        }
+        _ => {
+            let key = signing.key.clone().unwrap_or_else(|| committer.to_string());
+            let args = [OsStr::new("--status-fd=2"), OsStr::new("-bsau"), OsStr::new(&key)];
+            let output = run(&program, &args, Some(data)).map_err(program_error)?;
+            if !String::from_utf8_lossy(&output.stderr).contains("[GNUPG:] SIG_CREATED ") {
+                return Err(SigningError::Failed {
+                    program,
+                    output: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                });
+            }
+            output
+        }
+    };
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(SigningError::Failed {
+            program,
+            output: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> (std::path::PathBuf, Config) {
+        let path = std::env::temp_dir().join(format!("gitultra_signing_test_{}", entries.len()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = Config::open(&path).unwrap();
+        for (key, value) in entries {
+            config.set_str(key, value).unwrap();
+        }
+        (path, config)
+    }
+
+    #[test]
+    fn test_signing_config_from_config() {
+        let (path, empty) = config(&[]);
+        let signing = SigningConfig::from_config(&empty).unwrap();
+        assert!(!signing.sign_commits && !signing.sign_tags);
+        assert_eq!(signing.format, SignatureFormat::OpenPgp);
+        assert_eq!(signing.key, None);
+        let _ = std::fs::remove_file(path);
+
+        let (path, configured) = config(&[
+            ("commit.gpgsign", "true"),
+            ("tag.gpgsign", "yes"),
+            ("gpg.format", "ssh"),
+            ("user.signingkey", "~/.ssh/id_ed25519.pub"),
+        ]);
+        let signing = SigningConfig::from_config(&configured).unwrap();
+        assert!(signing.sign_commits && signing.sign_tags);
+        assert_eq!(signing.format, SignatureFormat::Ssh);
+        assert_eq!(signing.key.as_deref(), Some("~/.ssh/id_ed25519.pub"));
+        let _ = std::fs::remove_file(path);
+
+        let (path, unsupported) = config(&[("gpg.format", "pgp")]);
+        assert!(matches!(
+            SigningConfig::from_config(&unsupported),
+            Err(SigningError::UnsupportedFormat(format)) if format == "pgp"
+        ));
+        let _ = std::fs::remove_file(path);
+
+        let (path, empty_key) = config(&[("user.signingkey", ""), ("gpg.format", "x509")]);
+        let signing = SigningConfig::from_config(&empty_key).unwrap();
+        assert_eq!(signing.format, SignatureFormat::X509);
+        assert_eq!(signing.key, None);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,904 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use git2::build::CheckoutBuilder;
+use git2::{
+    Config, ConfigLevel, ErrorCode, Index, IndexEntryExtendedFlag, ObjectType, Repository, Status, StatusEntry,
+    StatusOptions, Tree,
+};
+use glob::{MatchOptions, Pattern};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::git::config::{self, ConfigError, ConfigScope};
+use crate::git::partial_clone::{self, MissingObject};
+
+/// Non-cone patterns that include everything, written before sparse checkout is disabled.
+const EVERYTHING: &str = "/*";
+
+/// The sparse checkout settings of a worktree.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct SparseCheckout {
+    /// `core.sparseCheckout`; when off, every file is checked out whatever the patterns say.
+    pub enabled: bool,
+    /// The patterns are in cone mode: whole directories, see `directories`.
+    pub cone: bool,
+    /// The directories checked out recursively in cone mode. Files at the root and directly in their parent
+    /// directories are checked out too.
+    pub directories: Vec<String>,
+    /// The lines of `info/sparse-checkout`.
+    pub patterns: Vec<String>,
+}
+
+/// What to check out, as given to [`set_sparse_checkout`].
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+#[serde(tag = "kind", content = "value")]
+pub enum SparseSpec {
+    /// Directories, like `git sparse-checkout set --cone`.
+    Cone(Vec<String>),
+    /// Gitignore-style patterns selecting files, like `git sparse-checkout set --no-cone`.
+    Patterns(Vec<String>),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Type)]
+pub enum SparseSelection {
+    /// Everything below the directory is checked out.
+    Included,
+    /// Only some of its subdirectories, and its own files, are checked out.
+    Partial,
+    Excluded,
+}
+
+/// A directory of the tree at HEAD, as shown in the sparse checkout directory picker.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct SparseDirectory {
+    pub name: String,
+    /// Path from the repository root, `/`-separated.
+    pub path: String,
+    pub has_subdirectories: bool,
+    pub selection: SparseSelection,
+}
+
+/// The outcome of updating the working tree to the sparse checkout patterns.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct SparseApplyOutcome {
+    /// Files checked out because the patterns now include them.
+    pub added: u32,
+    /// Files removed from the working tree because the patterns exclude them.
+    pub removed: u32,
+    /// Excluded files kept in the working tree because they have local changes, like git does.
+    pub kept_modified: Vec<String>,
+}
+
+/// Tracked files left out of the working tree.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct SparseExcluded {
+    pub total: u32,
+    /// The first excluded paths, up to the requested limit, in index order.
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SparseError {
+    #[error("Invalid sparse checkout directory {0:?}")]
+    InvalidDirectory(String),
+    #[error("Sparse checkout needs a working tree")]
+    Bare,
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    MissingObject(#[from] MissingObject),
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+}
+
+/// Reads the sparse checkout configuration of the worktree of `repo`.
+///
+/// Cone mode is reported only when the patterns have the cone shape; otherwise they are matched like git does in that
+/// case, as non-cone patterns.
+pub fn read_sparse_checkout(repo: &Repository) -> Result<SparseCheckout, SparseError> {
+    let config = worktree_config(repo)?;
+    let enabled = config_bool(&config, "core.sparseCheckout")?.unwrap_or(false);
+    let cone_config = config_bool(&config, "core.sparseCheckoutCone")?.unwrap_or(false);
+    let patterns = match fs::read_to_string(patterns_file(repo)) {
+        Ok(content) => content
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let directories = match cone_config {
+        true => parse_cone(&patterns),
+        false => None,
+    };
+    Ok(SparseCheckout {
+        enabled,
+        cone: directories.is_some(),
+        directories: directories.unwrap_or_default(),
+        patterns,
+    })
+}
+
+/// Enables sparse checkout with `spec`, like `git sparse-checkout set`, and updates the working tree to it.
+pub fn set_sparse_checkout(repo: &Repository, spec: &SparseSpec) -> Result<SparseApplyOutcome, SparseError> {
+    if repo.is_bare() {
+        return Err(SparseError::Bare);
+    }
+    let (cone, patterns) = match spec {
+        SparseSpec::Cone(directories) => (true, cone_patterns(&normalize_directories(directories)?)),
+        SparseSpec::Patterns(patterns) => (false, patterns.clone()),
+    };
+    write_patterns(repo, &patterns)?;
+    enable_worktree_config(repo)?;
+    config::set_value(Some(repo), ConfigScope::Worktree, "core.sparseCheckout", "true")?;
+    let cone = if cone { "true" } else { "false" };
+    config::set_value(Some(repo), ConfigScope::Worktree, "core.sparseCheckoutCone", cone)?;
+    info!(
+        "Sparse checkout of {:?} set to {} patterns",
+        repo.workdir(),
+        patterns.len()
+    );
+    reapply_sparse_checkout(repo)
+}
+
+/// Checks out every file again and turns sparse checkout off, like `git sparse-checkout disable`.
+pub fn disable_sparse_checkout(repo: &Repository) -> Result<SparseApplyOutcome, SparseError> {
+    if repo.is_bare() {
+        return Err(SparseError::Bare);
+    }
+    let outcome = apply(repo, &Matcher::Patterns(parse_patterns(&[EVERYTHING.to_string()])))?;
+    enable_worktree_config(repo)?;
+    config::set_value(Some(repo), ConfigScope::Worktree, "core.sparseCheckout", "false")?;
+    Ok(outcome)
+}
+
+/// Updates the working tree to the current patterns, like `git sparse-checkout reapply`: files they include are
+/// checked out, the others removed unless they have local changes.
+pub fn reapply_sparse_checkout(repo: &Repository) -> Result<SparseApplyOutcome, SparseError> {
+    if repo.is_bare() {
+        return Err(SparseError::Bare);
+    }
+    let matcher = matcher(&read_sparse_checkout(repo)?);
+    apply(repo, &matcher)
+}
+
+/// Lists the subdirectories of `parent` (`""` for the root) in the tree at HEAD, with how much of each the
+/// sparse checkout includes.
+pub fn sparse_directories(repo: &Repository, parent: &str) -> Result<Vec<SparseDirectory>, SparseError> {
+    let head = match repo.head() {
+        Ok(head) => head.peel_to_tree()?,
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let parent = parent.trim_matches('/');
+    let tree = match parent {
+        "" => head,
+        _ => match head.get_path(Path::new(parent)) {
+            Ok(entry) if entry.kind() == Some(ObjectType::Tree) => repo.find_tree(entry.id())?,
+            Ok(_) => return Err(SparseError::InvalidDirectory(parent.to_string())),
+            Err(e) if e.code() == ErrorCode::NotFound => return Err(SparseError::InvalidDirectory(parent.to_string())),
+            Err(e) => return Err(e.into()),
+        },
+    };
+
+    let sparse = read_sparse_checkout(repo)?;
+    let matcher = match sparse.enabled {
+        true => Some(matcher(&sparse)),
+        false => None,
+    };
+    let mut directories = Vec::new();
+    for entry in tree.iter().filter(|entry| entry.kind() == Some(ObjectType::Tree)) {
+        let name = String::from_utf8_lossy(entry.name_bytes()).into_owned();
+        let path = join(parent, &name);
+        let subtree = repo.find_tree(entry.id())?;
+        let has_subdirectories = subtree.iter().any(|entry| entry.kind() == Some(ObjectType::Tree));
+        let selection = match &matcher {
+            Some(matcher) => matcher.directory_selection(&path),
+            None => SparseSelection::Included,
+        };
+        directories.push(SparseDirectory {
+            name,
+            path,
+            has_subdirectories,
+            selection,
+        });
+    }
+    Ok(directories)
+}
+
+/// Counts the tracked files left out of the working tree, listing the first `limit` of them.
+pub fn excluded_paths(repo: &Repository, limit: usize) -> Result<SparseExcluded, SparseError> {
+    let mut excluded = SparseExcluded::default();
+    for entry in repo.index()?.iter() {
+        if IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree() {
+            excluded.total += 1;
+            if excluded.paths.len() < limit {
+                excluded.paths.push(String::from_utf8_lossy(&entry.path).into_owned());
+            }
+        }
+    }
+    Ok(excluded)
+}
+
+/// The status of `entry`, not counting files left out by the sparse checkout as deleted: libgit2 does, but they are
+/// missing from the working tree on purpose.
+pub(crate) fn sparse_status(index: &Index, entry: &StatusEntry) -> Status {
+    let mut status = entry.status();
+    if entry.path().is_some_and(|path| is_excluded(index, Path::new(path))) {
+        status.remove(Status::WT_DELETED);
+    }
+    status
+}
+
+/// Whether the sparse checkout leaves the tracked file `path` out of the working tree.
+pub(crate) fn is_excluded(index: &Index, path: &Path) -> bool {
+    index
+        .get_path(path, 0)
+        .is_some_and(|entry| IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree())
+}
+
+/// Checks out again the excluded files that a checkout of `target` changes. libgit2 ignores the skip-worktree bits and
+/// takes those files, missing from the working tree, for local deletions conflicting with the checkout.
+///
+/// [`restore_after_checkout`] removes them again.
+pub(crate) fn prepare_checkout(repo: &Repository, target: &Tree) -> Result<(), git2::Error> {
+    let head = match repo.head() {
+        Ok(head) => head.peel_to_tree()?,
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut index = repo.index()?;
+    let mut paths = Vec::new();
+    for delta in repo.diff_tree_to_tree(Some(&head), Some(target), None)?.deltas() {
+        let Some(path) = delta.old_file().path() else {
+            continue;
+        };
+        let Some(mut entry) = index.get_path(path, 0) else {
+            continue;
+        };
+        let mut flags = IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended);
+        if flags.is_skip_worktree() {
+            flags.remove(IndexEntryExtendedFlag::SKIP_WORKTREE);
+            entry.flags_extended = flags.bits();
+            index.add(&entry)?;
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    if !paths.is_empty() {
+        index.write()?;
+        check_out(repo, &mut index, &paths)?;
+    }
+    Ok(())
+}
+
+/// Puts the sparse checkout back after a checkout or reset: libgit2 rewrites the index without the skip-worktree
+/// bits and forced checkouts write the excluded files back. Does nothing when sparse checkout is off.
+pub(crate) fn restore_after_checkout(repo: &Repository) -> Result<(), git2::Error> {
+    let sparse = read_sparse_checkout(repo).map_err(git_error)?;
+    if !sparse.enabled || repo.is_bare() {
+        return Ok(());
+    }
+    apply(repo, &matcher(&sparse)).map_err(git_error)?;
+    Ok(())
+}
+
+fn git_error(error: SparseError) -> git2::Error {
+    match error {
+        SparseError::Git(e) => e,
+        e => git2::Error::from_str(&e.to_string()),
+    }
+}
+
+fn config_bool(config: &git2::Config, name: &str) -> Result<Option<bool>, git2::Error> {
+    match config.get_bool(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Turns on `extensions.worktreeConfig` like `git sparse-checkout` does: the patterns belong to one worktree, so must
+/// the settings, rather than going to the `.git/config` that linked worktrees share.
+fn enable_worktree_config(repo: &Repository) -> Result<(), ConfigError> {
+    if config_bool(&repo.config()?, "extensions.worktreeConfig")?.unwrap_or(false) {
+        return Ok(());
+    }
+    // Extensions are only honored from repository format version 1.
+    config::set_value(Some(repo), ConfigScope::Local, "core.repositoryFormatVersion", "1")?;
+    config::set_value(Some(repo), ConfigScope::Local, "extensions.worktreeConfig", "true")
+}
+
+/// The config of `repo` including its `config.worktree`, which libgit2 only picks up when it opens the repository:
+/// [`enable_worktree_config`] may have turned it on since.
+fn worktree_config(repo: &Repository) -> Result<Config, git2::Error> {
+    let mut config = repo.config()?;
+    if config_bool(&config, "extensions.worktreeConfig")?.unwrap_or(false) {
+        match config.add_file(&repo.path().join("config.worktree"), ConfigLevel::Worktree, false) {
+            Err(e) if e.code() != ErrorCode::Exists => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(config)
+}
+
+/// The patterns live in the git directory of each worktree.
+fn patterns_file(repo: &Repository) -> std::path::PathBuf {
+    repo.path().join("info").join("sparse-checkout")
+}
+
+fn write_patterns(repo: &Repository, patterns: &[String]) -> io::Result<()> {
+    let file = patterns_file(repo);
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut content = patterns.join("\n");
+    content.push('\n');
+    fs::write(file, content)
+}
+
+/// Trims slashes and drops directories inside other listed ones, rejecting empty, `.` and `..` components.
+fn normalize_directories(directories: &[String]) -> Result<Vec<String>, SparseError> {
+    let mut normalized = Vec::new();
+    for directory in directories {
+        let trimmed = directory.trim().trim_matches('/');
+        if trimmed.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(SparseError::InvalidDirectory(directory.clone()));
+        }
+        normalized.push(trimmed.to_string());
+    }
+    normalized.sort();
+    normalized.dedup();
+    let all = normalized.clone();
+    normalized.retain(|directory| !all.iter().any(|other| is_inside(directory, other)));
+    Ok(normalized)
+}
+
+/// Whether `path` is strictly inside the directory `dir`.
+fn is_inside(path: &str, dir: &str) -> bool {
+    path.len() > dir.len() && path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/'
+}
+
+fn join(parent: &str, name: &str) -> String {
+    match parent {
+        "" => name.to_string(),
+        _ => format!("{}/{}", parent, name),
+    }
+}
+
+/// The cone mode patterns of `directories`, in the layout `git sparse-checkout set` writes.
+fn cone_patterns(directories: &[String]) -> Vec<String> {
+    let mut parents = directories.iter().flat_map(|dir| ancestors(dir)).collect::<Vec<_>>();
+    parents.sort();
+    parents.dedup();
+    let mut patterns = vec!["/*".to_string(), "!/*/".to_string()];
+    for parent in parents {
+        patterns.push(format!("/{}/", escape(&parent)));
+        patterns.push(format!("!/{}/*/", escape(&parent)));
+    }
+    for dir in directories {
+        patterns.push(format!("/{}/", escape(dir)));
+    }
+    patterns
+}
+
+/// Recovers the directories of cone mode patterns, `None` when they do not have the cone shape.
+fn parse_cone(patterns: &[String]) -> Option<Vec<String>> {
+    let mut lines = patterns.iter();
+    if lines.next().map(String::as_str) != Some("/*") || lines.next().map(String::as_str) != Some("!/*/") {
+        return None;
+    }
+    let mut included = Vec::new();
+    let mut parents = HashSet::new();
+    for line in lines {
+        if let Some(parent) = line.strip_prefix("!/").and_then(|rest| rest.strip_suffix("/*/")) {
+            parents.insert(unescape(parent));
+        } else if let Some(dir) = line.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            if dir.is_empty() {
+                return None;
+            }
+            included.push(unescape(dir));
+        } else {
+            return None;
+        }
+    }
+    let directories = included.into_iter().filter(|dir| !parents.contains(dir)).collect();
+    Some(directories)
+}
+
+/// The proper ancestors of `dir`, outermost first.
+fn ancestors(dir: &str) -> Vec<String> {
+    dir.match_indices('/').map(|(i, _)| dir[..i].to_string()).collect()
+}
+
+/// Cone patterns are glob patterns, special characters in directory names are escaped.
+fn escape(dir: &str) -> String {
+    let mut escaped = String::with_capacity(dir.len());
+    for c in dir.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(pattern: &str) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Decides which paths the sparse checkout includes.
+enum Matcher {
+    /// Everything is checked out, sparse checkout is off.
+    All,
+    Cone {
+        /// Directories included recursively.
+        recursive: HashSet<String>,
+        /// Their ancestors, whose own files are included.
+        parents: HashSet<String>,
+    },
+    Patterns(Vec<SparsePattern>),
+}
+
+struct SparsePattern {
+    pattern: Pattern,
+    negated: bool,
+    directory_only: bool,
+    /// Matched against the whole path rather than the last component.
+    anchored: bool,
+}
+
+fn matcher(sparse: &SparseCheckout) -> Matcher {
+    if !sparse.enabled {
+        return Matcher::All;
+    }
+    if sparse.cone {
+        let recursive = sparse.directories.iter().cloned().collect::<HashSet<_>>();
+        let parents = sparse.directories.iter().flat_map(|dir| ancestors(dir)).collect();
+        return Matcher::Cone { recursive, parents };
+    }
+    Matcher::Patterns(parse_patterns(&sparse.patterns))
+}
+
+fn parse_patterns(lines: &[String]) -> Vec<SparsePattern> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.as_str()),
+            };
+            let directory_only = line.ends_with('/');
+            let line = line.trim_end_matches('/');
+            let anchored = line.contains('/');
+            let pattern = Pattern::new(line.trim_start_matches('/')).ok()?;
+            Some(SparsePattern {
+                pattern,
+                negated,
+                directory_only,
+                anchored,
+            })
+        })
+        .collect()
+}
+
+impl Matcher {
+    /// Whether the tracked file `path` is checked out.
+    fn includes(&self, path: &str) -> bool {
+        match self {
+            Matcher::All => true,
+            Matcher::Cone { recursive, parents } => {
+                let Some((dir, _)) = path.rsplit_once('/') else {
+                    return true;
+                };
+                parents.contains(dir)
+                    || recursive.contains(dir)
+                    || ancestors(dir).iter().any(|ancestor| recursive.contains(ancestor))
+            }
+            // Like git, the last pattern matching the path decides; when none does, its directories are tried from
+            // the innermost.
+            Matcher::Patterns(patterns) => {
+                let mut candidate = Some((path, false));
+                while let Some((current, is_dir)) = candidate {
+                    if let Some(included) = Self::decide(patterns, current, is_dir) {
+                        return included;
+                    }
+                    candidate = current.rsplit_once('/').map(|(dir, _)| (dir, true));
+                }
+                false
+            }
+        }
+    }
+
+    fn decide(patterns: &[SparsePattern], path: &str, is_dir: bool) -> Option<bool> {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let name = path.rsplit('/').next().unwrap_or(path);
+        patterns.iter().rev().find_map(|pattern| {
+            if pattern.directory_only && !is_dir {
+                return None;
+            }
+            let subject = if pattern.anchored { path } else { name };
+            pattern.pattern.matches_with(subject, options).then_some(!pattern.negated)
+        })
+    }
+
+    fn directory_selection(&self, dir: &str) -> SparseSelection {
+        match self {
+            Matcher::All => SparseSelection::Included,
+            Matcher::Cone { recursive, parents } => {
+                if recursive.contains(dir) || ancestors(dir).iter().any(|ancestor| recursive.contains(ancestor)) {
+                    SparseSelection::Included
+                } else if parents.contains(dir) {
+                    SparseSelection::Partial
+                } else {
+                    SparseSelection::Excluded
+                }
+            }
+            // Non-cone patterns select files, a directory counts as included when the patterns take it as a whole.
+            Matcher::Patterns(patterns) => {
+                let mut candidate = Some(dir);
+                while let Some(current) = candidate {
+                    if let Some(included) = Self::decide(patterns, current, true) {
+                        return match included {
+                            true => SparseSelection::Included,
+                            false => SparseSelection::Excluded,
+                        };
+                    }
+                    candidate = current.rsplit_once('/').map(|(parent, _)| parent);
+                }
+                SparseSelection::Excluded
+            }
+        }
+    }
+}
+
+/// Sets the skip-worktree bit of every index entry per `matcher`, removing the files it excludes and checking out
+/// those it includes again.
+fn apply(repo: &Repository, matcher: &Matcher) -> Result<SparseApplyOutcome, SparseError> {
+    let root = repo.workdir().ok_or(SparseError::Bare)?.to_path_buf();
+    let modified = modified_paths(repo)?;
+    let mut index = repo.index()?;
+    let mut outcome = SparseApplyOutcome::default();
+    let mut restore = Vec::new();
+    let mut restore_ids = Vec::new();
+    let mut remove = Vec::new();
+    let mut updated = Vec::new();
+
+    let entries = index.iter().collect::<Vec<_>>();
+    for mut entry in entries {
+        // Conflicted entries stay as they are until resolved.
+        if (entry.flags >> 12) & 0x3 != 0 {
+            continue;
+        }
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let mut flags = IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended);
+        let skipped = flags.is_skip_worktree();
+        match (matcher.includes(&path), skipped) {
+            (true, true) => {
+                flags.remove(IndexEntryExtendedFlag::SKIP_WORKTREE);
+                restore_ids.push(entry.id);
+                restore.push(path);
+            }
+            (false, false) if modified.contains(&path) => {
+                outcome.kept_modified.push(path);
+                continue;
+            }
+            (false, false) => {
+                flags.insert(IndexEntryExtendedFlag::SKIP_WORKTREE);
+                remove.push(path);
+            }
+            _ => continue,
+        }
+        entry.flags_extended = flags.bits();
+        updated.push(entry);
+    }
+    // A partial clone may not have the files to check out yet. Fail before changing anything rather than with
+    // libgit2's bare "object not found", which even adding their index entries back runs into.
+    if let Some(missing) = partial_clone::missing_promised_object(repo, &restore_ids)? {
+        return Err(missing.into());
+    }
+    for entry in &updated {
+        index.add(entry)?;
+    }
+    index.write()?;
+
+    if !restore.is_empty() {
+        check_out(repo, &mut index, &restore)?;
+        outcome.added = restore.len() as u32;
+    }
+    for path in &remove {
+        let full = root.join(path);
+        match fs::remove_file(&full) {
+            Ok(()) => outcome.removed += 1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        remove_empty_parents(&root, &full);
+    }
+    if !outcome.kept_modified.is_empty() {
+        warn!(
+            "Kept {} modified files outside the sparse checkout",
+            outcome.kept_modified.len()
+        );
+    }
+    Ok(outcome)
+}
+
+/// Writes the index version of `paths` to the working tree.
+fn check_out(repo: &Repository, index: &mut Index, paths: &[String]) -> Result<(), git2::Error> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.recreate_missing(true);
+    for path in paths {
+        checkout.path(path.as_str());
+    }
+    repo.checkout_index(Some(index), Some(&mut checkout))
+}
+
+/// Tracked files with local changes in the working tree.
+fn modified_paths(repo: &Repository) -> Result<HashSet<String>, git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).exclude_submodules(true);
+    Ok(repo
+        .statuses(Some(&mut options))?
+        .iter()
+        .filter(|entry| {
+            entry
+                .status()
+                .intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE | Status::WT_RENAMED)
+        })
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+/// Removes the directories left empty above `file`, up to the working tree root.
+fn remove_empty_parents(root: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::status;
+    use crate::git::test_support::{commit_files, init_repo};
+
+    #[test]
+    fn test_cone_patterns_round_trip() {
+        let directories = normalize_directories(&["/apps/web/".into(), "docs".into(), "apps/web/src".into()]).unwrap();
+        assert_eq!(directories, vec!["apps/web".to_string(), "docs".into()]);
+        let patterns = cone_patterns(&directories);
+        assert_eq!(
+            patterns,
+            vec!["/*", "!/*/", "/apps/", "!/apps/*/", "/apps/web/", "/docs/"]
+        );
+        assert_eq!(parse_cone(&patterns), Some(directories.clone()));
+        assert_eq!(parse_cone(&["*.rs".to_string()]), None);
+
+        let matcher = Matcher::Cone {
+            recursive: directories.iter().cloned().collect(),
+            parents: directories.iter().flat_map(|dir| ancestors(dir)).collect(),
+        };
+        assert!(matcher.includes("README.md"));
+        assert!(matcher.includes("apps/package.json"));
+        assert!(matcher.includes("apps/web/src/main.ts"));
+        assert!(!matcher.includes("apps/api/main.go"));
+        assert_eq!(matcher.directory_selection("apps"), SparseSelection::Partial);
+        assert_eq!(matcher.directory_selection("apps/web/src"), SparseSelection::Included);
+        assert_eq!(matcher.directory_selection("libs"), SparseSelection::Excluded);
+
+        let matcher = Matcher::Patterns(parse_patterns(&["/*".into(), "!/libs/".into(), "/libs/core/".into()]));
+        assert!(matcher.includes("README.md"));
+        assert!(!matcher.includes("libs/ui/button.ts"));
+        assert!(matcher.includes("libs/core/lib.rs"));
+    }
+
+    #[test]
+    fn test_applies_and_disables_sparse_checkout() {
+        let repo = init_repo("sparse");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let files = ["README", "apps/web/main.ts", "apps/api/main.go", "docs/guide.md"];
+        commit_files(&repo, &files.map(|file| (file, file)), "files");
+        fs::write(dir.join("docs/guide.md"), "local change").unwrap();
+
+        let outcome = set_sparse_checkout(&repo, &SparseSpec::Cone(vec!["apps/web".into()])).unwrap();
+        assert_eq!(outcome.removed, 1);
+        assert_eq!(outcome.kept_modified, vec!["docs/guide.md".to_string()]);
+        assert!(!dir.join("apps/api").exists());
+        assert!(dir.join("apps/web/main.ts").exists());
+        let sparse = read_sparse_checkout(&repo).unwrap();
+        assert!(sparse.enabled && sparse.cone);
+        assert_eq!(sparse.directories, vec!["apps/web".to_string()]);
+        assert_eq!(
+            excluded_paths(&repo, 10).unwrap().paths,
+            vec!["apps/api/main.go".to_string()]
+        );
+        let statuses = status::file_statuses(&repo).unwrap();
+        assert_eq!(
+            statuses.iter().map(|status| status.path.as_path()).collect::<Vec<_>>(),
+            vec![Path::new("docs/guide.md")]
+        );
+
+        let selections = sparse_directories(&repo, "apps")
+            .unwrap()
+            .into_iter()
+            .map(|dir| (dir.name, dir.selection))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selections,
+            vec![
+                ("api".to_string(), SparseSelection::Excluded),
+                ("web".to_string(), SparseSelection::Included)
+            ]
+        );
+
+        let outcome = disable_sparse_checkout(&repo).unwrap();
+        assert_eq!(outcome.added, 1);
+        assert_eq!(
+            fs::read_to_string(dir.join("apps/api/main.go")).unwrap(),
+            "apps/api/main.go"
+        );
+        assert_eq!(excluded_paths(&repo, 10).unwrap().total, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_checkouts_keep_sparse_checkout() {
+        use crate::git::branch;
+        use crate::git::index_cache::GitIndexCache;
+        use crate::git::journal::{self, OperationKind};
+        use crate::git::lfs::{self, LfsDiffRange};
+        use crate::git::reflog::{self, ResetMode};
+
+        let repo = init_repo("sparse_checkout");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let commit = |message: &str| {
+            commit_files(
+                &repo,
+                &[("apps/web/main.ts", message), ("apps/api/main.go", message)],
+                message,
+            )
+        };
+        let base = commit("base");
+        let main = repo.head().unwrap().name().unwrap().to_string();
+        repo.branch("other", &repo.find_commit(base).unwrap(), false).unwrap();
+        repo.set_head("refs/heads/other").unwrap();
+        commit("other");
+        repo.set_head(&main).unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force())).unwrap();
+        set_sparse_checkout(&repo, &SparseSpec::Cone(vec!["apps/web".into()])).unwrap();
+
+        let assert_sparse = |content: &str| {
+            assert_eq!(fs::read_to_string(dir.join("apps/web/main.ts")).unwrap(), content);
+            assert!(!dir.join("apps/api").exists());
+            assert_eq!(
+                excluded_paths(&repo, 10).unwrap().paths,
+                vec!["apps/api/main.go".to_string()]
+            );
+            assert!(status::file_statuses(&repo).unwrap().is_empty());
+            assert!(!status::status_summary(&repo).unwrap().is_dirty());
+            assert!(lfs::lfs_diffs(&repo, &LfsDiffRange::Unstaged, None).unwrap().is_empty());
+        };
+
+        let cache = GitIndexCache::open(&dir).unwrap();
+        journal::record(&repo, Some(&cache), OperationKind::Checkout, "checkout", true, || {
+            branch::switch_branch(&repo, "other")
+        })
+        .unwrap();
+        assert_sparse("other");
+        journal::undo_last(&repo, &cache).unwrap();
+        assert_sparse("base");
+        journal::redo(&repo, &cache).unwrap();
+        assert_sparse("other");
+
+        reflog::reset_to_entry(&repo, "HEAD", 1, ResetMode::Hard).unwrap();
+        assert_sparse("base");
+        reflog::reset_to_entry(&repo, "HEAD", 1, ResetMode::Mixed).unwrap();
+        assert_eq!(excluded_paths(&repo, 10).unwrap().total, 1);
+
+        drop(cache);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sparse_checkout_is_per_worktree() {
+        let dir = std::env::temp_dir().join("gitultra_sparse_worktree_test");
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(dir.join("main")).unwrap();
+        let files = [
+            ("README", "readme"),
+            ("apps/web/main.ts", "web"),
+            ("apps/api/main.go", "api"),
+        ];
+        commit_files(&repo, &files, "files");
+        repo.worktree("linked", &dir.join("linked"), None).unwrap();
+        let linked = Repository::open(dir.join("linked")).unwrap();
+
+        set_sparse_checkout(&linked, &SparseSpec::Cone(vec!["apps/web".into()])).unwrap();
+        assert!(!dir.join("linked/apps/api").exists());
+        assert!(read_sparse_checkout(&linked).unwrap().enabled);
+        assert!(
+            read_sparse_checkout(&Repository::open(dir.join("linked")).unwrap())
+                .unwrap()
+                .enabled
+        );
+
+        // The main worktree, and the config both share, are left alone.
+        assert!(!read_sparse_checkout(&repo).unwrap().enabled);
+        assert!(
+            !read_sparse_checkout(&Repository::open(dir.join("main")).unwrap())
+                .unwrap()
+                .enabled
+        );
+        let shared = Config::open(&dir.join("main/.git/config")).unwrap();
+        assert_eq!(config_bool(&shared, "core.sparseCheckout").unwrap(), None);
+        assert_eq!(config_bool(&shared, "extensions.worktreeConfig").unwrap(), Some(true));
+        assert!(dir.join("main/apps/api/main.go").exists());
+
+        disable_sparse_checkout(&linked).unwrap();
+        assert!(!read_sparse_checkout(&linked).unwrap().enabled);
+        assert!(dir.join("linked/apps/api/main.go").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_promised_files_keep_sparse_checkout() {
+        let repo = init_repo("sparse_partial_clone");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        commit_files(
+            &repo,
+            &[("apps/web/main.ts", "web"), ("apps/api/main.go", "api")],
+            "files",
+        );
+        set_sparse_checkout(&repo, &SparseSpec::Cone(vec!["apps/web".into()])).unwrap();
+
+        // Like a blobless clone that never needed the file.
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        let hex = tree.get_path(Path::new("apps/api/main.go")).unwrap().id().to_string();
+        fs::remove_file(repo.path().join("objects").join(&hex[..2]).join(&hex[2..])).unwrap();
+        repo.remote("origin", "https://example.com/repo.git").unwrap();
+        repo.config().unwrap().set_bool("remote.origin.promisor", true).unwrap();
+        // Opened again, as the first handle may have the object cached.
+        let repo = Repository::open(&dir).unwrap();
+
+        let error = disable_sparse_checkout(&repo).unwrap_err();
+        assert!(
+            matches!(&error, SparseError::MissingObject(missing) if missing.oid == hex && missing.remote == "origin"),
+            "{}",
+            error
+        );
+        assert!(read_sparse_checkout(&repo).unwrap().enabled);
+        assert_eq!(
+            excluded_paths(&repo, 10).unwrap().paths,
+            vec!["apps/api/main.go".to_string()]
+        );
+        assert!(!dir.join("apps/api").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
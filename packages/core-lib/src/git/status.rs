@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+use git2::{BranchType, Repository, Status, StatusOptions};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::history::ChangeStatus;
+use super::lfs::{self, LfsInfo, LfsStore};
+use super::sparse::sparse_status;
+
+/// Condensed working tree and branch state of a repository.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct StatusSummary {
+    /// Short name of the checked out branch, `None` when HEAD is detached or unborn.
+    pub branch: Option<String>,
+    pub head_oid: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub staged: u32,
+    pub unstaged: u32,
+    pub untracked: u32,
+    pub conflicted: u32,
+}
+
+impl StatusSummary {
+    pub fn is_dirty(&self) -> bool {
+        self.staged + self.unstaged + self.untracked + self.conflicted > 0
+    }
+}
+
+/// A path that differs between HEAD, the index and the working tree.
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct FileStatus {
+    pub path: PathBuf,
+    /// How the index differs from HEAD.
+    pub staged: Option<ChangeStatus>,
+    /// How the working tree differs from the index, `Added` for untracked files.
+    pub unstaged: Option<ChangeStatus>,
+    pub conflicted: bool,
+    /// The LFS object in the index. Working tree files are not hashed to describe theirs, that is slow for large files.
+    pub lfs: Option<LfsInfo>,
+}
+
+pub fn status_summary(repo: &Repository) -> Result<StatusSummary, git2::Error> {
+    let mut summary = StatusSummary::default();
+
+    if let Ok(head) = repo.head() {
+        summary.head_oid = head.target().map(|oid| oid.to_string());
+        if head.is_branch() {
+            summary.branch = head.shorthand().map(str::to_string);
+        }
+    }
+
+    if let Some(branch_name) = &summary.branch {
+        let branch = repo.find_branch(branch_name, BranchType::Local)?;
+        if let Ok(upstream) = branch.upstream() {
+            summary.upstream = upstream.name()?.map(str::to_string);
+            if let (Some(local), Some(remote)) = (branch.get().target(), upstream.get().target()) {
+                let (ahead, behind) = repo.graph_ahead_behind(local, remote)?;
+                summary.ahead = ahead as u32;
+                summary.behind = behind as u32;
+            }
+        }
+    }
+
+    if repo.is_bare() {
+        return Ok(summary);
+    }
+
+    let index = repo.index()?;
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).exclude_submodules(true);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let status = sparse_status(&index, &entry);
+        if status.is_conflicted() {
+            summary.conflicted += 1;
+            continue;
+        }
+        if status.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        ) {
+            summary.staged += 1;
+        }
+        if status.intersects(Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE) {
+            summary.unstaged += 1;
+        }
+        if status.is_wt_new() {
+            summary.untracked += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Lists the changed paths, like `git status`, describing LFS files by their objects.
+///
+/// LFS files whose smudged working tree content matches the pointer in the index are left out: they only look
+/// modified because libgit2 does not run the LFS filter.
+pub fn file_statuses(repo: &Repository) -> Result<Vec<FileStatus>, git2::Error> {
+    if repo.is_bare() {
+        return Ok(Vec::new());
+    }
+    let store = LfsStore::open(repo)?;
+    let index = repo.index()?;
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .exclude_submodules(true);
+    let mut files = Vec::new();
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let Some(path) = entry.path().map(PathBuf::from) else {
+            continue;
+        };
+        let status = sparse_status(&index, &entry);
+        let index_pointer = match index.get_path(&path, 0) {
+            Some(index_entry) => lfs::blob_pointer(repo, index_entry.id)?,
+            None => None,
+        };
+        let mut unstaged = unstaged_change(status);
+        // Unreadable files are reported as they are.
+        if let (true, Some(pointer)) = (status.is_wt_modified(), &index_pointer) {
+            if lfs::worktree_matches(repo, &path, pointer).unwrap_or(false) {
+                unstaged = None;
+            }
+        }
+        let staged = staged_change(status);
+        let conflicted = status.is_conflicted();
+        if staged.is_none() && unstaged.is_none() && !conflicted {
+            continue;
+        }
+        files.push(FileStatus {
+            lfs: index_pointer.map(|pointer| store.info(&pointer)),
+            path,
+            staged,
+            unstaged,
+            conflicted,
+        });
+    }
+    Ok(files)
+}
+
+fn staged_change(status: Status) -> Option<ChangeStatus> {
+    if status.is_index_new() {
+        Some(ChangeStatus::Added)
+    } else if status.is_index_modified() {
+        Some(ChangeStatus::Modified)
+    } else if status.is_index_deleted() {
+        Some(ChangeStatus::Deleted)
+    } else if status.is_index_renamed() {
+        Some(ChangeStatus::Renamed)
+    } else if status.is_index_typechange() {
+        Some(ChangeStatus::TypeChanged)
+    } else {
+        None
+    }
+}
+
+fn unstaged_change(status: Status) -> Option<ChangeStatus> {
+    if status.is_wt_new() {
+        Some(ChangeStatus::Added)
+    } else if status.is_wt_modified() {
+        Some(ChangeStatus::Modified)
+    } else if status.is_wt_deleted() {
+        Some(ChangeStatus::Deleted)
+    } else if status.is_wt_renamed() {
+        Some(ChangeStatus::Renamed)
+    } else if status.is_wt_typechange() {
+        Some(ChangeStatus::TypeChanged)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{commit_files, init_repo};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_status_summary() {
+        let repo = init_repo("status");
+        let dir = repo.workdir().unwrap().to_path_buf();
+        let base = commit_files(&repo, &[("a.txt", "a"), ("b.txt", "b")], "base");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        // An upstream one commit behind and one commit ahead of the branch.
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let base_commit = repo.find_commit(base).unwrap();
+        let remote = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                "remote",
+                &base_commit.tree().unwrap(),
+                &[&base_commit],
+            )
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", remote, false, "test").unwrap();
+        repo.remote("origin", "https://example.com/repo.git").unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str(&format!("branch.{}.remote", branch), "origin").unwrap();
+        config.set_str(&format!("branch.{}.merge", branch), "refs/heads/main").unwrap();
+        let head = commit_files(&repo, &[("a.txt", "a2")], "local");
+
+        assert!(!status_summary(&repo).unwrap().is_dirty());
+
+        fs::write(dir.join("a.txt"), "a3").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.join("b.txt"), "b2").unwrap();
+        fs::write(dir.join("c.txt"), "c").unwrap();
+
+        let summary = status_summary(&repo).unwrap();
+        assert_eq!(summary.branch.as_deref(), Some(branch.as_str()));
+        assert_eq!(summary.head_oid, Some(head.to_string()));
+        assert_eq!(summary.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((summary.ahead, summary.behind), (1, 1));
+        assert_eq!((summary.staged, summary.unstaged, summary.untracked), (1, 1, 1));
+        assert_eq!(summary.conflicted, 0);
+        assert!(summary.is_dirty());
+
+        repo.set_head_detached(head).unwrap();
+        let summary = status_summary(&repo).unwrap();
+        assert_eq!(summary.branch, None);
+        assert_eq!(summary.upstream, None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use git2::{FetchOptions, Repository, Submodule, SubmoduleIgnore, SubmoduleStatus};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::remote::{remote_callbacks, FetchProgress};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct SubmoduleInfo {
+    pub name: String,
+    /// Path relative to the working directory of the superproject.
+    pub path: PathBuf,
+    pub url: Option<String>,
+    pub branch: Option<String>,
+    /// Commit recorded in the superproject's HEAD.
+    pub head_oid: Option<String>,
+    /// Commit staged in the superproject's index.
+    pub index_oid: Option<String>,
+    /// Commit checked out in the submodule.
+    pub workdir_oid: Option<String>,
+    /// Whether the URL is registered in `.git/config`, as done by `git submodule init`.
+    pub initialized: bool,
+    pub checked_out: bool,
+    /// The checked out commit differs from the one staged in the superproject.
+    pub commit_changed: bool,
+    /// The submodule has staged, modified or untracked files.
+    pub dirty: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct SubmoduleUpdateOptions {
+    /// Paths of the submodules to update, all of them when empty.
+    pub paths: Vec<PathBuf>,
+    /// Initialize submodules that are not yet, like `git submodule update --init`.
+    pub init: bool,
+    /// Also update the submodules of updated submodules.
+    pub recursive: bool,
+}
+
+pub fn list_submodules(repo: &Repository) -> Result<Vec<SubmoduleInfo>, git2::Error> {
+    repo.submodules()?
+        .iter()
+        .map(|submodule| {
+            let name = submodule
+                .name()
+                .ok_or_else(|| git2::Error::from_str("submodule name is not valid utf-8"))?;
+            let status = repo.submodule_status(name, SubmoduleIgnore::None)?;
+            Ok(SubmoduleInfo {
+                name: name.to_string(),
+                path: submodule.path().to_path_buf(),
+                url: submodule.url().map(str::to_string),
+                branch: submodule.branch().map(str::to_string),
+                head_oid: submodule.head_id().map(|oid| oid.to_string()),
+                index_oid: submodule.index_id().map(|oid| oid.to_string()),
+                workdir_oid: submodule.workdir_id().map(|oid| oid.to_string()),
+                initialized: is_initialized(repo, submodule)?,
+                checked_out: status.contains(SubmoduleStatus::IN_WD)
+                    && !status.contains(SubmoduleStatus::WD_UNINITIALIZED),
+                commit_changed: status.contains(SubmoduleStatus::WD_MODIFIED),
+                dirty: status.intersects(
+                    SubmoduleStatus::WD_INDEX_MODIFIED
+                        | SubmoduleStatus::WD_WD_MODIFIED
+                        | SubmoduleStatus::WD_UNTRACKED,
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Registers the URLs of the submodules at `paths` (all when empty) in `.git/config`. Returns the initialized paths.
+pub fn init_submodules(repo: &Repository, paths: &[PathBuf]) -> Result<Vec<PathBuf>, git2::Error> {
+    let mut initialized = Vec::new();
+    for mut submodule in repo.submodules()? {
+        if !paths.is_empty() && !paths.iter().any(|path| path == submodule.path()) {
+            continue;
+        }
+        submodule.init(false)?;
+        initialized.push(submodule.path().to_path_buf());
+    }
+    Ok(initialized)
+}
+
+/// Clones missing submodules and checks out the commits recorded in the superproject, like `git submodule update`.
+///
+/// `on_progress` reports the transfer of each submodule, named by its path; returning `false` cancels the update.
+/// Returns the updated paths, nested ones relative to the top-level superproject.
+pub fn update_submodules(
+    repo: &Repository,
+    options: &SubmoduleUpdateOptions,
+    mut on_progress: impl FnMut(FetchProgress) -> bool,
+) -> Result<Vec<PathBuf>, git2::Error> {
+    let mut updated = Vec::new();
+    update_in(repo, options, Path::new(""), &mut on_progress, &mut updated)?;
+    Ok(updated)
+}
+
+/// Copies the submodule URLs from `.gitmodules` to the configuration, like `git submodule sync`.
+///
+/// Returns the synced paths, nested ones relative to the top-level superproject.
+pub fn sync_submodules(repo: &Repository, recursive: bool) -> Result<Vec<PathBuf>, git2::Error> {
+    let mut synced = Vec::new();
+    sync_in(repo, recursive, Path::new(""), &mut synced)?;
+    Ok(synced)
+}
+
+fn update_in(
+    repo: &Repository,
+    options: &SubmoduleUpdateOptions,
+    prefix: &Path,
+    on_progress: &mut dyn FnMut(FetchProgress) -> bool,
+    updated: &mut Vec<PathBuf>,
+) -> Result<(), git2::Error> {
+    for mut submodule in repo.submodules()? {
+        // The path filter only applies to the top-level superproject.
+        let selected = !prefix.as_os_str().is_empty()
+            || options.paths.is_empty()
+            || options.paths.iter().any(|path| path == submodule.path());
+        if !selected || !(options.init || is_initialized(repo, &submodule)?) {
+            continue;
+        }
+
+        let path = prefix.join(submodule.path());
+        {
+            let label = path.to_string_lossy().into_owned();
+            let mut callbacks = remote_callbacks(repo.config().ok());
+            callbacks.transfer_progress(|stats| {
+                on_progress(FetchProgress {
+                    remote: label.clone(),
+                    total_objects: stats.total_objects() as u32,
+                    received_objects: stats.received_objects() as u32,
+                    indexed_objects: stats.indexed_objects() as u32,
+                    received_bytes: stats.received_bytes() as u64,
+                })
+            });
+            let mut fetch = FetchOptions::new();
+            fetch.remote_callbacks(callbacks);
+            let mut update = git2::SubmoduleUpdateOptions::new();
+            update.fetch(fetch);
+            submodule.update(options.init, Some(&mut update))?;
+        }
+        updated.push(path.clone());
+
+        if options.recursive {
+            update_in(&submodule.open()?, options, &path, on_progress, updated)?;
+        }
+    }
+    Ok(())
+}
+
+fn sync_in(repo: &Repository, recursive: bool, prefix: &Path, synced: &mut Vec<PathBuf>) -> Result<(), git2::Error> {
+    for mut submodule in repo.submodules()? {
+        if !is_initialized(repo, &submodule)? {
+            continue;
+        }
+        let path = prefix.join(submodule.path());
+        submodule.sync()?;
+        synced.push(path.clone());
+        if recursive {
+            if let Ok(nested) = submodule.open() {
+                sync_in(&nested, recursive, &path, synced)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `submodule.<name>.url` is set in the configuration. `SubmoduleStatus::IN_CONFIG` only means the
+/// submodule is listed in `.gitmodules`.
+fn is_initialized(repo: &Repository, submodule: &Submodule) -> Result<bool, git2::Error> {
+    let Some(name) = submodule.name() else {
+        return Ok(false);
+    };
+    Ok(repo.config()?.get_entry(&format!("submodule.{}.url", name)).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::commit_files;
+    use std::fs;
+
+    #[test]
+    fn test_list_init_and_update() {
+        let temp_dir = std::env::temp_dir().join("gitultra_submodule_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let library = Repository::init(temp_dir.join("library")).unwrap();
+        let library_head = commit_files(&library, &[("lib.txt", "lib\n")], "library");
+
+        let superproject = Repository::init(temp_dir.join("super")).unwrap();
+        let url = temp_dir.join("library").to_string_lossy().into_owned();
+        let mut submodule = superproject.submodule(&url, Path::new("libs/library"), true).unwrap();
+        submodule.clone(None).unwrap();
+        submodule.add_finalize().unwrap();
+        commit_files(&superproject, &[], "add library");
+
+        // A fresh clone has the submodule listed but neither initialized nor checked out.
+        let clone = git2::build::RepoBuilder::new()
+            .clone(&temp_dir.join("super").to_string_lossy(), &temp_dir.join("clone"))
+            .unwrap();
+        let listed = list_submodules(&clone).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, PathBuf::from("libs/library"));
+        assert_eq!(listed[0].url.as_deref(), Some(url.as_str()));
+        assert_eq!(listed[0].head_oid, Some(library_head.to_string()));
+        assert!(!listed[0].initialized && !listed[0].checked_out);
+
+        // Without init, update skips uninitialized submodules.
+        let options = SubmoduleUpdateOptions::default();
+        assert!(update_submodules(&clone, &options, |_| true).unwrap().is_empty());
+
+        let initialized = init_submodules(&clone, &[]).unwrap();
+        assert_eq!(initialized, vec![PathBuf::from("libs/library")]);
+        assert!(list_submodules(&clone).unwrap()[0].initialized);
+
+        let updated = update_submodules(&clone, &options, |_| true).unwrap();
+        assert_eq!(updated, vec![PathBuf::from("libs/library")]);
+        let listed = list_submodules(&clone).unwrap();
+        assert!(listed[0].checked_out);
+        assert!(!listed[0].commit_changed && !listed[0].dirty);
+        assert_eq!(listed[0].workdir_oid, Some(library_head.to_string()));
+        assert!(temp_dir.join("clone/libs/library/lib.txt").exists());
+
+        fs::write(temp_dir.join("clone/libs/library/lib.txt"), "changed\n").unwrap();
+        assert!(list_submodules(&clone).unwrap()[0].dirty);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,40 @@
+use std::fs;
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+
+/// Creates an empty repository in `gitultra_<name>_test` under the temp dir, removing what an earlier run left there.
+pub(crate) fn init_repo(name: &str) -> Repository {
+    let dir = std::env::temp_dir().join(format!("gitultra_{}_test", name));
+    let _ = fs::remove_dir_all(&dir);
+    Repository::init(&dir).unwrap()
+}
+
+/// Writes `files` into the working tree, stages every change including deletions and commits on top of HEAD.
+pub(crate) fn commit_files(repo: &Repository, files: &[(&str, &str)], message: &str) -> Oid {
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    commit_files_as(repo, &signature, &signature, files, message)
+}
+
+/// Like [`commit_files`], with an explicit author and committer.
+pub(crate) fn commit_files_as(
+    repo: &Repository,
+    author: &Signature,
+    committer: &Signature,
+    files: &[(&str, &str)],
+    message: &str,
+) -> Oid {
+    let workdir = repo.workdir().unwrap();
+    for (name, content) in files {
+        let path = workdir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+    index.update_all(["*"], None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some("HEAD"), author, committer, message, &tree, &parents).unwrap()
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use git2::Repository;
+use log::{debug, error, info};
+use notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::index_cache::GitIndexCache;
+
+/// How long the watcher waits for a burst of filesystem events to settle.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Upper bound of worktree paths reported with a single change; beyond that views should refresh fully.
+const MAX_REPORTED_PATHS: usize = 256;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Type)]
+pub enum ChangeKind {
+    /// Files in the working directory changed.
+    Worktree,
+    /// `.git/index` was written, e.g. by staging.
+    Index,
+    /// HEAD, branches, tags or packed-refs moved.
+    Refs,
+    Config,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+pub struct RepoChange {
+    pub kinds: Vec<ChangeKind>,
+    /// Changed worktree paths relative to the working directory.
+    pub paths: Vec<PathBuf>,
+    /// More worktree paths changed than are listed in `paths`.
+    pub truncated: bool,
+}
+
+/// Watches a repository's worktree and git directory, reporting debounced, classified changes.
+///
+/// Watching stops when the watcher is dropped.
+pub struct RepoWatcher {
+    _debouncer: Debouncer<notify::RecommendedWatcher>,
+}
+
+impl RepoWatcher {
+    pub fn new(
+        repo_path: &Path,
+        index_cache: Option<Arc<GitIndexCache>>,
+        on_change: impl Fn(RepoChange) + Send + 'static,
+    ) -> Result<Self, WatchError> {
+        Self::with_debounce(repo_path, DEFAULT_DEBOUNCE, index_cache, on_change)
+    }
+
+    pub fn with_debounce(
+        repo_path: &Path,
+        debounce: Duration,
+        index_cache: Option<Arc<GitIndexCache>>,
+        on_change: impl Fn(RepoChange) + Send + 'static,
+    ) -> Result<Self, WatchError> {
+        let repo = Repository::open(repo_path)?;
+        let layout = Layout::of(&repo);
+        let mut roots = vec![];
+        if let Some(workdir) = &layout.workdir {
+            roots.push(workdir.clone());
+        }
+        for dir in [&layout.git_dir, &layout.common_dir] {
+            if !roots.iter().any(|root| dir.starts_with(root)) {
+                roots.push(dir.clone());
+            }
+        }
+
+        let mut debouncer = new_debouncer(debounce, move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                let change = layout.classify(&repo, events.iter().map(|e| e.path.as_path()));
+                if change.kinds.is_empty() {
+                    return;
+                }
+                if change.kinds.contains(&ChangeKind::Index) {
+                    if let Some(cache) = &index_cache {
+                        if let Err(e) = cache.invalidate() {
+                            error!("Failed to invalidate index cache: {}", e);
+                        }
+                    }
+                }
+                debug!("Repository change: {:?}", change.kinds);
+                on_change(change);
+            }
+            Err(e) => error!("Watch error: {:?}", e),
+        })?;
+
+        for root in &roots {
+            info!("Watching {:?}", root);
+            debouncer.watcher().watch(root, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self { _debouncer: debouncer })
+    }
+}
+
+/// Where the parts of a repository live on disk. Linked worktrees keep refs in the common dir.
+struct Layout {
+    workdir: Option<PathBuf>,
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+}
+
+impl Layout {
+    fn of(repo: &Repository) -> Self {
+        let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        Self {
+            workdir: repo.workdir().map(canonical),
+            git_dir: canonical(repo.path()),
+            common_dir: canonical(repo.commondir()),
+        }
+    }
+
+    fn classify<'a>(&self, repo: &Repository, paths: impl Iterator<Item = &'a Path>) -> RepoChange {
+        let mut kinds = BTreeSet::new();
+        let mut changed = BTreeSet::new();
+
+        for path in paths {
+            if let Some(kind) = self.classify_git_path(path) {
+                kinds.extend(kind);
+                continue;
+            }
+            let Some(relative) = self.workdir.as_ref().and_then(|w| path.strip_prefix(w).ok()) else {
+                continue;
+            };
+            if relative.as_os_str().is_empty() || repo.status_should_ignore(relative).unwrap_or(false) {
+                continue;
+            }
+            kinds.insert(ChangeKind::Worktree);
+            changed.insert(relative.to_path_buf());
+        }
+
+        let truncated = changed.len() > MAX_REPORTED_PATHS;
+        RepoChange {
+            kinds: kinds.into_iter().collect(),
+            paths: changed.into_iter().take(MAX_REPORTED_PATHS).collect(),
+            truncated,
+        }
+    }
+
+    /// Returns `Some` for paths inside the git directories, with the change kind if it is one we report.
+    fn classify_git_path(&self, path: &Path) -> Option<Option<ChangeKind>> {
+        let relative = path
+            .strip_prefix(&self.git_dir)
+            .or_else(|_| path.strip_prefix(&self.common_dir))
+            .ok()?;
+        let name = relative.to_string_lossy().replace('\\', "/");
+
+        let kind = match name.as_str() {
+            _ if name.ends_with(".lock") || name.starts_with("gitultra_") => None,
+            "index" => Some(ChangeKind::Index),
+            "HEAD" | "ORIG_HEAD" | "FETCH_HEAD" | "MERGE_HEAD" | "packed-refs" => Some(ChangeKind::Refs),
+            _ if name.starts_with("refs/") => Some(ChangeKind::Refs),
+            "config" | "config.worktree" => Some(ChangeKind::Config),
+            // objects, logs and other bookkeeping do not change what the views show on their own.
+            _ => None,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("Watch error: {0}")]
+    Notify(#[from] notify::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_paths() {
+        let temp_dir = std::env::temp_dir().join("gitultra_watcher_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let repo = Repository::init(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join(".gitignore"), "ignored.log\n").unwrap();
+
+        let layout = Layout::of(&repo);
+        let workdir = layout.workdir.clone().unwrap();
+        let paths = [
+            workdir.join("src/main.rs"),
+            workdir.join("ignored.log"),
+            layout.git_dir.join("index"),
+            layout.git_dir.join("index.lock"),
+            layout.git_dir.join("refs/heads/main"),
+            layout.git_dir.join("objects/ab/cdef"),
+        ];
+
+        let change = layout.classify(&repo, paths.iter().map(|p| p.as_path()));
+
+        assert_eq!(
+            change.kinds,
+            vec![ChangeKind::Worktree, ChangeKind::Index, ChangeKind::Refs]
+        );
+        assert_eq!(change.paths, vec![PathBuf::from("src/main.rs")]);
+        assert!(!change.truncated);
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::{Path, PathBuf};
+
+use git2::{
+    BranchType, Repository, StatusOptions, Worktree, WorktreeAddOptions, WorktreeLockStatus, WorktreePruneOptions,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::sparse::sparse_status;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct WorktreeInfo {
+    pub name: String,
+    pub path: PathBuf,
+    /// Short name of the checked out branch, `None` when detached or unreadable.
+    pub branch: Option<String>,
+    pub head_oid: Option<String>,
+    pub locked: bool,
+    pub lock_reason: Option<String>,
+    /// The working directory is gone and the worktree is not locked, so [`prune_worktrees`] would remove it.
+    pub prunable: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Type)]
+#[serde(default)]
+pub struct AddWorktreeOptions {
+    /// Branch to check out. Without one, a branch named after the worktree is created from HEAD, like
+    /// `git worktree add <path>`.
+    pub branch: Option<String>,
+    /// Create `branch` instead of checking out an existing one. Requires `branch`.
+    pub create_branch: bool,
+    /// Revision the created branch starts at, HEAD by default.
+    pub start_point: Option<String>,
+    /// Lock the worktree right away, e.g. when it lives on removable media.
+    pub lock: bool,
+}
+
+/// Lists the linked worktrees of the repository. The main working directory is not included.
+pub fn list_worktrees(repo: &Repository) -> Result<Vec<WorktreeInfo>, git2::Error> {
+    repo.worktrees()?
+        .iter()
+        .flatten()
+        .map(|name| worktree_info(&repo.find_worktree(name)?))
+        .collect()
+}
+
+/// Adds a linked worktree at `path`, like `git worktree add`.
+///
+/// A branch created for it is deleted again when the worktree cannot be added.
+pub fn add_worktree(
+    repo: &Repository,
+    name: &str,
+    path: &Path,
+    options: &AddWorktreeOptions,
+) -> Result<WorktreeInfo, git2::Error> {
+    let branch = match (&options.branch, options.create_branch) {
+        (Some(branch), true) => {
+            let start = repo.revparse_single(options.start_point.as_deref().unwrap_or("HEAD"))?;
+            Some(repo.branch(branch, &start.peel_to_commit()?, false)?)
+        }
+        (Some(branch), false) => Some(repo.find_branch(branch, BranchType::Local)?),
+        (None, true) => return Err(git2::Error::from_str("create_branch requires a branch name")),
+        (None, false) => None,
+    };
+
+    let mut add = WorktreeAddOptions::new();
+    if let Some(branch) = &branch {
+        add.reference(Some(branch.get()));
+    }
+    add.lock(options.lock);
+    let worktree = match repo.worktree(name, path, Some(&add)) {
+        Ok(worktree) => worktree,
+        Err(e) => {
+            if let (Some(mut branch), true) = (branch, options.create_branch) {
+                if let Err(delete_error) = branch.delete() {
+                    warn!(
+                        "Failed to delete branch created for worktree {}: {}",
+                        name, delete_error
+                    );
+                }
+            }
+            return Err(e);
+        }
+    };
+    worktree_info(&worktree)
+}
+
+pub fn lock_worktree(repo: &Repository, name: &str, reason: Option<&str>) -> Result<(), git2::Error> {
+    repo.find_worktree(name)?.lock(reason)
+}
+
+pub fn unlock_worktree(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    repo.find_worktree(name)?.unlock()
+}
+
+/// Removes the administrative files of worktrees whose working directory is gone, like `git worktree prune`.
+///
+/// Locked worktrees are kept. Returns the names of the pruned worktrees.
+pub fn prune_worktrees(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut pruned = Vec::new();
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = repo.find_worktree(name)?;
+        if worktree.is_prunable(None)? {
+            worktree.prune(None)?;
+            pruned.push(name.to_string());
+        }
+    }
+    Ok(pruned)
+}
+
+/// Deletes the worktree and its working directory, like `git worktree remove`.
+///
+/// Without `force`, worktrees that are locked or have uncommitted or untracked changes are refused.
+pub fn remove_worktree(repo: &Repository, name: &str, force: bool) -> Result<(), git2::Error> {
+    let worktree = repo.find_worktree(name)?;
+    if !force {
+        if let WorktreeLockStatus::Locked(_) = worktree.is_locked()? {
+            return Err(git2::Error::from_str(&format!("worktree {} is locked", name)));
+        }
+        if worktree.validate().is_ok() {
+            let linked = Repository::open_from_worktree(&worktree)?;
+            let index = linked.index()?;
+            let mut options = StatusOptions::new();
+            options.include_untracked(true);
+            // Files left out by a sparse checkout are not changes.
+            let changed = linked
+                .statuses(Some(&mut options))?
+                .iter()
+                .any(|entry| !sparse_status(&index, &entry).is_empty());
+            if changed {
+                return Err(git2::Error::from_str(&format!("worktree {} has changes", name)));
+            }
+        }
+    }
+    worktree.prune(Some(
+        WorktreePruneOptions::new().valid(true).locked(force).working_tree(true),
+    ))
+}
+
+/// The working directory of the main repository when `repo` is a linked worktree.
+pub fn main_repo_path(repo: &Repository) -> Option<PathBuf> {
+    if !repo.is_worktree() {
+        return None;
+    }
+    let main = Repository::open(repo.commondir()).ok()?;
+    Some(main.workdir().unwrap_or(main.path()).to_path_buf())
+}
+
+fn worktree_info(worktree: &Worktree) -> Result<WorktreeInfo, git2::Error> {
+    let lock_reason = match worktree.is_locked()? {
+        WorktreeLockStatus::Locked(reason) => Some(reason),
+        WorktreeLockStatus::Unlocked => None,
+    };
+    let head = Repository::open_from_worktree(worktree).and_then(|repo| {
+        let head = repo.head()?;
+        let branch = head.is_branch().then(|| head.shorthand().map(str::to_string)).flatten();
+        Ok((branch, head.target().map(|oid| oid.to_string())))
+    });
+    let (branch, head_oid) = head.unwrap_or_default();
+
+    Ok(WorktreeInfo {
+        name: worktree.name().unwrap_or_default().to_string(),
+        path: worktree.path().to_path_buf(),
+        branch,
+        head_oid,
+        locked: lock_reason.is_some(),
+        lock_reason: lock_reason.flatten(),
+        prunable: worktree.is_prunable(None)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::commit_files;
+    use std::fs;
+
+    #[test]
+    fn test_add_lock_and_prune_worktrees() {
+        let temp_dir = std::env::temp_dir().join("gitultra_worktree_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let repo = Repository::init(temp_dir.join("main")).unwrap();
+        commit_files(&repo, &[], "init");
+
+        let options = AddWorktreeOptions {
+            branch: Some("feature".to_string()),
+            create_branch: true,
+            ..Default::default()
+        };
+        let added = add_worktree(&repo, "feature", &temp_dir.join("feature"), &options).unwrap();
+        assert_eq!(added.branch.as_deref(), Some("feature"));
+        let worktree = Repository::open(temp_dir.join("feature")).unwrap();
+        assert_eq!(main_repo_path(&worktree), Some(temp_dir.join("main")));
+
+        lock_worktree(&repo, "feature", Some("on a usb stick")).unwrap();
+        fs::remove_dir_all(temp_dir.join("feature")).unwrap();
+        let listed = list_worktrees(&repo).unwrap();
+        assert_eq!(listed[0].lock_reason.as_deref(), Some("on a usb stick"));
+        assert!(!listed[0].prunable);
+        assert!(prune_worktrees(&repo).unwrap().is_empty());
+
+        unlock_worktree(&repo, "feature").unwrap();
+        assert_eq!(prune_worktrees(&repo).unwrap(), vec!["feature".to_string()]);
+        assert!(list_worktrees(&repo).unwrap().is_empty());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_worktree_failure_keeps_no_branch() {
+        let temp_dir = std::env::temp_dir().join("gitultra_worktree_failure_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let repo = Repository::init(temp_dir.join("main")).unwrap();
+        commit_files(&repo, &[], "init");
+        add_worktree(&repo, "taken", &temp_dir.join("taken"), &AddWorktreeOptions::default()).unwrap();
+
+        let options = AddWorktreeOptions {
+            branch: Some("feature".to_string()),
+            create_branch: true,
+            ..Default::default()
+        };
+        assert!(add_worktree(&repo, "taken", &temp_dir.join("other"), &options).is_err());
+        assert!(repo.find_branch("feature", BranchType::Local).is_err());
+
+        let options = AddWorktreeOptions {
+            create_branch: true,
+            ..Default::default()
+        };
+        assert!(add_worktree(&repo, "unnamed", &temp_dir.join("unnamed"), &options).is_err());
+        assert_eq!(list_worktrees(&repo).unwrap().len(), 1);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
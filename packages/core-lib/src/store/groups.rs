@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
+
+use super::repos::{self, RegistryError, RepoRecord, RepoRegistry, GITULTRA_TAURI_STORE};
+
+const GITULTRA_REPO_GROUPS: &str = "gitultra-repo-groups";
+
+pub const GROUPS_SCHEMA_VERSION: u32 = 1;
+
+/// A named set of registered repositories, e.g. "payments stack".
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct RepoGroup {
+    pub id: Uuid,
+    pub name: String,
+    /// Ids of [`RepoRecord`]s in display order.
+    pub repos: Vec<Uuid>,
+    pub created_at: i64,
+}
+
+impl RepoGroup {
+    /// Resolves the members against `registry`, skipping repositories that were removed since.
+    pub fn members(&self, registry: &RepoRegistry) -> Vec<RepoRecord> {
+        self.repos.iter().filter_map(|id| registry.find(*id).cloned()).collect()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct RepoGroups {
+    pub version: u32,
+    pub groups: Vec<RepoGroup>,
+}
+
+impl Default for RepoGroups {
+    fn default() -> Self {
+        Self {
+            version: GROUPS_SCHEMA_VERSION,
+            groups: Vec::new(),
+        }
+    }
+}
+
+pub fn load_groups<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<RepoGroups, RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    match store.get(GITULTRA_REPO_GROUPS) {
+        Some(value) => {
+            let groups: RepoGroups = serde_json::from_value(value)?;
+            if groups.version > GROUPS_SCHEMA_VERSION {
+                return Err(RegistryError::UnsupportedVersion(groups.version));
+            }
+            Ok(groups)
+        }
+        None => Ok(RepoGroups::default()),
+    }
+}
+
+fn save_groups<T: tauri::Runtime>(app: &AppHandle<T>, groups: &RepoGroups) -> Result<(), RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    store.set(GITULTRA_REPO_GROUPS, serde_json::to_value(groups)?);
+    Ok(())
+}
+
+pub fn get_groups<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<Vec<RepoGroup>, RegistryError> {
+    Ok(load_groups(app)?.groups)
+}
+
+pub fn get_group<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<RepoGroup, RegistryError> {
+    load_groups(app)?
+        .groups
+        .into_iter()
+        .find(|g| g.id == id)
+        .ok_or_else(|| RegistryError::NotFound(id.to_string()))
+}
+
+pub fn create_group<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    name: String,
+    repos: Vec<Uuid>,
+) -> Result<RepoGroup, RegistryError> {
+    let registry = repos::load_registry(app)?;
+    if let Some(missing) = repos.iter().find(|id| registry.find(**id).is_none()) {
+        return Err(RegistryError::NotFound(missing.to_string()));
+    }
+
+    let group = RepoGroup {
+        id: Uuid::new_v4(),
+        name,
+        repos: dedup(repos),
+        created_at: repos::now(),
+    };
+    let mut groups = load_groups(app)?;
+    groups.groups.push(group.clone());
+    save_groups(app, &groups)?;
+    info!("Created repo group {} ({})", group.name, group.id);
+    Ok(group)
+}
+
+pub fn rename_group<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid, name: String) -> Result<RepoGroup, RegistryError> {
+    update_group(app, id, |group| group.name = name)
+}
+
+/// Replaces the members of a group, keeping the given order.
+pub fn set_group_repos<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    id: Uuid,
+    repos: Vec<Uuid>,
+) -> Result<RepoGroup, RegistryError> {
+    let registry = repos::load_registry(app)?;
+    if let Some(missing) = repos.iter().find(|id| registry.find(**id).is_none()) {
+        return Err(RegistryError::NotFound(missing.to_string()));
+    }
+    update_group(app, id, |group| group.repos = dedup(repos))
+}
+
+pub fn delete_group<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<Option<RepoGroup>, RegistryError> {
+    let mut groups = load_groups(app)?;
+    let Some(idx) = groups.groups.iter().position(|g| g.id == id) else {
+        return Ok(None);
+    };
+    let removed = groups.groups.remove(idx);
+    save_groups(app, &groups)?;
+    Ok(Some(removed))
+}
+
+/// Resolves the members of a group against the registry, skipping repositories that were removed since.
+pub fn group_members<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<Vec<RepoRecord>, RegistryError> {
+    let group = get_group(app, id)?;
+    Ok(group.members(&repos::load_registry(app)?))
+}
+
+fn update_group<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    id: Uuid,
+    f: impl FnOnce(&mut RepoGroup),
+) -> Result<RepoGroup, RegistryError> {
+    let mut groups = load_groups(app)?;
+    let group = groups
+        .groups
+        .iter_mut()
+        .find(|g| g.id == id)
+        .ok_or_else(|| RegistryError::NotFound(id.to_string()))?;
+    f(group);
+    let group = group.clone();
+    save_groups(app, &groups)?;
+    Ok(group)
+}
+
+fn dedup(mut repos: Vec<Uuid>) -> Vec<Uuid> {
+    let mut seen = HashSet::new();
+    repos.retain(|id| seen.insert(*id));
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_dedup_keeps_first_occurrence() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(dedup(vec![a, b, a, b]), vec![a, b]);
+    }
+
+    #[test]
+    fn test_members_follow_group_order_and_skip_removed_repos() {
+        let mut registry = RepoRegistry::default();
+        let app = registry.add(Path::new("/src/app")).id;
+        let lib = registry.add(Path::new("/src/lib")).id;
+        let removed = registry.add(Path::new("/src/old")).id;
+        registry.remove(removed);
+        let group = RepoGroup {
+            id: Uuid::new_v4(),
+            name: "stack".to_string(),
+            repos: vec![lib, removed, app],
+            created_at: 0,
+        };
+
+        let members = group.members(&registry);
+
+        assert_eq!(members.iter().map(|r| r.id).collect::<Vec<_>>(), vec![lib, app]);
+    }
+}
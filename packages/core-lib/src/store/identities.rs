@@ -0,0 +1,354 @@
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+use log::info;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
+
+use super::repos::{self, RegistryError, GITULTRA_TAURI_STORE};
+use crate::git::commit::CommitIdentity;
+use crate::git::config::{self, CommonSetting, ConfigError, ConfigScope};
+
+const GITULTRA_IDENTITIES: &str = "gitultra-identities";
+
+pub const IDENTITIES_SCHEMA_VERSION: u32 = 1;
+
+/// A named author identity, e.g. "Work" or "Personal".
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct Identity {
+    pub id: Uuid,
+    pub label: String,
+    pub name: String,
+    pub email: String,
+    /// Written to `user.signingkey`, in the format `gpg.format` expects.
+    pub signing_key: Option<String>,
+    /// Private key used for SSH remotes, written to `core.sshCommand`.
+    pub ssh_key: Option<PathBuf>,
+    pub created_at: i64,
+}
+
+impl Identity {
+    pub fn commit_identity(&self) -> CommitIdentity {
+        CommitIdentity {
+            name: self.name.clone(),
+            email: self.email.clone(),
+            signing_key: self.signing_key.clone(),
+        }
+    }
+}
+
+/// User editable fields of an [`Identity`].
+#[derive(Deserialize, Serialize, Debug, Clone, Type)]
+pub struct IdentityFields {
+    pub label: String,
+    pub name: String,
+    pub email: String,
+    pub signing_key: Option<String>,
+    pub ssh_key: Option<PathBuf>,
+}
+
+/// What a rule matches a repository by.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+#[serde(tag = "kind", content = "value")]
+pub enum IdentityMatch {
+    /// The repository lives in this directory or below.
+    PathPrefix(PathBuf),
+    /// A remote points at this host, e.g. `github.com`, or at a path below it when given as `github.com/acme`.
+    RemoteHost(String),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct IdentityRule {
+    pub identity: Uuid,
+    pub matcher: IdentityMatch,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct IdentityProfiles {
+    pub version: u32,
+    pub identities: Vec<Identity>,
+    /// Checked in order, the first matching rule picks the identity.
+    pub rules: Vec<IdentityRule>,
+}
+
+impl Default for IdentityProfiles {
+    fn default() -> Self {
+        Self {
+            version: IDENTITIES_SCHEMA_VERSION,
+            identities: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl IdentityProfiles {
+    pub fn find(&self, id: Uuid) -> Option<&Identity> {
+        self.identities.iter().find(|i| i.id == id)
+    }
+
+    /// The identity of the first rule matching a repository at `path` with the given remote URLs.
+    pub fn resolve(&self, path: &Path, remote_urls: &[String]) -> Option<&Identity> {
+        let path = repos::canonical_path(path);
+        let remotes: Vec<String> = remote_urls.iter().filter_map(|url| remote_location(url)).collect();
+        self.rules
+            .iter()
+            .find(|rule| match &rule.matcher {
+                IdentityMatch::PathPrefix(prefix) => path.starts_with(prefix),
+                IdentityMatch::RemoteHost(host) => {
+                    let host = host.trim_end_matches('/').to_lowercase();
+                    remotes
+                        .iter()
+                        .any(|remote| remote == &host || remote.starts_with(&format!("{}/", host)))
+                }
+            })
+            .and_then(|rule| self.find(rule.identity))
+    }
+}
+
+pub fn load_identities<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<IdentityProfiles, RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    match store.get(GITULTRA_IDENTITIES) {
+        Some(value) => {
+            let profiles: IdentityProfiles = serde_json::from_value(value)?;
+            if profiles.version > IDENTITIES_SCHEMA_VERSION {
+                return Err(RegistryError::UnsupportedVersion(profiles.version));
+            }
+            Ok(profiles)
+        }
+        None => Ok(IdentityProfiles::default()),
+    }
+}
+
+fn save_identities<T: tauri::Runtime>(app: &AppHandle<T>, profiles: &IdentityProfiles) -> Result<(), RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    store.set(GITULTRA_IDENTITIES, serde_json::to_value(profiles)?);
+    Ok(())
+}
+
+pub fn create_identity<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    fields: IdentityFields,
+) -> Result<Identity, RegistryError> {
+    let identity = Identity {
+        id: Uuid::new_v4(),
+        label: fields.label,
+        name: fields.name,
+        email: fields.email,
+        signing_key: fields.signing_key,
+        ssh_key: fields.ssh_key,
+        created_at: repos::now(),
+    };
+    let mut profiles = load_identities(app)?;
+    profiles.identities.push(identity.clone());
+    save_identities(app, &profiles)?;
+    info!("Created identity {} ({})", identity.label, identity.id);
+    Ok(identity)
+}
+
+pub fn update_identity<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    id: Uuid,
+    fields: IdentityFields,
+) -> Result<Identity, RegistryError> {
+    let mut profiles = load_identities(app)?;
+    let identity = profiles
+        .identities
+        .iter_mut()
+        .find(|i| i.id == id)
+        .ok_or_else(|| RegistryError::NotFound(id.to_string()))?;
+    identity.label = fields.label;
+    identity.name = fields.name;
+    identity.email = fields.email;
+    identity.signing_key = fields.signing_key;
+    identity.ssh_key = fields.ssh_key;
+    let identity = identity.clone();
+    save_identities(app, &profiles)?;
+    Ok(identity)
+}
+
+/// Deletes the identity and the rules that select it.
+pub fn delete_identity<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<Option<Identity>, RegistryError> {
+    let mut profiles = load_identities(app)?;
+    let Some(idx) = profiles.identities.iter().position(|i| i.id == id) else {
+        return Ok(None);
+    };
+    let removed = profiles.identities.remove(idx);
+    profiles.rules.retain(|rule| rule.identity != id);
+    save_identities(app, &profiles)?;
+    Ok(Some(removed))
+}
+
+/// Replaces the rules, keeping the given order.
+pub fn set_identity_rules<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    rules: Vec<IdentityRule>,
+) -> Result<Vec<IdentityRule>, RegistryError> {
+    let mut profiles = load_identities(app)?;
+    if let Some(missing) = rules.iter().find(|rule| profiles.find(rule.identity).is_none()) {
+        return Err(RegistryError::NotFound(missing.identity.to_string()));
+    }
+    profiles.rules = rules
+        .into_iter()
+        .map(|rule| match rule.matcher {
+            IdentityMatch::PathPrefix(prefix) => IdentityRule {
+                matcher: IdentityMatch::PathPrefix(repos::canonical_path(&prefix)),
+                ..rule
+            },
+            IdentityMatch::RemoteHost(_) => rule,
+        })
+        .collect();
+    save_identities(app, &profiles)?;
+    Ok(profiles.rules)
+}
+
+/// The identity the rules pick for `repo`, if any.
+pub fn identity_for_repo<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    repo: &Repository,
+) -> Result<Option<Identity>, RegistryError> {
+    let profiles = load_identities(app)?;
+    if profiles.rules.is_empty() {
+        return Ok(None);
+    }
+    let path = repo.workdir().unwrap_or(repo.path());
+    Ok(profiles.resolve(path, &remote_urls(repo)).cloned())
+}
+
+/// Writes the identity to the repository's local config, so command line git uses it too.
+///
+/// The signing key and SSH command are only written when the identity has them, existing values are kept otherwise.
+pub fn write_identity_to_config(repo: &Repository, identity: &Identity) -> Result<(), ConfigError> {
+    let repo = Some(repo);
+    config::set_common(
+        repo,
+        ConfigScope::Local,
+        &CommonSetting::UserName(identity.name.clone()),
+    )?;
+    config::set_common(
+        repo,
+        ConfigScope::Local,
+        &CommonSetting::UserEmail(identity.email.clone()),
+    )?;
+    if let Some(key) = &identity.signing_key {
+        config::set_value(repo, ConfigScope::Local, "user.signingkey", key)?;
+    }
+    if let Some(key) = &identity.ssh_key {
+        let command = format!("ssh -i '{}' -o IdentitiesOnly=yes", key.display());
+        config::set_value(repo, ConfigScope::Local, "core.sshCommand", &command)?;
+    }
+    Ok(())
+}
+
+fn remote_urls(repo: &Repository) -> Vec<String> {
+    let Ok(names) = repo.remotes() else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .flatten()
+        .filter_map(|name| repo.find_remote(name).ok()?.url().map(str::to_string))
+        .collect()
+}
+
+/// Reduces a remote URL to lowercase `host/path` without user, port and `.git` suffix.
+///
+/// Handles `scheme://[user@]host[:port]/path` as well as scp-like `[user@]host:path`. Local paths yield `None`.
+fn remote_location(url: &str) -> Option<String> {
+    let (host, path) = match url.split_once("://") {
+        Some((_, rest)) => {
+            let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+            (host.split(':').next().unwrap_or(host), path)
+        }
+        None => {
+            let (authority, path) = url.split_once(':')?;
+            if authority.contains('/') {
+                return None;
+            }
+            (authority.rsplit_once('@').map_or(authority, |(_, host)| host), path)
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    Some(format!("{}/{}", host, path).trim_end_matches('/').to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(label: &str) -> Identity {
+        Identity {
+            id: Uuid::new_v4(),
+            label: label.to_string(),
+            name: "Jane".to_string(),
+            email: format!("jane@{}.example", label),
+            signing_key: None,
+            ssh_key: None,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_remote_location() {
+        assert_eq!(
+            remote_location("git@github.com:Acme/app.git").as_deref(),
+            Some("github.com/acme/app")
+        );
+        assert_eq!(
+            remote_location("ssh://git@gitlab.acme.com:2222/team/app").as_deref(),
+            Some("gitlab.acme.com/team/app")
+        );
+        assert_eq!(
+            remote_location("https://user@github.com/jane/dotfiles/").as_deref(),
+            Some("github.com/jane/dotfiles")
+        );
+        assert_eq!(remote_location("/srv/git/app.git"), None);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let work = identity("work");
+        let personal = identity("personal");
+        let profiles = IdentityProfiles {
+            version: IDENTITIES_SCHEMA_VERSION,
+            rules: vec![
+                IdentityRule {
+                    identity: work.id,
+                    matcher: IdentityMatch::RemoteHost("github.com/acme".to_string()),
+                },
+                IdentityRule {
+                    identity: personal.id,
+                    matcher: IdentityMatch::PathPrefix(PathBuf::from("/src/personal")),
+                },
+                IdentityRule {
+                    identity: work.id,
+                    matcher: IdentityMatch::RemoteHost("gitlab.acme.com".to_string()),
+                },
+            ],
+            identities: vec![work.clone(), personal.clone()],
+        };
+
+        let acme = vec!["git@github.com:acme/app.git".to_string()];
+        let own = vec!["git@github.com:jane/app.git".to_string()];
+        assert_eq!(profiles.resolve(Path::new("/src/personal/app"), &acme), Some(&work));
+        assert_eq!(profiles.resolve(Path::new("/src/personal/app"), &own), Some(&personal));
+        assert_eq!(profiles.resolve(Path::new("/src/personal-old/app"), &own), None);
+        assert_eq!(
+            profiles.resolve(Path::new("/src/app"), &["https://gitlab.acme.com/team/app".to_string()]),
+            Some(&work)
+        );
+    }
+}
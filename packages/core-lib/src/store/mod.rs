@@ -1 +1,4 @@
-pub mod repos;
\ No newline at end of file
+pub mod groups;
+pub mod identities;
+pub mod repos;
+pub mod settings;
\ No newline at end of file
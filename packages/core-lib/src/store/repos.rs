@@ -1,65 +1,469 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use log::info;
-use tauri::App;
+use serde::{Deserialize, Serialize};
+use specta::Type;
 use tauri::AppHandle;
-use tauri::Manager;
-use tauri::Runtime;
 use tauri_plugin_store::JsonValue;
 use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
 
-const GITULTRA_TAURI_STORE: &str = "gitultra-tauri-store";
+pub(crate) const GITULTRA_TAURI_STORE: &str = "gitultra-tauri-store";
 const GITULTRA_LOADED_REPOS: &str = "gitultra-loaded-repos";
 
-pub fn get_repos<T: tauri::Runtime>(app: &AppHandle<T>) -> Option<Vec<JsonValue>> {
-    let store = app
-        .get_store(GITULTRA_TAURI_STORE)
-        .expect("Store should already be loaded or created");
+/// Current layout of the registry stored under [`GITULTRA_LOADED_REPOS`].
+///
+/// Version 0 is the legacy format: a bare JSON array of path strings.
+pub const REGISTRY_SCHEMA_VERSION: u32 = 1;
+
+/// A repository known to GitUltra.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct RepoRecord {
+    pub id: Uuid,
+    /// Canonicalized path of the repository working directory.
+    pub path: PathBuf,
+    pub name: String,
+    /// Unix timestamp (seconds) when the repository was added.
+    pub added_at: i64,
+    /// Unix timestamp (seconds) when the repository was last opened.
+    pub last_opened: Option<i64>,
+    #[serde(default)]
+    pub pinned: bool,
+    pub color: Option<String>,
+    pub avatar: Option<String>,
+    /// For linked worktrees, the id of the main repository they are grouped with.
+    #[serde(default)]
+    pub main_repo: Option<Uuid>,
+}
+
+impl RepoRecord {
+    pub fn new(path: &Path) -> Self {
+        let path = canonical_path(path);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+
+        Self {
+            id: Uuid::new_v4(),
+            path,
+            name,
+            added_at: now(),
+            last_opened: None,
+            pinned: false,
+            color: None,
+            avatar: None,
+            main_repo: None,
+        }
+    }
+}
+
+/// User editable fields of a [`RepoRecord`]. `None` or a missing field leaves the field untouched.
+///
+/// `color` and `avatar` are cleared with `Some(None)`, sent as `null`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Type)]
+pub struct RepoRecordUpdate {
+    pub name: Option<String>,
+    pub pinned: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    pub color: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub avatar: Option<Option<String>>,
+}
+
+impl RepoRecordUpdate {
+    pub fn apply(self, record: &mut RepoRecord) {
+        if let Some(name) = self.name {
+            record.name = name;
+        }
+        if let Some(pinned) = self.pinned {
+            record.pinned = pinned;
+        }
+        if let Some(color) = self.color {
+            record.color = color;
+        }
+        if let Some(avatar) = self.avatar {
+            record.avatar = avatar;
+        }
+    }
+}
+
+/// Tells a field sent as `null` (`Some(None)`) from a missing one (`None`, through `#[serde(default)]`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct RepoRegistry {
+    pub version: u32,
+    pub repos: Vec<RepoRecord>,
+}
+
+impl Default for RepoRegistry {
+    fn default() -> Self {
+        Self {
+            version: REGISTRY_SCHEMA_VERSION,
+            repos: Vec::new(),
+        }
+    }
+}
+
+impl RepoRegistry {
+    /// Parses a stored registry value, migrating older layouts.
+    ///
+    /// Returns the registry and whether a migration took place, so the caller knows to persist it.
+    pub fn from_json(value: JsonValue) -> Result<(Self, bool), RegistryError> {
+        match value {
+            JsonValue::Array(paths) => {
+                let mut registry = Self::default();
+                for path in paths {
+                    match path {
+                        JsonValue::String(path) => {
+                            registry.add(Path::new(&path));
+                        }
+                        unexpected_type => return Err(RegistryError::InvalidFormat(unexpected_type.to_string())),
+                    }
+                }
+                Ok((registry, true))
+            }
+            JsonValue::Object(_) => {
+                let registry: Self = serde_json::from_value(value)?;
+                if registry.version > REGISTRY_SCHEMA_VERSION {
+                    return Err(RegistryError::UnsupportedVersion(registry.version));
+                }
+                let migrated = registry.version != REGISTRY_SCHEMA_VERSION;
+                Ok((
+                    Self {
+                        version: REGISTRY_SCHEMA_VERSION,
+                        ..registry
+                    },
+                    migrated,
+                ))
+            }
+            unexpected_type => Err(RegistryError::InvalidFormat(unexpected_type.to_string())),
+        }
+    }
+
+    pub fn find_by_path(&self, path: &Path) -> Option<&RepoRecord> {
+        let path = canonical_path(path);
+        self.repos.iter().find(|r| r.path == path)
+    }
 
-    if let Some(loaded_repos) = store.get(GITULTRA_LOADED_REPOS) {
-        let stored_repos_arr = match loaded_repos {
-            JsonValue::Array(arr) => arr,
-            unexpected_type => panic!("Invalid value type: {}", unexpected_type),
+    pub fn find(&self, id: Uuid) -> Option<&RepoRecord> {
+        self.repos.iter().find(|r| r.id == id)
+    }
+
+    /// Adds the repository at `path`, returning the existing record if it is already registered.
+    pub fn add(&mut self, path: &Path) -> &RepoRecord {
+        let record = RepoRecord::new(path);
+        let idx = match self.repos.iter().position(|r| r.path == record.path) {
+            Some(idx) => idx,
+            None => {
+                self.repos.push(record);
+                self.repos.len() - 1
+            }
         };
-        Some(stored_repos_arr)
-    } else {
-        None
+        &self.repos[idx]
+    }
+
+    /// Adds the linked worktree at `path` grouped with the main repository at `main`, registering both as needed.
+    pub fn add_worktree(&mut self, main: &Path, path: &Path) -> &RepoRecord {
+        let main_id = self.add(main).id;
+        let id = self.add(path).id;
+        let record = self.repos.iter_mut().find(|r| r.id == id).unwrap();
+        record.main_repo = Some(main_id);
+        record
+    }
+
+    /// The linked worktrees grouped with the repository `id`.
+    pub fn worktrees_of(&self, id: Uuid) -> impl Iterator<Item = &RepoRecord> {
+        self.repos.iter().filter(move |r| r.main_repo == Some(id))
+    }
+
+    /// The linked worktree at `path` grouped with the repository `main_id`.
+    ///
+    /// A worktree whose directory is gone is matched through its parent directory, which still resolves the same
+    /// symlinks as when the worktree was registered.
+    pub fn find_worktree(&self, main_id: Uuid, path: &Path) -> Option<&RepoRecord> {
+        let resolved = match (path.exists(), path.parent(), path.file_name()) {
+            (false, Some(parent), Some(name)) => canonical_path(parent).join(name),
+            _ => canonical_path(path),
+        };
+        self.worktrees_of(main_id).find(|r| r.path == resolved)
+    }
+
+    /// Removes the repository registered at exactly `path`.
+    pub fn remove_path(&mut self, path: &Path) -> Option<RepoRecord> {
+        let path = canonical_path(path);
+        let id = self.repos.iter().find(|r| r.path == path)?.id;
+        self.remove(id)
+    }
+
+    /// Removes the repository `id`. Its worktrees stay registered on their own.
+    pub fn remove(&mut self, id: Uuid) -> Option<RepoRecord> {
+        let idx = self.repos.iter().position(|r| r.id == id)?;
+        for record in self.repos.iter_mut().filter(|r| r.main_repo == Some(id)) {
+            record.main_repo = None;
+        }
+        Some(self.repos.remove(idx))
     }
 }
 
-pub fn remove_repo<T: tauri::Runtime>(app: &AppHandle<T>, path: &Path) {
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("Invalid registry format: {0}")]
+    InvalidFormat(String),
+    #[error("Unsupported registry version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Repository not found: {0}")]
+    NotFound(String),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Loads the registry from the store, migrating and persisting older formats.
+pub fn load_registry<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<RepoRegistry, RegistryError> {
     let store = app
         .get_store(GITULTRA_TAURI_STORE)
         .expect("Store should already be loaded or created");
 
-    if let Some(mut loaded_repos) = get_repos(app) {
-        loaded_repos.retain(|s| !s.as_str().unwrap().contains(path.to_str().unwrap()));
-        store.set(GITULTRA_LOADED_REPOS, JsonValue::Array(loaded_repos));
+    let Some(value) = store.get(GITULTRA_LOADED_REPOS) else {
+        return Ok(RepoRegistry::default());
+    };
+
+    let (registry, migrated) = RepoRegistry::from_json(value)?;
+    if migrated {
+        info!("Migrated repo registry to version {}", REGISTRY_SCHEMA_VERSION);
+        save_registry(app, &registry)?;
     }
+    Ok(registry)
 }
 
-pub fn add_repo<T: tauri::Runtime>(app: &AppHandle<T>, path: &Path) -> String {
+pub fn save_registry<T: tauri::Runtime>(app: &AppHandle<T>, registry: &RepoRegistry) -> Result<(), RegistryError> {
     let store = app
         .get_store(GITULTRA_TAURI_STORE)
         .expect("Store should already be loaded or created");
 
-    if let Some(loaded_repos) = store.get(GITULTRA_LOADED_REPOS) {
-        let mut stored_repos_obj = match loaded_repos {
-            JsonValue::Array(arr) => arr,
-            unexpected_type => panic!("Invalid value type: {}", unexpected_type),
-        };
-        stored_repos_obj.push(JsonValue::String(path.to_str().unwrap().to_string()));
-        stored_repos_obj.dedup();
-        info!("stored_repos_obj: {:?}", stored_repos_obj);
-        store.set(GITULTRA_LOADED_REPOS, JsonValue::Array(stored_repos_obj));
-        path.to_str().unwrap().to_string()
-    } else {
-        // create key-value with json array
-        store.set(
-            GITULTRA_LOADED_REPOS,
-            // need to convert this ugly shit
-            JsonValue::Array(vec![JsonValue::String(path.to_str().unwrap().to_string())]),
-        );
-        path.to_str().unwrap().to_string()
+    store.set(GITULTRA_LOADED_REPOS, serde_json::to_value(registry)?);
+    Ok(())
+}
+
+pub fn get_repos<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<Vec<RepoRecord>, RegistryError> {
+    Ok(load_registry(app)?.repos)
+}
+
+pub fn get_repo<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<RepoRecord, RegistryError> {
+    load_registry(app)?
+        .find(id)
+        .cloned()
+        .ok_or_else(|| RegistryError::NotFound(id.to_string()))
+}
+
+pub fn find_repo_by_path<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    path: &Path,
+) -> Result<Option<RepoRecord>, RegistryError> {
+    Ok(load_registry(app)?.find_by_path(path).cloned())
+}
+
+pub fn remove_repo<T: tauri::Runtime>(app: &AppHandle<T>, path: &Path) -> Result<Option<RepoRecord>, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let removed = registry.remove_path(path);
+    if removed.is_some() {
+        save_registry(app, &registry)?;
+    }
+    Ok(removed)
+}
+
+pub fn remove_repo_id<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<Option<RepoRecord>, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let removed = registry.remove(id);
+    if removed.is_some() {
+        save_registry(app, &registry)?;
+    }
+    Ok(removed)
+}
+
+pub fn add_repo<T: tauri::Runtime>(app: &AppHandle<T>, path: &Path) -> Result<RepoRecord, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let record = registry.add(path).clone();
+    info!("Registered repo {} at {:?}", record.id, record.path);
+    save_registry(app, &registry)?;
+    Ok(record)
+}
+
+/// Registers the linked worktree at `path`, grouped with its main repository at `main`.
+pub fn add_worktree<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    main: &Path,
+    path: &Path,
+) -> Result<RepoRecord, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let record = registry.add_worktree(main, path).clone();
+    info!("Registered worktree {} at {:?}", record.id, record.path);
+    save_registry(app, &registry)?;
+    Ok(record)
+}
+
+/// Registers several repositories at once, e.g. the results of a discovery scan.
+pub fn add_repos<T: tauri::Runtime>(app: &AppHandle<T>, paths: &[PathBuf]) -> Result<Vec<RepoRecord>, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let records = paths.iter().map(|path| registry.add(path).clone()).collect();
+    save_registry(app, &registry)?;
+    Ok(records)
+}
+
+/// Marks the repository as opened now.
+pub fn touch_repo<T: tauri::Runtime>(app: &AppHandle<T>, id: Uuid) -> Result<RepoRecord, RegistryError> {
+    update_record(app, id, |record| record.last_opened = Some(now()))
+}
+
+pub fn update_repo<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    id: Uuid,
+    update: RepoRecordUpdate,
+) -> Result<RepoRecord, RegistryError> {
+    update_record(app, id, |record| update.apply(record))
+}
+
+fn update_record<T: tauri::Runtime>(
+    app: &AppHandle<T>,
+    id: Uuid,
+    f: impl FnOnce(&mut RepoRecord),
+) -> Result<RepoRecord, RegistryError> {
+    let mut registry = load_registry(app)?;
+    let record = registry
+        .repos
+        .iter_mut()
+        .find(|r| r.id == id)
+        .ok_or_else(|| RegistryError::NotFound(id.to_string()))?;
+    f(record);
+    let record = record.clone();
+    save_registry(app, &registry)?;
+    Ok(record)
+}
+
+/// Resolves symlinks and relative components, falling back to the given path if it no longer exists.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Current Unix timestamp in seconds.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrates_legacy_array() {
+        let legacy = serde_json::json!(["/src/app", "/src/app-legacy", "/src/app"]);
+
+        let (registry, migrated) = RepoRegistry::from_json(legacy).unwrap();
+
+        assert!(migrated);
+        assert_eq!(registry.version, REGISTRY_SCHEMA_VERSION);
+        assert_eq!(registry.repos.len(), 2);
+        assert_eq!(registry.repos[0].name, "app");
+        assert_eq!(registry.repos[1].name, "app-legacy");
+    }
+
+    #[test]
+    fn test_current_version_roundtrip() {
+        let mut registry = RepoRegistry::default();
+        registry.add(Path::new("/src/app"));
+
+        let (loaded, migrated) = RepoRegistry::from_json(serde_json::to_value(&registry).unwrap()).unwrap();
+
+        assert!(!migrated);
+        assert_eq!(loaded, registry);
+    }
+
+    #[test]
+    fn test_remove_matches_exact_path() {
+        let mut registry = RepoRegistry::default();
+        registry.add(Path::new("/src/app"));
+        registry.add(Path::new("/src/app-legacy"));
+
+        let removed = registry.remove_path(Path::new("/src/app")).unwrap();
+
+        assert_eq!(removed.path, PathBuf::from("/src/app"));
+        assert_eq!(registry.repos.len(), 1);
+        assert_eq!(registry.repos[0].path, PathBuf::from("/src/app-legacy"));
+        assert!(registry.remove_path(Path::new("/src")).is_none());
+    }
+
+    #[test]
+    fn test_worktrees_are_grouped_with_main_repo() {
+        let mut registry = RepoRegistry::default();
+        let worktree = registry
+            .add_worktree(Path::new("/src/app"), Path::new("/src/app-feature"))
+            .clone();
+        let main = registry.find_by_path(Path::new("/src/app")).unwrap().id;
+
+        assert_eq!(worktree.main_repo, Some(main));
+        assert_eq!(registry.worktrees_of(main).count(), 1);
+
+        registry.remove(main);
+        assert_eq!(registry.repos.len(), 1);
+        assert_eq!(registry.repos[0].main_repo, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_deleted_worktree_through_symlink() {
+        let dir = std::env::temp_dir().join("gitultra_registry_worktree_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("real/main")).unwrap();
+        std::fs::create_dir_all(dir.join("real/feature")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        let mut registry = RepoRegistry::default();
+        let id = registry.add_worktree(&dir.join("link/main"), &dir.join("link/feature")).id;
+        let main = registry.find_by_path(&dir.join("real/main")).unwrap().id;
+        assert_eq!(registry.find_worktree(main, &dir.join("link/feature")).unwrap().id, id);
+
+        std::fs::remove_dir(dir.join("real/feature")).unwrap();
+        assert_eq!(registry.find_worktree(main, &dir.join("link/feature")).unwrap().id, id);
+        assert!(registry.find_worktree(main, &dir.join("link/other")).is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_add_keeps_existing_identity() {
+        let mut registry = RepoRegistry::default();
+        let id = registry.add(Path::new("/src/app")).id;
+
+        assert_eq!(registry.add(Path::new("/src/app")).id, id);
+        assert_eq!(registry.repos.len(), 1);
+    }
+
+    #[test]
+    fn test_update_sets_keeps_and_clears_fields() {
+        let mut record = RepoRecord::new(Path::new("/src/app"));
+        let update: RepoRecordUpdate = serde_json::from_value(serde_json::json!({"color": "#ff0000"})).unwrap();
+        update.apply(&mut record);
+        assert_eq!(record.color.as_deref(), Some("#ff0000"));
+
+        let update: RepoRecordUpdate = serde_json::from_value(serde_json::json!({"avatar": "a.png"})).unwrap();
+        update.apply(&mut record);
+        assert_eq!(record.color.as_deref(), Some("#ff0000"));
+        assert_eq!(record.avatar.as_deref(), Some("a.png"));
+
+        let update: RepoRecordUpdate = serde_json::from_value(serde_json::json!({"color": null})).unwrap();
+        update.apply(&mut record);
+        assert_eq!(record.color, None);
+        assert_eq!(record.avatar.as_deref(), Some("a.png"));
     }
 }
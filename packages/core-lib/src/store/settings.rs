@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
+
+use crate::git::signature::GpgPrograms;
+
+use super::repos::{RegistryError, GITULTRA_TAURI_STORE};
+
+const GITULTRA_SETTINGS: &str = "gitultra-settings";
+
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
+/// Application settings persisted in the Tauri store. Missing fields fall back to their defaults.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub auto_fetch: AutoFetchSettings,
+    /// Programs used to verify and create commit and tag signatures.
+    pub gpg: GpgPrograms,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_SCHEMA_VERSION,
+            auto_fetch: AutoFetchSettings::default(),
+            gpg: GpgPrograms::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+#[serde(default)]
+pub struct AutoFetchSettings {
+    pub enabled: bool,
+    /// Interval between two fetches of the same repository, in seconds.
+    pub interval_secs: u32,
+    pub pause_on_battery: bool,
+    pub pause_on_metered: bool,
+    /// Per repository overrides, keyed by [`RepoRecord::id`](super::repos::RepoRecord::id).
+    pub overrides: HashMap<Uuid, AutoFetchOverride>,
+}
+
+impl Default for AutoFetchSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 10 * 60,
+            pause_on_battery: true,
+            pause_on_metered: true,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Type)]
+pub struct AutoFetchOverride {
+    pub enabled: bool,
+    /// Replaces [`AutoFetchSettings::interval_secs`] for this repository.
+    pub interval_secs: Option<u32>,
+}
+
+impl AutoFetchSettings {
+    /// Fetch interval for a repository, `None` if auto-fetch is off for it.
+    pub fn interval_for(&self, repo: Uuid) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+        match self.overrides.get(&repo) {
+            Some(o) if !o.enabled => None,
+            Some(o) => Some(o.interval_secs.unwrap_or(self.interval_secs)),
+            None => Some(self.interval_secs),
+        }
+    }
+}
+
+pub fn load_settings<T: tauri::Runtime>(app: &AppHandle<T>) -> Result<Settings, RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    match store.get(GITULTRA_SETTINGS) {
+        Some(value) => {
+            let settings: Settings = serde_json::from_value(value)?;
+            if settings.version > SETTINGS_SCHEMA_VERSION {
+                return Err(RegistryError::UnsupportedVersion(settings.version));
+            }
+            Ok(settings)
+        }
+        None => Ok(Settings::default()),
+    }
+}
+
+pub fn save_settings<T: tauri::Runtime>(app: &AppHandle<T>, settings: &Settings) -> Result<(), RegistryError> {
+    let store = app
+        .get_store(GITULTRA_TAURI_STORE)
+        .expect("Store should already be loaded or created");
+
+    store.set(GITULTRA_SETTINGS, serde_json::to_value(settings)?);
+    Ok(())
+}